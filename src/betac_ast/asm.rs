@@ -0,0 +1,65 @@
+//! `@asm` blocks, which are kept as they are for the backend
+
+use super::{AstNode, Metadata, Span};
+
+/// how an operand of an `@asm` block is passed to the assembly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmDirection {
+    /// `in(reg) expr`, which the assembly only reads
+    In,
+    /// `out(reg) place`, which the assembly only writes
+    Out,
+    /// `inout(reg) place`, which the assembly reads and then writes
+    InOut,
+}
+
+impl AsmDirection {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "in" => Self::In,
+            "out" => Self::Out,
+            "inout" => Self::InOut,
+            _ => return None,
+        })
+    }
+}
+
+/// `name => in(reg) expr`, an operand of an `@asm` block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmOperand {
+    /// what the assembly calls it, like the `len` in `${len}`
+    pub name: String,
+    pub direction: AsmDirection,
+    /// where it's kept, like `reg`
+    pub class: String,
+    /// the expression it's bound to
+    pub expr: Span,
+    pub span: Span,
+}
+
+/// a piece of a line of assembly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmPiece {
+    Text(String),
+    /// `${name}`, which is replaced by the operand at this index
+    Operand(usize),
+}
+
+/// `@asm(code => in(reg) code): "mov rbx, ${code};" @end;`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmBlock {
+    pub span: Span,
+    pub operands: Vec<AsmOperand>,
+    /// every line of assembly, in order, with its placeholders resolved
+    pub lines: Vec<Vec<AsmPiece>>,
+}
+
+impl AstNode for AsmBlock {
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        None
+    }
+}
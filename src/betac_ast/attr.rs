@@ -0,0 +1,160 @@
+//! `@[Attribute]`s, and the table of every attribute the compiler knows about
+
+use crate::betac_tokenizer::token::Kw;
+
+use super::Span;
+
+/// the sorts of item an attribute can be put on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Function,
+    Object,
+    Component,
+    Trait,
+    Extension,
+    Alias,
+    Variable,
+    Pack,
+    Import,
+}
+
+impl ItemKind {
+    pub const ALL: [Self; 9] = [
+        Self::Function,
+        Self::Object,
+        Self::Component,
+        Self::Trait,
+        Self::Extension,
+        Self::Alias,
+        Self::Variable,
+        Self::Pack,
+        Self::Import,
+    ];
+
+    /// the item that `kw` starts, if it isn't a modifier like `pub`
+    pub const fn from_keyword(kw: Kw) -> Option<Self> {
+        Some(match kw {
+            Kw::Defun => Self::Function,
+            Kw::Obj => Self::Object,
+            Kw::Comp => Self::Component,
+            Kw::Trait => Self::Trait,
+            Kw::Extend => Self::Extension,
+            Kw::Alias => Self::Alias,
+            Kw::Let => Self::Variable,
+            Kw::Pack => Self::Pack,
+            Kw::Import => Self::Import,
+            _ => return None,
+        })
+    }
+
+    /// the name of the item, with an article, like "a function"
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Function => "a function",
+            Self::Object => "an object",
+            Self::Component => "a component",
+            Self::Trait => "a trait",
+            Self::Extension => "an extension",
+            Self::Alias => "an alias",
+            Self::Variable => "a variable",
+            Self::Pack => "a pack",
+            Self::Import => "an import",
+        }
+    }
+}
+
+/// what a built-in attribute takes in its parentheses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrArgs {
+    /// `@[Name]`
+    Nothing,
+    /// `@[Name]` or `@[Name("..")]`
+    OptionalString,
+    /// `@[Name]`, or `@[Name(Choice)]` with one of the choices
+    OptionalChoice(&'static [&'static str]),
+    /// `@[Name(Choice)]` with one of the choices
+    Choice(&'static [&'static str]),
+}
+
+/// every built-in attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrKind {
+    /// `@[NoReturn]`, on functions that never return
+    NoReturn,
+    /// `@[Inline]`, `@[Inline(Always)]` or `@[Inline(Never)]`
+    Inline,
+    /// `@[Cold]`, on functions that are rarely called
+    Cold,
+    /// `@[Deprecated]` or `@[Deprecated("use .. instead")]`
+    Deprecated,
+    /// `@[Test]`, on functions that are only compiled to be run as tests
+    Test,
+    /// `@[Repr(C)]`, `@[Repr(Packed)]` or `@[Repr(Transparent)]`, the layout of an object
+    Repr,
+    /// `@[Export]` or `@[Export("symbol")]`, which makes the item visible to other languages
+    Export,
+}
+
+impl AttrKind {
+    pub const ALL: [Self; 7] = [
+        Self::NoReturn,
+        Self::Inline,
+        Self::Cold,
+        Self::Deprecated,
+        Self::Test,
+        Self::Repr,
+        Self::Export,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::NoReturn => "NoReturn",
+            Self::Inline => "Inline",
+            Self::Cold => "Cold",
+            Self::Deprecated => "Deprecated",
+            Self::Test => "Test",
+            Self::Repr => "Repr",
+            Self::Export => "Export",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub const fn args(self) -> AttrArgs {
+        match self {
+            Self::NoReturn | Self::Cold | Self::Test => AttrArgs::Nothing,
+            Self::Inline => AttrArgs::OptionalChoice(&["Always", "Never"]),
+            Self::Deprecated | Self::Export => AttrArgs::OptionalString,
+            Self::Repr => AttrArgs::Choice(&["C", "Packed", "Transparent"]),
+        }
+    }
+
+    /// the items this attribute can be put on
+    pub const fn targets(self) -> &'static [ItemKind] {
+        match self {
+            Self::NoReturn | Self::Inline | Self::Cold | Self::Test => &[ItemKind::Function],
+            Self::Deprecated => &ItemKind::ALL,
+            Self::Repr => &[ItemKind::Object],
+            Self::Export => &[ItemKind::Function, ItemKind::Variable],
+        }
+    }
+}
+
+/// the argument of an attribute, like the `C` in `@[Repr(C)]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrArg {
+    /// one of the choices of `AttrArgs::Choice` or `AttrArgs::OptionalChoice`
+    Choice(String),
+    /// an unescaped string
+    Str(String),
+}
+
+/// an attribute that's been checked against the table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub kind: AttrKind,
+    pub arg: Option<AttrArg>,
+    pub span: Span,
+}
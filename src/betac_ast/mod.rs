@@ -1,3 +1,4 @@
+use attr::Attribute;
 use pproc::{Tag, Tags};
 
 use crate::{
@@ -6,6 +7,7 @@ use crate::{
         linked_list::{Link, LinkedList, Pointers},
         ptr::Ptr,
         small_vec::SmallVec,
+        source_map::FileId,
     },
 };
 
@@ -21,7 +23,9 @@ use std::{
     },
 };
 
+pub mod asm;
 pub mod assignment;
+pub mod attr;
 pub mod pproc;
 
 static SYNTAX_TREE_LISTS: LazyLock<Mutex<FxHashMap<u16, SyntaxTree>>> =
@@ -75,12 +79,23 @@ pub struct Metadata(u8);
 
 impl Metadata {
     pub const STATIC: u8 = 1 << 0;
+    /// `pub`
+    pub const PUBLIC: u8 = 1 << 1;
+    /// `pub(pack)`, which is only public inside of its pack
+    pub const PUBLIC_IN_PACK: u8 = 1 << 2;
+    pub const CONST: u8 = 1 << 3;
+    pub const EXTERN: u8 = 1 << 4;
+    pub const UNSAFE: u8 = 1 << 5;
 }
 
 pub type SyntaxTree = LinkedList<AstToken, <AstToken as Link>::Target>;
 
 pub struct AstToken {
     data: Ptr<dyn AstNode>,
+    /// the spans of the `///` doc comments right before this node
+    docs: Vec<Span>,
+    /// the `@[Attribute]`s right before this node
+    attributes: Vec<Attribute>,
     pointers: Pointers<AstToken>,
 }
 
@@ -105,17 +120,38 @@ unsafe impl Link for AstToken {
 
 impl AstToken {
     pub fn new(metadata: Ptr<dyn AstNode>) -> Pin<Rc<Self>> {
+        Self::annotated(metadata, Vec::new(), Vec::new())
+    }
+
+    /// a node with the doc comments and attributes that were right before it
+    pub fn annotated(
+        metadata: Ptr<dyn AstNode>,
+        docs: Vec<Span>,
+        attributes: Vec<Attribute>,
+    ) -> Pin<Rc<Self>> {
         unsafe {
             Pin::new_unchecked(Rc::new(Self {
                 data: metadata,
+                docs,
+                attributes,
                 pointers: Pointers::new(),
             }))
         }
     }
+
+    /// the doc comments attached to this node, in source order
+    pub fn docs(&self) -> &[Span] {
+        &self.docs
+    }
+
+    /// the attributes on this node, in source order
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
 }
 
 pub trait AstNode: fmt::Debug + Send {
-    fn start_pos(&self) -> u16 {
+    fn start_pos(&self) -> u32 {
         self.span().start_pos
     }
 
@@ -138,17 +174,32 @@ pub trait AstNode: fmt::Debug + Send {
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Span {
-    pub start_pos: u16,
-    pub len: u8,
+    pub file: FileId,
+    pub start_pos: u32,
+    pub len: u32,
     pub meta: Metadata,
 }
 
 impl Span {
     pub const DUMMY: Self = Self {
+        file: FileId::DUMMY,
         start_pos: 0,
         len: 0,
         meta: Metadata(0),
     };
+
+    pub const fn new(file: FileId, start_pos: u32, len: u32) -> Self {
+        Self {
+            file,
+            start_pos,
+            len,
+            meta: Metadata(0),
+        }
+    }
+
+    pub const fn end_pos(&self) -> u32 {
+        self.start_pos + self.len
+    }
 }
 
 impl Debug for Span {
@@ -157,6 +208,7 @@ impl Debug for Span {
             f.write_str("<dummy>")
         } else {
             f.debug_struct("Span")
+                .field("file", &self.file)
                 .field("start_pos", &self.start_pos)
                 .field("len", &self.len)
                 .field("meta", &self.meta)
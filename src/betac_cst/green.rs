@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use crate::betac_tokenizer::token::TokenKind;
+
+use super::NodeKind;
+
+/// an immutable token in the green tree, which owns its text
+///
+/// green tokens don't know where they are, so identical tokens can be shared between trees
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: TokenKind,
+    text: Box<str>,
+}
+
+impl GreenToken {
+    pub fn new(kind: TokenKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> u32 {
+        self.text.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+/// an immutable node in the green tree
+///
+/// green nodes only store their kind, their children and their total length,
+/// so an unchanged subtree can be reused as is after an edit anywhere else in the file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: NodeKind,
+    len: u32,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+        Self {
+            kind,
+            len,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// writes the text of every token under this node, in order
+    pub fn write_text(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(out),
+                GreenElement::Token(token) => out.push_str(token.text()),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> u32 {
+        match self {
+            Self::Node(node) => node.len(),
+            Self::Token(token) => token.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// builds a green tree from the top down
+///
+/// ```ignore
+/// let mut builder = GreenBuilder::new();
+/// builder.start_node(NodeKind::Root);
+/// builder.token(TokenKind::Ident, "x");
+/// builder.finish_node();
+/// let root = builder.finish();
+/// ```
+#[derive(Debug, Default)]
+pub struct GreenBuilder {
+    /// the nodes that have been started, but not finished, along with their children so far
+    stack: Vec<(NodeKind, Vec<GreenElement>)>,
+    root: Option<Arc<GreenNode>>,
+}
+
+impl GreenBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_node(&mut self, kind: NodeKind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    pub fn token(&mut self, kind: TokenKind, text: &str) {
+        self.element(GreenElement::Token(Arc::new(GreenToken::new(kind, text))));
+    }
+
+    /// adds an already built node or token, like an unchanged subtree from an older tree
+    pub fn element(&mut self, element: GreenElement) {
+        self.stack
+            .last_mut()
+            .expect("`GreenBuilder` has no open node to add to")
+            .1
+            .push(element);
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self
+            .stack
+            .pop()
+            .expect("`GreenBuilder::finish_node` called without a matching `start_node`");
+        let node = Arc::new(GreenNode::new(kind, children));
+        match self.stack.last_mut() {
+            Some((_, parent)) => parent.push(GreenElement::Node(node)),
+            None => self.root = Some(node),
+        }
+    }
+
+    /// the number of nodes that have been started, but not finished
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// returns the root node
+    /// every started node must have been finished by now
+    pub fn finish(self) -> Arc<GreenNode> {
+        assert!(self.stack.is_empty(), "`GreenBuilder` has unfinished nodes");
+        self.root.expect("`GreenBuilder` never built a root node")
+    }
+}
@@ -0,0 +1,268 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    betac_tokenizer::{
+        token::{Token, TokenKind},
+        Tokenizer,
+    },
+    betac_util::source_map::FileId,
+};
+
+use super::{
+    build, build_item,
+    green::{GreenBuilder, GreenNode},
+    red::SyntaxNode,
+    NodeKind,
+};
+
+/// replaces `start..end` (byte offsets into the old text) with `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: u32,
+    pub end: u32,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<u32>, text: impl Into<String>) -> Self {
+        Self {
+            start: range.start,
+            end: range.end,
+            text: text.into(),
+        }
+    }
+
+    /// how much the edit moves everything after it
+    pub fn delta(&self) -> i64 {
+        self.text.len() as i64 - (self.end - self.start) as i64
+    }
+
+    /// returns `text` with the edit applied
+    /// both ends of the edit must be on char boundaries
+    pub fn apply(&self, text: &str) -> String {
+        let (start, end) = (self.start as usize, self.end as usize);
+        let mut out = String::with_capacity(text.len() + self.text.len() - (end - start));
+        out.push_str(&text[..start]);
+        out.push_str(&self.text);
+        out.push_str(&text[end..]);
+        out
+    }
+}
+
+/// the result of `relex`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relexed {
+    pub tokens: Vec<Token>,
+    /// the part of the new text whose tokens were lexed again;
+    /// every token outside of it was copied from the old tokens
+    pub changed: Range<u32>,
+}
+
+/// updates `old`, the tokens of the text before `edit`, to match `new_text`
+///
+/// the tokenizer has no state between tokens, and never looks more than two chars past
+/// the char it's on. so lexing restarts two tokens before the edit, and stops as soon as
+/// a new token starts where an old one did, past the edit; every token after that is the same.
+/// only the relexed region reports diagnostics again
+pub fn relex(old: &[Token], new_text: &str, edit: &TextEdit, file: FileId) -> Relexed {
+    let delta = edit.delta();
+    let touched = old.partition_point(|token| token.start < edit.start);
+    let first = touched.saturating_sub(2);
+    let restart = old.get(first).map(|token| token.start).unwrap_or(0);
+    let edit_end = edit.start + edit.text.len() as u32;
+
+    let mut tokens = old[..first].to_vec();
+    let mut tokenizer = Tokenizer::with_file(new_text, file);
+    tokenizer.seek(restart as usize);
+    loop {
+        let token = tokenizer.advance_token();
+        if token.kind == TokenKind::Eof {
+            return Relexed {
+                tokens,
+                changed: restart..new_text.len() as u32,
+            };
+        }
+
+        if token.start >= edit_end {
+            let old_start = (token.start as i64 - delta) as u32;
+            let idx = old.partition_point(|old| old.start < old_start);
+            if old.get(idx).is_some_and(|old| old.start == old_start) {
+                tokens.extend(old[idx..].iter().map(|old| Token {
+                    start: (old.start as i64 + delta) as u32,
+                    ..*old
+                }));
+                return Relexed {
+                    tokens,
+                    changed: restart..token.start,
+                };
+            }
+        }
+        tokens.push(token);
+    }
+}
+
+/// updates `old`, the tree of the text before an edit, to match `tokens` and `new_text`
+///
+/// top-level items that end before `changed` are reused as is, and so is everything after
+/// the first item that starts past `changed` at the same place an old one did (moved by `delta`).
+/// items only depend on the tokens from where they start, so this matches `build` exactly
+pub fn reparse(
+    old: &GreenNode,
+    new_text: &str,
+    tokens: &[Token],
+    changed: Range<u32>,
+    delta: i64,
+) -> Arc<GreenNode> {
+    let children = old.children();
+    let mut builder = GreenBuilder::new();
+    builder.start_node(NodeKind::Root);
+
+    let mut reused = 0;
+    let mut offset = 0;
+    while let Some(child) = children.get(reused)
+        && offset + child.len() <= changed.start
+    {
+        builder.element(child.clone());
+        offset += child.len();
+        reused += 1;
+    }
+
+    // the old offset of `children[next]`, which is the next candidate to sync up with
+    let mut next = reused;
+    let mut next_offset = offset as i64;
+    let mut idx = tokens.partition_point(|token| token.start < offset);
+    while let Some(token) = tokens.get(idx) {
+        if token.start >= changed.end {
+            while let Some(child) = children.get(next)
+                && next_offset + delta < token.start as i64
+            {
+                next_offset += child.len() as i64;
+                next += 1;
+            }
+            if next < children.len() && next_offset + delta == token.start as i64 {
+                for child in &children[next..] {
+                    builder.element(child.clone());
+                }
+                break;
+            }
+        }
+        idx = build_item(&mut builder, new_text, tokens, idx);
+    }
+
+    builder.finish_node();
+    builder.finish()
+}
+
+/// a file that's kept up to date through small edits, like one open in an editor
+pub struct Document {
+    text: String,
+    file: FileId,
+    tokens: Vec<Token>,
+    green: Arc<GreenNode>,
+}
+
+impl Document {
+    pub fn new(text: impl Into<String>, file: FileId) -> Self {
+        let text = text.into();
+        let tokens = crate::betac_tokenizer::run_tokenizer(&text, file).collect::<Vec<_>>();
+        let green = build(&text, &tokens);
+        Self {
+            text,
+            file,
+            tokens,
+            green,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.green
+    }
+
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone(), self.file)
+    }
+
+    /// applies `edit`, and only re-lexes and re-parses the region around it
+    pub fn edit(&mut self, edit: &TextEdit) {
+        let text = edit.apply(&self.text);
+        let relexed = relex(&self.tokens, &text, edit, self.file);
+        self.green = reparse(
+            &self.green,
+            &text,
+            &relexed.tokens,
+            relexed.changed,
+            edit.delta(),
+        );
+        self.tokens = relexed.tokens;
+        self.text = text;
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_incremental_matches_full_parse() {
+    use super::green::GreenElement;
+    use crate::betac_tokenizer::run_tokenizer;
+
+    // editing the last item reuses the ones before it
+    let mut doc = Document::new("let a => 1;\nlet b => (2);\nlet c => 3;", FileId::DUMMY);
+    let old = doc.green().clone();
+    doc.edit(&TextEdit::new(35..36, "30"));
+    assert_eq!(doc.text(), "let a => 1;\nlet b => (2);\nlet c => 30;");
+    let reused = |idx: usize| match (&old.children()[idx], &doc.green().children()[idx]) {
+        (GreenElement::Node(before), GreenElement::Node(after)) => Arc::ptr_eq(before, after),
+        _ => false,
+    };
+    assert!(reused(0) && reused(2));
+    assert!(!reused(4));
+
+    // xorshift, so the test doesn't need a dependency and every run is the same
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut random = |below: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % below as u64) as usize
+    };
+
+    let fragments = [
+        "", "x", " ", "\n", "\r\n", ";", "{", "}", "(", ")", "[", "]", "@", "@[Attr]", "=>", "=",
+        ">", "let ", "defun ", "//", "///", "/*", "*/", "\"", "'", "r#\"", "\"#", "0x", "1.5e",
+        "§", "é", "\t", "pub ",
+    ];
+    let mut doc = Document::new(
+        include_str!("../../library/core/sys/unix.blp"),
+        FileId::DUMMY,
+    );
+
+    for _ in 0..500 {
+        let text = doc.text();
+        let boundary = |offset: usize| {
+            (offset..=text.len())
+                .find(|&offset| text.is_char_boundary(offset))
+                .unwrap() as u32
+        };
+        let start = boundary(random(text.len() + 1));
+        let end = boundary((start as usize + random(4)).min(text.len()));
+        let edit = TextEdit::new(start..end, fragments[random(fragments.len())]);
+
+        doc.edit(&edit);
+
+        let tokens = run_tokenizer(doc.text(), FileId::DUMMY).collect::<Vec<_>>();
+        assert_eq!(doc.tokens(), tokens, "tokens differ after {edit:?}");
+        assert_eq!(
+            **doc.green(),
+            *build(doc.text(), &tokens),
+            "trees differ after {edit:?}"
+        );
+        assert_eq!(doc.syntax().text(), doc.text());
+    }
+}
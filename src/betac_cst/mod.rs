@@ -0,0 +1,204 @@
+//! the lossless concrete syntax tree
+//!
+//! unlike the AST, the CST keeps every token, trivia included, so the source can be
+//! rebuilt from it byte for byte. this is what a formatter or IDE refactorings work on.
+//!
+//! it's split the same way as rowan and roslyn:
+//! the green tree (`green`) is immutable and position-independent, so subtrees can be shared,
+//! and the red tree (`red`) is a cheap view over it with parents and offsets
+
+pub mod green;
+pub mod incremental;
+pub mod red;
+
+use std::sync::Arc;
+
+use crate::{
+    betac_tokenizer::{
+        run_tokenizer,
+        token::{DocStyle, Token, TokenKind},
+    },
+    betac_util::source_map::FileId,
+};
+
+use green::{GreenBuilder, GreenNode};
+use red::SyntaxNode;
+
+/// the kinds of nodes in the CST; tokens keep their `TokenKind`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NodeKind {
+    /// the whole file
+    Root,
+    /// a top-level item, from its doc comments up to its closing `;` or `}`
+    Item,
+    /// a preprocessor macro, from the `@` up to its closing `;` or `:`,
+    /// or an attribute like `@[NoReturn]`
+    Preproc,
+    /// a delimited group, like `( .. )`, `[ .. ]` or `{ .. }`
+    Group,
+    /// a closing delimiter without an opening one
+    Error,
+}
+
+/// lexes and parses `text` into a CST that round-trips it exactly
+pub fn parse(text: &str, file: FileId) -> SyntaxNode {
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    SyntaxNode::new_root(build(text, &tokens), file)
+}
+
+/// builds the green tree for `text` out of its tokens
+///
+/// the tokens must cover all of `text`, which is always the case for `run_tokenizer`
+pub fn build(text: &str, tokens: &[Token]) -> Arc<GreenNode> {
+    let mut builder = GreenBuilder::new();
+    builder.start_node(NodeKind::Root);
+    let mut idx = 0;
+    while idx < tokens.len() {
+        idx = build_item(&mut builder, text, tokens, idx);
+    }
+    builder.finish_node();
+    builder.finish()
+}
+
+fn token_text<'a>(text: &'a str, token: &Token) -> &'a str {
+    &text[token.start as usize..token.end() as usize]
+}
+
+/// the delimiter that closes a group opened by `kind`
+fn closing(kind: TokenKind) -> Option<TokenKind> {
+    match kind {
+        TokenKind::LeftParen => Some(TokenKind::RightParen),
+        TokenKind::LeftBrace => Some(TokenKind::RightBrace),
+        TokenKind::LeftBracket => Some(TokenKind::RightBracket),
+        _ => None,
+    }
+}
+
+fn is_closing(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket
+    )
+}
+
+/// builds whatever starts at `tokens[idx]`, and returns the index of the next token
+///
+/// trivia between items goes straight into the root,
+/// except for `///` doc comments, which start the item they document
+pub(crate) fn build_item(
+    builder: &mut GreenBuilder,
+    text: &str,
+    tokens: &[Token],
+    idx: usize,
+) -> usize {
+    let first = tokens[idx];
+    if first.kind.is_trivia() && first.kind != TokenKind::DocComment(DocStyle::Outer) {
+        builder.token(first.kind, token_text(text, &first));
+        return idx + 1;
+    }
+    if is_closing(first.kind) {
+        builder.start_node(NodeKind::Error);
+        builder.token(first.kind, token_text(text, &first));
+        builder.finish_node();
+        return idx + 1;
+    }
+
+    let is_preproc = first.kind == TokenKind::At;
+    let is_attribute =
+        is_preproc && tokens.get(idx + 1).map(|token| token.kind) == Some(TokenKind::LeftBrace);
+    let kind = if is_preproc {
+        NodeKind::Preproc
+    } else {
+        NodeKind::Item
+    };
+    builder.start_node(kind);
+
+    // the closing delimiters of the groups that are open
+    let mut groups = Vec::new();
+    let mut idx = idx;
+    while let Some(&token) = tokens.get(idx) {
+        idx += 1;
+        if let Some(close) = closing(token.kind) {
+            builder.start_node(NodeKind::Group);
+            builder.token(token.kind, token_text(text, &token));
+            groups.push(close);
+            continue;
+        }
+        if groups.last() == Some(&token.kind) {
+            builder.token(token.kind, token_text(text, &token));
+            builder.finish_node();
+            groups.pop();
+
+            // `defun f() {}` ends at its block, and `@[Attr]` at its brackets
+            let ends_item = match token.kind {
+                TokenKind::RightBracket => !is_preproc,
+                TokenKind::RightBrace => is_attribute,
+                _ => false,
+            };
+            if groups.is_empty() && ends_item {
+                break;
+            }
+            continue;
+        }
+        if is_closing(token.kind) {
+            // a closer that doesn't match is an error, but doesn't end the item
+            builder.start_node(NodeKind::Error);
+            builder.token(token.kind, token_text(text, &token));
+            builder.finish_node();
+            continue;
+        }
+
+        builder.token(token.kind, token_text(text, &token));
+        let ends_item = match token.kind {
+            TokenKind::Semi => true,
+            TokenKind::Colon => is_preproc,
+            _ => false,
+        };
+        if groups.is_empty() && ends_item {
+            break;
+        }
+    }
+
+    // close anything left open at the end of the file
+    for _ in 0..groups.len() {
+        builder.finish_node();
+    }
+    builder.finish_node();
+    idx
+}
+
+#[test]
+#[cfg(test)]
+fn test_cst_round_trip() {
+    let sources = [
+        include_str!("../../library/core/pack.blp"),
+        include_str!("../../library/core/alloc.blp"),
+        include_str!("../../library/core/sys/unix.blp"),
+        "/// docs\r\npub defun f() => Int64 { ret 0; }\t// trailing\n@[NoReturn] ) \"§\" /* open",
+    ];
+    for source in sources {
+        let root = parse(source, FileId::DUMMY);
+        assert_eq!(root.text(), source);
+        assert_eq!(
+            root.tokens()
+                .iter()
+                .map(|token| token.text())
+                .collect::<String>(),
+            source
+        );
+    }
+
+    let root = parse("/// docs\nlet x => (1);\n@[Attr] @end;", FileId::DUMMY);
+    let kinds = root
+        .child_nodes()
+        .map(|node| node.kind())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [NodeKind::Item, NodeKind::Preproc, NodeKind::Preproc]
+    );
+
+    let item = root.child_nodes().next().unwrap();
+    assert_eq!(item.text(), "/// docs\nlet x => (1);");
+    assert_eq!(item.child_nodes().next().unwrap().text(), "(1)");
+}
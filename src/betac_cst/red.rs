@@ -0,0 +1,213 @@
+use std::{fmt, rc::Rc, sync::Arc};
+
+use crate::{betac_ast::Span, betac_tokenizer::token::TokenKind, betac_util::source_map::FileId};
+
+use super::{
+    green::{GreenElement, GreenNode, GreenToken},
+    NodeKind,
+};
+
+/// a node in the red tree, which is a view over a green node that knows its parent and offset
+///
+/// red nodes are made lazily while walking down from the root, and are cheap to clone
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// the index of this node in its parent's children
+    index: usize,
+    offset: u32,
+    file: FileId,
+}
+
+/// a token in the red tree
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    parent: SyntaxNode,
+    offset: u32,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Arc<GreenNode>, file: FileId) -> Self {
+        Self(Rc::new(NodeData {
+            green,
+            parent: None,
+            index: 0,
+            offset: 0,
+            file,
+        }))
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind()
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// the index of this node in its parent's children, or `0` for the root
+    pub fn index(&self) -> usize {
+        self.0.index
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.0.offset
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.0.file, self.0.offset, self.0.green.len())
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children()
+            .iter()
+            .enumerate()
+            .map(move |(index, child)| {
+                let start = offset;
+                offset += child.len();
+                match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(Self(Rc::new(NodeData {
+                        green: green.clone(),
+                        parent: Some(self.clone()),
+                        index,
+                        offset: start,
+                        file: self.0.file,
+                    }))),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        parent: self.clone(),
+                        offset: start,
+                    }),
+                }
+            })
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// every token under this node, trivia included, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens(&self, tokens: &mut Vec<SyntaxToken>) {
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// the exact source text this node covers
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.0.green.len() as usize);
+        self.0.green.write_text(&mut text);
+        text
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let span = self.span();
+        writeln!(
+            f,
+            "{:indent$}{:?}@{}..{}",
+            "",
+            self.kind(),
+            span.start_pos,
+            span.end_pos(),
+            indent = depth * 2
+        )?;
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => node.fmt_tree(f, depth + 1)?,
+                SyntaxElement::Token(token) => {
+                    writeln!(f, "{:indent$}{token:?}", "", indent = (depth + 1) * 2)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    /// `{:?}` prints just this node, like `Item@4..12`, and `{:#?}` prints the whole subtree
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return self.fmt_tree(f, 0);
+        }
+        let span = self.span();
+        write!(
+            f,
+            "{:?}@{}..{}",
+            self.kind(),
+            span.start_pos,
+            span.end_pos()
+        )
+    }
+}
+
+impl SyntaxToken {
+    pub fn green(&self) -> &Arc<GreenToken> {
+        &self.green
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind()
+    }
+
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.parent.0.file, self.offset, self.green.len())
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        self.kind().is_trivia()
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}@{}..{} {:?}",
+            self.kind(),
+            self.offset,
+            self.offset + self.green.len(),
+            self.text()
+        )
+    }
+}
@@ -0,0 +1,682 @@
+/// a stable identifier for a kind of diagnostic, along with its long-form explanation
+///
+/// codes are never reused or renumbered; if an error goes away, its entry stays in the registry
+#[derive(Debug)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+pub const B0001: ErrorCode = ErrorCode {
+    code: "B0001",
+    title: "an identifier was expected, but not found",
+    explanation: "\
+An identifier was expected, but something else was found.
+
+Names are required after keywords that introduce a binding or an item,
+such as `let`, `defun`, `obj` and `alias`.
+
+Erroneous code example:
+
+    let => 0;
+
+Give the binding a name:
+
+    let x => 0;
+",
+};
+
+pub const B0002: ErrorCode = ErrorCode {
+    code: "B0002",
+    title: "a token appeared where it isn't allowed",
+    explanation: "\
+The parser found a token that can't start or continue the current
+construct.
+
+Erroneous code example:
+
+    pub defun foo() => Int64 }
+
+Check for a missing or extra delimiter, or a missing `;`:
+
+    pub defun foo() => Int64 {}
+",
+};
+
+pub const B0003: ErrorCode = ErrorCode {
+    code: "B0003",
+    title: "an unknown preprocessor macro was used",
+    explanation: "\
+A name following `@` isn't a known preprocessor macro.
+
+The built-in macros are `@start`, `@eval`, `@else`, `@for`, `@template`,
+`@def`, `@external`, `@macro` and `@end`.
+
+Erroneous code example:
+
+    @evaluate(OS == LINUX):
+    @end;
+
+Use one of the built-in macros:
+
+    @eval(OS == LINUX):
+    @end;
+",
+};
+
+pub const B0004: ErrorCode = ErrorCode {
+    code: "B0004",
+    title: "the input ended while more tokens were expected",
+    explanation: "\
+The compiler needed another token to finish what it was reading, but the
+file ended first.
+
+Erroneous code example:
+
+    let x =>
+
+Finish the statement:
+
+    let x => 0;
+",
+};
+
+pub const B0005: ErrorCode = ErrorCode {
+    code: "B0005",
+    title: "a literal is malformed",
+    explanation: "\
+A number, string or char literal couldn't be read.
+
+For numbers, this covers digits that don't fit the radix, a radix prefix
+with no digits after it, exponents with no digits, and unknown type
+suffixes. The type suffixes are the core number types: `Int8`, `Int16`,
+`Int32`, `Int64`, `Uint8`, `Uint16`, `Uint32`, `Uint64`, `Float32` and
+`Float64`.
+
+For strings and chars, this covers unknown escapes like `\\q`, `\\x`
+escapes above `7F` outside of byte strings, `\\u{...}` escapes that aren't
+a valid char, non-ASCII text in byte literals, and char literals that
+don't hold exactly one char.
+
+Erroneous code example:
+
+    let mask => 0b1021;
+    let big => 10Int128;
+
+Use digits that fit the radix, and a known suffix:
+
+    let mask => 0b1011;
+    let big => 10Int64;
+",
+};
+
+pub const B0006: ErrorCode = ErrorCode {
+    code: "B0006",
+    title: "a string or char literal is missing its closing quote",
+    explanation: "\
+A string, char or raw string literal was opened, but the file ended before
+it was closed. Char literals also can't span more than one line.
+
+Erroneous code example:
+
+    let name: &Str => \"core;
+
+Close the literal:
+
+    let name: &Str => \"core\";
+
+Raw strings must be closed with the same number of `#`s they were opened
+with, so `r#\"...\"#` can contain a bare `\"`.
+",
+};
+
+pub const B0007: ErrorCode = ErrorCode {
+    code: "B0007",
+    title: "a block comment is missing its closing `*/`",
+    explanation: "\
+A block comment was opened with `/*`, but the file ended before it was
+closed.
+
+Block comments nest, so every `/*` inside of a comment needs its own `*/`.
+
+Erroneous code example:
+
+    /* outer /* inner */
+    let x => 0;
+
+Close every comment that was opened:
+
+    /* outer /* inner */ */
+    let x => 0;
+",
+};
+
+pub const B0008: ErrorCode = ErrorCode {
+    code: "B0008",
+    title: "a source file isn't valid UTF-8",
+    explanation: "\
+Source files must be encoded as UTF-8, but a sequence of bytes in the file
+couldn't be decoded.
+
+This usually means the file was saved in another encoding, such as
+Latin-1 or UTF-16. Each malformed sequence is reported once, and the rest
+of the file is still read, with the sequence replaced by `U+FFFD`.
+
+Re-save the file as UTF-8. To put arbitrary bytes in the program, use a
+byte string with `\\x` escapes instead:
+
+    let bytes => b\"\\xFF\\xFE\";
+",
+};
+
+pub const B0009: ErrorCode = ErrorCode {
+    code: "B0009",
+    title: "a character can't start any token",
+    explanation: "\
+The tokenizer found a character that isn't part of any token, like a
+control character or a symbol the language doesn't use.
+
+The character is skipped, and the rest of the file is still checked.
+
+Erroneous code example:
+
+    let x => 5 § 2;
+
+Remove the character, or move it into a string or a comment:
+
+    let x => 5; // § 2
+",
+};
+
+pub const B0010: ErrorCode = ErrorCode {
+    code: "B0010",
+    title: "a preprocessor block was never closed",
+    explanation: "\
+A block macro, like `@eval(..):`, is still open at the end of the file.
+Every `@eval` chain has to be closed with `@end;`, after its last `@else`.
+
+Erroneous code example:
+
+    @eval(OS == LINUX):
+        let x => 1;
+
+Close the block with `@end;`:
+
+    @eval(OS == LINUX):
+        let x => 1;
+    @end;
+",
+};
+
+pub const B0011: ErrorCode = ErrorCode {
+    code: "B0011",
+    title: "an `@else` or `@end` has no matching `@eval`",
+    explanation: "\
+`@else` and `@end` can only follow an open `@eval` block, and a plain
+`@else:` has to be the last branch of its chain.
+
+Erroneous code example:
+
+    @eval(CPU_BITS == 64):
+        let x => 1;
+    @end;
+    @else:
+        let x => 2;
+    @end;
+
+Move the `@else` before the `@end` that closes the block:
+
+    @eval(CPU_BITS == 64):
+        let x => 1;
+    @else:
+        let x => 2;
+    @end;
+",
+};
+
+pub const B0012: ErrorCode = ErrorCode {
+    code: "B0012",
+    title: "a preprocessor expression couldn't be evaluated",
+    explanation: "\
+The condition of an `@eval`, or the value of a `@def`, is malformed or uses
+a value of the wrong kind. Preprocessor expressions are made of names,
+integers, parentheses, `!`, `-` and the binary operators `*`, `/`, `%`, `+`,
+`-`, `<`, `<=`, `>`, `>=`, `==`, `!=`, `&&` and `||`.
+
+A name is looked up in the file's `@def`s, and then in the target
+configuration, which has `OS`, `OS_FAMILY`, `ARCH` and `CPU_BITS` by
+default, and can be extended with `--cfg KEY=VALUE`. A name that's neither
+can only be compared with a configuration value, like the `LINUX` in
+`OS == LINUX`.
+
+Erroneous code example:
+
+    @eval(OS):
+    @end;
+
+`OS` is a name, not a condition, so compare it with something:
+
+    @eval(OS == LINUX):
+    @end;
+",
+};
+
+pub const B0013: ErrorCode = ErrorCode {
+    code: "B0013",
+    title: "a `@def` or a `@template` was defined more than once",
+    explanation: "\
+A name can only be given to one `@def` in each file, and a `@def` can't be
+changed after it's defined. The same goes for `@template`s, which are named
+after the item they declare.
+
+Erroneous code example:
+
+    @def WIDTH => 4;
+    @def WIDTH => 8;
+
+Use a different name, or remove one of the definitions:
+
+    @def WIDTH => 4;
+    @def WIDE_WIDTH => WIDTH * 2;
+",
+};
+
+pub const B0014: ErrorCode = ErrorCode {
+    code: "B0014",
+    title: "a preprocessor expression used an undefined name",
+    explanation: "\
+A name in an `@eval` condition or a `@def` value isn't a `@def` in this
+file, or a key of the target configuration.
+
+`@def`s are local to the file they're in, and can only be used after them.
+
+Erroneous code example:
+
+    @eval(WIDTH > 4):
+    @end;
+    @def WIDTH => 8;
+
+Define the name before using it:
+
+    @def WIDTH => 8;
+    @eval(WIDTH > 4):
+    @end;
+",
+};
+
+pub const B0015: ErrorCode = ErrorCode {
+    code: "B0015",
+    title: "a preprocessor expression overflowed",
+    explanation: "\
+Numbers in preprocessor expressions are `Int64`s, and the result of an
+operation didn't fit in one, or divided by zero.
+
+Erroneous code example:
+
+    @def BIG => 9223372036854775807;
+    @def BIGGER => BIG + 1;
+
+Make sure every step of the expression stays in range:
+
+    @def BIG => 9223372036854775807;
+    @def SMALLER => BIG - 1;
+",
+};
+
+pub const B0016: ErrorCode = ErrorCode {
+    code: "B0016",
+    title: "a `@for` loop can't be unrolled",
+    explanation: "\
+A `@for` is unrolled while the file is preprocessed, so everything about it
+has to be known then. Its variable needs an integer type, and it has to loop
+over a range like `0..4` or `0..=3`, or a tuple of numbers like `(1, 2, 4)`.
+The bounds can only use numbers, `@def`s and configuration keys, every value
+has to fit in the type of the variable, and a loop can't make more than 4096
+copies of its body.
+
+Erroneous code example:
+
+    defun count(len: Uint64) {
+        @for(constexpr let N: Uint64 => 0..len):
+            out.println(N);
+        @end;
+    }
+
+Make the bound a constant:
+
+    @def LEN => 4;
+
+    defun count() {
+        @for(constexpr let N: Uint64 => 0..LEN):
+            out.println(N);
+        @end;
+    }
+",
+};
+
+pub const B0017: ErrorCode = ErrorCode {
+    code: "B0017",
+    title: "a `@template` is malformed or can't be instantiated",
+    explanation: "\
+A `@template` has to declare a named item, like `obj Buffer`, and takes
+integer parameters, like `constexpr let N: Uint64`. It's instantiated with
+`@` and the name of the item, like `@Buffer(16)`, which expands the template
+for those arguments the first time, and is replaced with the name of that
+instance everywhere.
+
+Every argument has to be a compile-time constant that fits in the type of its
+parameter, there has to be one argument for every parameter, and a template
+can't have more than 256 instances.
+
+Erroneous code example:
+
+    @template(constexpr let N: Uint8):
+        obj Buffer {
+            pub static defun Capacity() => Uint64 {
+                ret N;
+            }
+        }
+    @end;
+
+    alias Page = @Buffer(4096);
+
+Use a parameter type that's big enough for every argument:
+
+    @template(constexpr let N: Uint64):
+        obj Buffer {
+            pub static defun Capacity() => Uint64 {
+                ret N;
+            }
+        }
+    @end;
+
+    alias Page = @Buffer(4096);
+",
+};
+
+pub const B0018: ErrorCode = ErrorCode {
+    code: "B0018",
+    title: "a `@macro` is malformed",
+    explanation: "\
+A `@macro` is written as `@macro defun name(pattern): body @end;`. The pattern
+is made of tokens that every call has to repeat, parameters like `$x: expr`,
+and repetitions like `$($x: expr),*`. A parameter matches a name (`ident`), a
+literal (`lit`), a single token or a group in brackets (`tt`), or the tokens
+up to the next `,`, `;` or `=>` (`expr`). A repetition is followed by an
+optional separator and `*`, `+` or `?`.
+
+The body can use every parameter with `$x`, but a parameter that's inside of
+a repetition in the pattern has to be inside of one in the body as well.
+
+Erroneous code example:
+
+    @macro defun sum($($x: expr),*):
+        0 $(+ $x)
+    @end;
+
+Say how often the repetition repeats:
+
+    @macro defun sum($($x: expr),*):
+        0 $(+ $x)*
+    @end;
+",
+};
+
+pub const B0019: ErrorCode = ErrorCode {
+    code: "B0019",
+    title: "a macro call doesn't match its macro",
+    explanation: "\
+The tokens in the parentheses of a macro call have to match the pattern of
+the macro, and every parameter in one repetition of the body has to have
+matched the same number of times.
+
+Erroneous code example:
+
+    @macro defun add($a: expr, $b: expr):
+        $a + $b
+    @end;
+
+    let sum: Int64 => @add(1; 2);
+
+Separate the arguments the way the pattern does:
+
+    let sum: Int64 => @add(1, 2);
+",
+};
+
+pub const B0020: ErrorCode = ErrorCode {
+    code: "B0020",
+    title: "macro expansion reached the recursion limit",
+    explanation: "\
+The code a macro expands to is preprocessed again, so macros can call other
+macros and themselves. Macro calls can be nested at most 64 deep, and a file
+can expand at most 65536 calls in total, so a macro that calls itself without
+end is stopped.
+
+Erroneous code example:
+
+    @macro defun forever():
+        @forever()
+    @end;
+
+    @forever();
+
+Make sure the macro stops calling itself at some point.
+",
+};
+
+pub const B0021: ErrorCode = ErrorCode {
+    code: "B0021",
+    title: "the header of an `@external` couldn't be found",
+    explanation: "\
+`@external \"header.h\":` reads a C header, and declares what's in it. The
+header is looked for next to the file the `@external` is in, and then in every
+directory given with `-I DIR`, in order.
+
+Erroneous code example:
+
+    @external \"missing.h\":
+    @end;
+
+Make sure the header exists, or add the directory it's in with `-I`.
+",
+};
+
+pub const B0022: ErrorCode = ErrorCode {
+    code: "B0022",
+    title: "a C header couldn't be read",
+    explanation: "\
+The C headers `@external` reads are only partly understood: functions, structs,
+enums, typedefs and `#define`s of integers are declared, and everything else
+is skipped. Skipping only works as long as the header is well formed, so
+comments, strings and brackets that are never closed are reported.
+
+Erroneous code example, in `point.h`:
+
+    struct point {
+        int x;
+        int y;
+
+Make sure every `{`, `(` and `[`, and every comment and string, is closed.
+",
+};
+
+pub const B0023: ErrorCode = ErrorCode {
+    code: "B0023",
+    title: "an `@asm` block is malformed",
+    explanation: "\
+An `@asm` block binds its operands in parentheses, and is followed by the lines
+of assembly, which are strings:
+
+    @asm(code => in(reg) code, exit => in(reg) SYS_EXIT):
+        \"mov rbx, ${code};\"
+        \"mov rax, ${exit};\"
+        \"syscall;\"
+    @end;
+
+Every operand is `name => direction(class) expression`, where the direction is
+`in`, `out` or `inout`, and every name can only be bound once.
+
+Erroneous code example:
+
+    @asm(code => into(reg) code):
+        \"mov rbx, ${code};\"
+    @end;
+
+`into` isn't a direction. Use `in`, `out` or `inout` instead.
+",
+};
+
+pub const B0024: ErrorCode = ErrorCode {
+    code: "B0024",
+    title: "an `@asm` block uses an operand it doesn't bind",
+    explanation: "\
+A `${name}` in the assembly of an `@asm` block is replaced with the operand
+bound to `name`, so every name it uses has to be bound in its parentheses.
+
+Erroneous code example:
+
+    @asm(code => in(reg) code):
+        \"mov rbx, ${code};\"
+        \"mov rax, ${exit};\"
+    @end;
+
+Bind the operand, like `exit => in(reg) SYS_EXIT`, or fix the name.
+",
+};
+
+pub const B0025: ErrorCode = ErrorCode {
+    code: "B0025",
+    title: "an attribute isn't known",
+    explanation: "\
+Only the built-in attributes can be put on items: `NoReturn`, `Inline`, `Cold`,
+`Deprecated`, `Test`, `Repr` and `Export`.
+
+Erroneous code example:
+
+    @[NoRet]
+    defun exit(code: Int32) => Void { .. }
+
+Attributes are case sensitive, so this has to be `@[NoReturn]`.
+",
+};
+
+pub const B0026: ErrorCode = ErrorCode {
+    code: "B0026",
+    title: "an attribute is malformed",
+    explanation: "\
+An attribute is a name, which can be followed by one argument in parentheses.
+Some attributes take a string, some take one of a few names, and some don't
+take anything:
+
+    @[Inline(Always)]
+    @[Deprecated(\"use `sysWrite` instead\")]
+    @[Cold]
+
+Every attribute can only be put on an item once.
+
+Erroneous code example:
+
+    @[Repr(Aligned)]
+    obj Header { .. }
+
+`Repr` takes `C`, `Packed` or `Transparent`.
+",
+};
+
+pub const B0027: ErrorCode = ErrorCode {
+    code: "B0027",
+    title: "an attribute isn't on an item it can be put on",
+    explanation: "\
+Attributes are put right before an item, and every attribute can only be put on
+some sorts of item. `Repr` is only for objects, `Export` is for functions and
+variables, `Deprecated` is for any item, and the rest are only for functions.
+
+Erroneous code example:
+
+    @[NoReturn]
+    obj Exit { .. }
+
+Objects can't return, so `NoReturn` has to be put on a function.
+",
+};
+
+pub const B0028: ErrorCode = ErrorCode {
+    code: "B0028",
+    title: "a program doesn't have an entry point",
+    explanation: "\
+Every program starts at the function its `@start` names, and packages, which
+aren't run on their own, say so with `@start NO_START;`.
+
+Erroneous code example:
+
+    defun main() => Void { .. }
+
+Add `@start main;`. This error is also reported when `@start` names a function
+that doesn't exist.
+",
+};
+
+pub const B0029: ErrorCode = ErrorCode {
+    code: "B0029",
+    title: "a program has more than one entry point",
+    explanation: "\
+A program can only start in one place, so it can only have one `@start`.
+
+Erroneous code example:
+
+    @start main;
+    @start run;
+
+Remove one of them.
+",
+};
+
+pub const B0030: ErrorCode = ErrorCode {
+    code: "B0030",
+    title: "an entry point is malformed",
+    explanation: "\
+`@start` is followed by the name of a function, or `NO_START`, and a `;`. The
+function can't be generic, and either takes nothing or the arguments of the
+program, and returns nothing or an exit code:
+
+    defun main() => Void { .. }
+    defun main(args: &[Str]) => Int32 { .. }
+
+Erroneous code example:
+
+    @start main;
+    defun main(code: Int32) => Void { .. }
+
+`main` can't take an `Int32`.
+",
+};
+
+/// every known error code, in order
+pub static REGISTRY: &[ErrorCode] = &[
+    B0001, B0002, B0003, B0004, B0005, B0006, B0007, B0008, B0009, B0010, B0011, B0012, B0013,
+    B0014, B0015, B0016, B0017, B0018, B0019, B0020, B0021, B0022, B0023, B0024, B0025, B0026,
+    B0027, B0028, B0029, B0030,
+];
+
+/// looks up an error code, ignoring case
+/// codes can be given with or without the leading `B`, so `B0001`, `b0001` and `0001` all match
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    let code = code.trim();
+    let digits = code
+        .strip_prefix('B')
+        .or_else(|| code.strip_prefix('b'))
+        .unwrap_or(code);
+    REGISTRY.iter().find(|entry| &entry.code[1..] == digits)
+}
+
+#[test]
+#[cfg(test)]
+fn test_error_code_lookup() {
+    assert_eq!(lookup("B0001").map(|entry| entry.code), Some("B0001"));
+    assert_eq!(lookup("b0003").map(|entry| entry.code), Some("B0003"));
+    assert_eq!(lookup("0004").map(|entry| entry.code), Some("B0004"));
+    assert!(lookup("B9999").is_none());
+    assert!(REGISTRY.windows(2).all(|pair| pair[0].code < pair[1].code));
+}
@@ -0,0 +1,261 @@
+use std::fmt::{self, Write};
+
+use crate::{betac_ast::Span, betac_util::source_map::SourceMap};
+
+use super::{Level, Reportable};
+
+/// a plain-data snapshot of a `Reportable`, with every span resolved through the `SourceMap`
+///
+/// this is what `--error-format=json` writes out, one object per line,
+/// and what tools can use directly through `Emitter::diagnostics`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: &'static str,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub spans: Vec<DiagnosticSpan>,
+    pub children: Vec<DiagnosticChild>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticSpan {
+    pub file: String,
+    pub byte_start: u32,
+    pub byte_end: u32,
+    pub line_start: u32,
+    pub column_start: u32,
+    pub line_end: u32,
+    pub column_end: u32,
+    pub is_primary: bool,
+    pub label: Option<String>,
+}
+
+/// a note or help message attached to a diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticChild {
+    pub level: &'static str,
+    pub message: String,
+}
+
+impl DiagnosticSpan {
+    fn new(map: &SourceMap, span: Span, is_primary: bool, label: Option<String>) -> Option<Self> {
+        let file = map.get(span.file)?;
+        let (line_start, column_start) = file.line_col(span.start_pos);
+        let (line_end, column_end) = file.line_col(span.end_pos());
+        Some(Self {
+            file: file.name().to_string(),
+            byte_start: span.start_pos,
+            byte_end: span.end_pos(),
+            line_start,
+            column_start,
+            line_end,
+            column_end,
+            is_primary,
+            label,
+        })
+    }
+}
+
+impl Diagnostic {
+    pub fn new(err: &dyn Reportable, map: &SourceMap) -> Self {
+        let level = match err.level() {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+
+        let spans = std::iter::once(DiagnosticSpan::new(map, err.span(), true, None))
+            .chain(err.labels().iter().map(|label| {
+                DiagnosticSpan::new(map, label.span, false, Some(label.message.clone()))
+            }))
+            .flatten()
+            .collect();
+
+        let children = err
+            .notes()
+            .iter()
+            .map(|note| DiagnosticChild {
+                level: "note",
+                message: note.clone(),
+            })
+            .chain(err.help().map(|help| DiagnosticChild {
+                level: "help",
+                message: help.to_string(),
+            }))
+            .collect();
+
+        Self {
+            level,
+            code: err.code(),
+            message: err.message().to_string(),
+            spans,
+            children,
+        }
+    }
+
+    /// serializes the diagnostic as a single-line JSON object
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out).unwrap();
+        out
+    }
+
+    fn write_json(&self, out: &mut String) -> fmt::Result {
+        write!(out, "{{\"level\":")?;
+        write_str(out, self.level)?;
+        write!(out, ",\"code\":")?;
+        match self.code {
+            Some(code) => write_str(out, code)?,
+            None => out.write_str("null")?,
+        }
+        write!(out, ",\"message\":")?;
+        write_str(out, &self.message)?;
+
+        write!(out, ",\"spans\":[")?;
+        for (idx, span) in self.spans.iter().enumerate() {
+            if idx != 0 {
+                out.write_char(',')?;
+            }
+            write!(out, "{{\"file\":")?;
+            write_str(out, &span.file)?;
+            write!(
+                out,
+                ",\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"column_start\":{},\
+                 \"line_end\":{},\"column_end\":{},\"is_primary\":{},\"label\":",
+                span.byte_start,
+                span.byte_end,
+                span.line_start,
+                span.column_start,
+                span.line_end,
+                span.column_end,
+                span.is_primary
+            )?;
+            match &span.label {
+                Some(label) => write_str(out, label)?,
+                None => out.write_str("null")?,
+            }
+            out.write_char('}')?;
+        }
+
+        write!(out, "],\"children\":[")?;
+        for (idx, child) in self.children.iter().enumerate() {
+            if idx != 0 {
+                out.write_char(',')?;
+            }
+            write!(out, "{{\"level\":")?;
+            write_str(out, child.level)?;
+            write!(out, ",\"message\":")?;
+            write_str(out, &child.message)?;
+            out.write_char('}')?;
+        }
+        out.write_str("]}")
+    }
+}
+
+/// writes `s` as a quoted JSON string
+fn write_str(out: &mut String, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+#[test]
+#[cfg(test)]
+fn test_diagnostic_to_json() {
+    let diagnostic = Diagnostic {
+        level: "error",
+        code: None,
+        message: "expected \"ident\"".to_string(),
+        spans: vec![DiagnosticSpan {
+            file: "a.blp".to_string(),
+            byte_start: 4,
+            byte_end: 6,
+            line_start: 1,
+            column_start: 5,
+            line_end: 1,
+            column_end: 7,
+            is_primary: true,
+            label: None,
+        }],
+        children: vec![DiagnosticChild {
+            level: "note",
+            message: "line\nbreak".to_string(),
+        }],
+    };
+
+    assert_eq!(
+        diagnostic.to_json(),
+        "{\"level\":\"error\",\"code\":null,\"message\":\"expected \\\"ident\\\"\",\"spans\":[\
+         {\"file\":\"a.blp\",\"byte_start\":4,\"byte_end\":6,\"line_start\":1,\"column_start\":5,\
+         \"line_end\":1,\"column_end\":7,\"is_primary\":true,\"label\":null}],\
+         \"children\":[{\"level\":\"note\",\"message\":\"line\\nbreak\"}]}"
+    );
+}
+
+#[test]
+#[cfg(test)]
+fn test_diagnostic_from_reportable() {
+    use super::{general_errors::UnexpectedTokenInInput, Emitter, SpanKind};
+
+    let text = "let x => ;\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("json_diagnostic.beta", text));
+    UnexpectedTokenInInput::builder()
+        .message("expected a value, found `;`".to_string())
+        .span(Span::new(file, 9, 1), SpanKind::NoMeta)
+        .label(Span::new(file, 0, 3), "in this `let`")
+        .note("every `let` has a value")
+        .help("add one, like `let x => 0;`")
+        .report();
+
+    let diagnostic = Emitter::with(|lock| lock.diagnostics())
+        .into_iter()
+        .find(|diagnostic| {
+            diagnostic
+                .spans
+                .first()
+                .is_some_and(|span| span.file == "json_diagnostic.beta")
+        })
+        .unwrap();
+    let span = |byte_start, byte_end, is_primary, label: Option<&str>| DiagnosticSpan {
+        file: "json_diagnostic.beta".to_string(),
+        byte_start,
+        byte_end,
+        line_start: 1,
+        column_start: byte_start + 1,
+        line_end: 1,
+        column_end: byte_end + 1,
+        is_primary,
+        label: label.map(str::to_string),
+    };
+    assert_eq!(
+        diagnostic,
+        Diagnostic {
+            level: "error",
+            code: Some("B0002"),
+            message: "expected a value, found `;`".to_string(),
+            spans: vec![
+                span(9, 10, true, None),
+                span(0, 3, false, Some("in this `let`"))
+            ],
+            children: vec![
+                DiagnosticChild {
+                    level: "note",
+                    message: "every `let` has a value".to_string(),
+                },
+                DiagnosticChild {
+                    level: "help",
+                    message: "add one, like `let x => 0;`".to_string(),
+                },
+            ],
+        }
+    );
+}
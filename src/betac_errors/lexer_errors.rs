@@ -0,0 +1,9 @@
+use super::reportable;
+
+reportable! {
+    InvalidLiteral => B0005,
+    UnterminatedLiteral => B0006,
+    UnterminatedComment => B0007,
+    InvalidUtf8 => B0008,
+    UnknownCharacter => B0009,
+}
@@ -1,13 +1,35 @@
-use crate::{betac_ast::Span, betac_util::small_vec::SmallVec};
+use crate::{
+    betac_ast::Span,
+    betac_util::{small_vec::SmallVec, source_map::SourceMap},
+};
 use std::fmt;
 use std::sync::{Mutex, MutexGuard};
 
+pub mod codes;
 pub mod general_errors;
+pub mod json;
+pub mod lexer_errors;
 pub mod option;
+pub mod parser_errors;
 pub mod preproc_errors;
+pub mod render;
+
+use json::Diagnostic;
+use render::{Label, Renderer};
 
 pub struct Emitter {
     errors: SmallVec<Box<dyn Reportable>>,
+    color: bool,
+    format: ErrorFormat,
+}
+
+/// how `Emitter::flush` writes out diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// rustc-style snippets, meant for people
+    Human,
+    /// one JSON object per line, meant for editors and CI
+    Json,
 }
 
 pub static EMITTER: Mutex<Emitter> = Mutex::new(Emitter::new());
@@ -16,20 +38,67 @@ impl Emitter {
     pub const fn new() -> Self {
         Self {
             errors: SmallVec::new(),
+            color: false,
+            format: ErrorFormat::Human,
         }
     }
 
+    pub fn set_format(&mut self, format: ErrorFormat) {
+        self.format = format;
+    }
+
+    /// returns every collected error as a `Diagnostic`, without clearing the list
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        SourceMap::with(|map| {
+            self.errors
+                .iter()
+                .map(|err| Diagnostic::new(&**err, &map))
+                .collect()
+        })
+    }
+
+    /// the messages of the collected errors in `file`, so tests can check their own errors
+    /// while other tests report theirs
+    #[cfg(test)]
+    pub fn messages_in(file: crate::betac_util::source_map::FileId) -> Vec<String> {
+        Self::with(|lock| {
+            lock.errors
+                .iter()
+                .filter(|err| err.span().file == file)
+                .map(|err| err.message().to_string())
+                .collect()
+        })
+    }
+
+    /// sets whether `flush` should color its output
+    /// this should only be turned on when the writer is a terminal
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// writes every collected error in the current `ErrorFormat`, and clears the list
     pub fn flush(&mut self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
-        for err in self.errors.drain() {
-            let prefix = match err.level() {
-                Level::Error => "ERROR",
-                Level::Warning => "WARNING",
-            };
-
-            writeln!(w, "{prefix}: on {}:{}", err.line(), err.column())?;
-            writeln!(w, "{}", err.message())?;
-        }
-        Ok(())
+        SourceMap::with(|map| {
+            let renderer = Renderer::new(&map, self.color);
+            let mut explained = None;
+            for err in self.errors.drain() {
+                explained = explained.or(err.code());
+                match self.format {
+                    ErrorFormat::Human => renderer.render(w, &*err)?,
+                    ErrorFormat::Json => writeln!(w, "{}", Diagnostic::new(&*err, &map).to_json())?,
+                }
+            }
+
+            if let Some(code) = explained
+                && self.format == ErrorFormat::Human
+            {
+                writeln!(
+                    w,
+                    "for more information about this error, try `betac explain {code}`"
+                )?;
+            }
+            Ok(())
+        })
     }
 
     pub fn with<F, R>(f: F) -> R
@@ -62,6 +131,23 @@ pub trait Reportable: Send + Sync + fmt::Debug {
     fn message(&self) -> &str;
 
     fn level(&self) -> Level;
+
+    fn code(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// secondary spans, rendered alongside the primary one
+    fn labels(&self) -> &[Label] {
+        &[]
+    }
+
+    fn notes(&self) -> &[String] {
+        &[]
+    }
+
+    fn help(&self) -> Option<&str> {
+        None
+    }
 }
 
 macro_rules! impl_builder_for_reportable {
@@ -73,6 +159,9 @@ macro_rules! impl_builder_for_reportable {
                     line: 0,
                     column: 0,
                     message: None,
+                    labels: Vec::new(),
+                    notes: Vec::new(),
+                    help: None,
                 }
             }
 
@@ -86,8 +175,29 @@ macro_rules! impl_builder_for_reportable {
                 self
             }
 
-            pub fn span(mut self, span: Span, kind: SpanKind) -> Self {
+            /// sets the span of the error
+            /// this also fills in `line` and `column` if the span belongs to a registered file
+            ///
+            /// spans in code the preprocessor made are traced back to the code it was copied
+            /// from, and every expansion on the way gets a label at its call site
+            pub fn span(
+                mut self,
+                span: $crate::betac_ast::Span,
+                kind: $crate::betac_errors::SpanKind,
+            ) -> Self {
+                let (span, frames, loc) = crate::betac_util::source_map::SourceMap::with(|map| {
+                    let (span, frames) = map.resolve(span);
+                    (span, frames, map.lookup(span))
+                });
+                if let Some(loc) = loc {
+                    self.line = loc.line;
+                    self.column = loc.column;
+                }
                 self.span = Some((span, kind));
+                for (call_site, description) in frames {
+                    self.labels
+                        .push(super::render::Label::new(call_site, description));
+                }
                 self
             }
 
@@ -95,8 +205,99 @@ macro_rules! impl_builder_for_reportable {
                 self.message = Some(message);
                 self
             }
+
+            pub fn label(
+                mut self,
+                span: $crate::betac_ast::Span,
+                message: impl Into<String>,
+            ) -> Self {
+                let span =
+                    crate::betac_util::source_map::SourceMap::with(|map| map.resolve(span).0);
+                self.labels.push(super::render::Label::new(span, message));
+                self
+            }
+
+            pub fn note(mut self, note: impl Into<String>) -> Self {
+                self.notes.push(note.into());
+                self
+            }
+
+            pub fn help(mut self, help: impl Into<String>) -> Self {
+                self.help = Some(help.into());
+                self
+            }
         }
     };
 }
 
 use impl_builder_for_reportable as builder;
+
+/// declares errors that are made with `builder()`, like
+/// `UnterminatedPreprocBlock => B0010`, where `B0010` is the error's entry in `codes`
+///
+/// an error's message is the title of its code, unless the builder sets another one
+macro_rules! declare_reportable {
+    ($($(#[$meta:meta])* $name:ident => $code:ident),* $(,)?) => {$(
+        $(#[$meta])*
+        #[derive(Debug)]
+        pub struct $name {
+            span: Option<($crate::betac_ast::Span, $crate::betac_errors::SpanKind)>,
+            line: u32,
+            column: u32,
+            message: Option<String>,
+            labels: Vec<$crate::betac_errors::render::Label>,
+            notes: Vec<String>,
+            help: Option<String>,
+        }
+
+        super::builder!($name);
+
+        impl $crate::betac_errors::Reportable for $name {
+            fn code(&self) -> Option<&'static str> {
+                Some($crate::betac_errors::codes::$code.code)
+            }
+
+            fn line(&self) -> u32 {
+                self.line
+            }
+
+            fn column(&self) -> u32 {
+                self.column
+            }
+
+            fn span(&self) -> $crate::betac_ast::Span {
+                self.span
+                    .map(|(span, _)| span)
+                    .unwrap_or($crate::betac_ast::Span::DUMMY)
+            }
+
+            fn level(&self) -> $crate::betac_errors::Level {
+                $crate::betac_errors::Level::Error
+            }
+
+            fn message(&self) -> &str {
+                self.message
+                    .as_deref()
+                    .unwrap_or($crate::betac_errors::codes::$code.title)
+            }
+
+            fn labels(&self) -> &[$crate::betac_errors::render::Label] {
+                &self.labels
+            }
+
+            fn notes(&self) -> &[String] {
+                &self.notes
+            }
+
+            fn help(&self) -> Option<&str> {
+                self.help.as_deref()
+            }
+
+            fn report(self) {
+                $crate::betac_errors::Emitter::with(|mut lock| lock.errors.push(Box::new(self)))
+            }
+        }
+    )*};
+}
+
+use declare_reportable as reportable;
@@ -1,11 +1,12 @@
 use std::fmt::Debug;
 
-use crate::{betac_ast::Span, betac_tokenizer::token::Token};
+use crate::{betac_ast::Span, betac_tokenizer::token::Token, betac_util::source_map::SourceMap};
 
-use super::{Emitter, Level, Reportable};
+use super::{codes, Emitter, Level, Reportable};
 
 #[derive(Debug)]
 pub struct UnexpectedResult {
+    span: Span,
     line: u32,
     column: u32,
     message: String,
@@ -14,12 +15,23 @@ pub struct UnexpectedResult {
 impl UnexpectedResult {
     pub fn builder() -> Self {
         Self {
+            span: Span::DUMMY,
             line: 0,
             column: 0,
             message: String::new(),
         }
     }
 
+    /// sets the span of the error, and fills in `line` and `column` from the `SourceMap`
+    pub fn span(mut self, span: Span) -> Self {
+        if let Some(loc) = SourceMap::with(|map| map.lookup(span)) {
+            self.line = loc.line;
+            self.column = loc.column;
+        }
+        self.span = span;
+        self
+    }
+
     pub fn line(mut self, line: u32) -> Self {
         self.line = line;
         self
@@ -37,6 +49,10 @@ impl UnexpectedResult {
 }
 
 impl Reportable for UnexpectedResult {
+    fn code(&self) -> Option<&'static str> {
+        Some(codes::B0004.code)
+    }
+
     fn line(&self) -> u32 {
         self.line as u32
     }
@@ -46,7 +62,7 @@ impl Reportable for UnexpectedResult {
     }
 
     fn span(&self) -> crate::betac_ast::Span {
-        Span::DUMMY
+        self.span
     }
 
     fn level(&self) -> super::Level {
@@ -76,8 +92,6 @@ impl<E: Debug> ResultExtension for Result<Token, E> {
             Self::Ok(ok) => ok,
             Self::Err(err) => {
                 UnexpectedResult::builder()
-                    .column(column!())
-                    .line(line!())
                     .message(format!("expected: Token, found: {err:?}"))
                     .report();
                 Token::DUMMMY
@@ -94,8 +108,6 @@ impl ResultExtension for Option<Token> {
             Self::Some(ok) => ok,
             Self::None => {
                 UnexpectedResult::builder()
-                    .column(column!())
-                    .line(line!())
                     .message(format!("expected: Token, found None"))
                     .report();
                 Token::DUMMMY
@@ -0,0 +1,12 @@
+use super::reportable;
+
+reportable! {
+    InvalidAsmBlock => B0023,
+    UnknownAsmOperand => B0024,
+    UnknownAttribute => B0025,
+    InvalidAttribute => B0026,
+    MisplacedAttribute => B0027,
+    MissingEntryPoint => B0028,
+    DuplicateEntryPoint => B0029,
+    InvalidEntryPoint => B0030,
+}
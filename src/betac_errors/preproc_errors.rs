@@ -1,39 +1,18 @@
-use crate::betac_ast::Span;
-
-use super::{builder, Emitter, Reportable, SpanKind, EMITTER};
-
-#[derive(Debug)]
-pub struct UnrecognizedPreprocMacro {
-    span: Option<(Span, SpanKind)>,
-    line: u32,
-    column: u32,
-    message: Option<String>,
-}
-
-builder!(UnrecognizedPreprocMacro);
-
-impl Reportable for UnrecognizedPreprocMacro {
-    fn line(&self) -> u32 {
-        self.line
-    }
-
-    fn column(&self) -> u32 {
-        self.column
-    }
-
-    fn span(&self) -> Span {
-        self.span.unwrap().0
-    }
-
-    fn level(&self) -> super::Level {
-        super::Level::Error
-    }
-
-    fn message(&self) -> &str {
-        self.message.as_ref().unwrap()
-    }
-
-    fn report(self) {
-        Emitter::with(|mut lock| lock.errors.push(Box::new(self)))
-    }
+use super::reportable;
+
+reportable! {
+    UnrecognizedPreprocMacro => B0003,
+    UnterminatedPreprocBlock => B0010,
+    UnmatchedPreprocMacro => B0011,
+    InvalidPreprocCondition => B0012,
+    PreprocRedefinition => B0013,
+    UndefinedPreprocName => B0014,
+    PreprocOverflow => B0015,
+    InvalidPreprocLoop => B0016,
+    InvalidPreprocTemplate => B0017,
+    InvalidPreprocMacro => B0018,
+    PreprocMacroMismatch => B0019,
+    PreprocRecursionLimit => B0020,
+    MissingExternalHeader => B0021,
+    InvalidExternalHeader => B0022,
 }
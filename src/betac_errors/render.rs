@@ -0,0 +1,192 @@
+use std::io::{self, Write};
+
+use crate::{
+    betac_ast::Span,
+    betac_util::source_map::{SourceFile, SourceMap},
+};
+
+use super::{Level, Reportable};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// a secondary span attached to a diagnostic, rendered with `-` instead of `^`
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// renders diagnostics in the same shape rustc does:
+///
+/// ```text
+/// error[B0001]: message
+///  --> file.blp:3:5
+///   |
+/// 3 |     let x => ;
+///   |             ^ primary
+///   |
+///   = note: ...
+/// ```
+pub struct Renderer<'a> {
+    map: &'a SourceMap,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(map: &'a SourceMap, color: bool) -> Self {
+        Self { map, color }
+    }
+
+    fn paint(&self, style: &'static str) -> &'static str {
+        if self.color {
+            style
+        } else {
+            ""
+        }
+    }
+
+    pub fn render(&self, w: &mut dyn Write, err: &dyn Reportable) -> io::Result<()> {
+        let (name, style) = match err.level() {
+            Level::Error => ("error", RED),
+            Level::Warning => ("warning", YELLOW),
+        };
+
+        let code = err
+            .code()
+            .map(|code| format!("[{code}]"))
+            .unwrap_or_default();
+        writeln!(
+            w,
+            "{}{name}{code}{}{}: {}{}",
+            self.paint(style),
+            self.paint(RESET),
+            self.paint(BOLD),
+            err.message(),
+            self.paint(RESET)
+        )?;
+
+        let span = err.span();
+        let width = std::iter::once(span)
+            .chain(err.labels().iter().map(|label| label.span))
+            .filter_map(|span| self.map.lookup(span))
+            .map(|loc| loc.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(width);
+        let (blue, reset) = (self.paint(BLUE), self.paint(RESET));
+
+        match self.map.get(span.file) {
+            Some(file) => {
+                let (line, column) = file.line_col(span.start_pos);
+                writeln!(w, "{pad}{blue}-->{reset} {}:{line}:{column}", file.name())?;
+                writeln!(w, "{pad} {blue}|{reset}")?;
+                self.snippet(w, file, &Label::new(span, ""), Some(style), width)?;
+
+                for label in err.labels() {
+                    let Some(label_file) = self.map.get(label.span.file) else {
+                        continue;
+                    };
+                    if label.span.file != span.file {
+                        let (line, column) = label_file.line_col(label.span.start_pos);
+                        writeln!(
+                            w,
+                            "{pad}{blue}:::{reset} {}:{line}:{column}",
+                            label_file.name()
+                        )?;
+                    }
+                    self.snippet(w, label_file, label, None, width)?;
+                }
+                writeln!(w, "{pad} {blue}|{reset}")?;
+            }
+            None if err.line() != 0 => {
+                writeln!(w, "{pad}{blue}-->{reset} {}:{}", err.line(), err.column())?
+            }
+            None => {}
+        }
+
+        for note in err.notes() {
+            writeln!(
+                w,
+                "{pad} {blue}={reset} {}note{}: {note}",
+                self.paint(BOLD),
+                reset
+            )?;
+        }
+        if let Some(help) = err.help() {
+            writeln!(
+                w,
+                "{pad} {blue}={reset} {}help{}: {help}",
+                self.paint(BOLD),
+                reset
+            )?;
+        }
+        writeln!(w)
+    }
+
+    /// writes the line containing `label`, and underlines the spanned text
+    /// the primary span (`primary` is the level's color) gets `^`, secondary labels get `-`
+    /// spans running past the end of the line are cut off at the end of the line
+    fn snippet(
+        &self,
+        w: &mut dyn Write,
+        file: &SourceFile,
+        label: &Label,
+        primary: Option<&'static str>,
+        width: usize,
+    ) -> io::Result<()> {
+        let (span, message) = (label.span, &label.message);
+        let (marker, style) = match primary {
+            Some(style) => ("^", style),
+            None => ("-", BLUE),
+        };
+        let line = file.line_index(span.start_pos);
+        let (Some(text), Some(line_start)) = (file.line(line), file.line_start(line)) else {
+            return Ok(());
+        };
+
+        let start = ((span.start_pos - line_start) as usize).min(text.len());
+        let end = ((span.end_pos() - line_start) as usize).clamp(start, text.len());
+        let prefix = text.get(..start).unwrap_or(text);
+        let spanned = text.get(start..end).unwrap_or("");
+
+        let underline = marker.repeat(display_width(spanned).max(1));
+        let (blue, reset) = (self.paint(BLUE), self.paint(RESET));
+
+        writeln!(
+            w,
+            "{blue}{:>width$} |{reset} {}",
+            line + 1,
+            text.replace('\t', "    ")
+        )?;
+        writeln!(
+            w,
+            "{blue}{:width$} |{reset} {}{}{underline}{}{reset}",
+            "",
+            " ".repeat(display_width(prefix)),
+            self.paint(style),
+            if message.is_empty() {
+                String::new()
+            } else {
+                format!(" {message}")
+            },
+        )
+    }
+}
+
+/// the number of columns `s` takes up once tabs are expanded
+fn display_width(s: &str) -> usize {
+    s.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
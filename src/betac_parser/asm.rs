@@ -0,0 +1,361 @@
+use crate::{
+    betac_ast::{
+        asm::{AsmBlock, AsmDirection, AsmOperand, AsmPiece},
+        Span,
+    },
+    betac_errors::{
+        parser_errors::{InvalidAsmBlock, UnknownAsmOperand},
+        preproc_errors::UnterminatedPreprocBlock,
+        Reportable, SpanKind,
+    },
+    betac_tokenizer::{
+        literal::{unescape, Mode},
+        token::{LiteralKind, Token, TokenKind},
+    },
+};
+
+use super::{split_list, ParseInner};
+
+impl<Iter> ParseInner<Iter>
+where
+    Iter: Iterator<Item = Token> + Clone,
+{
+    /// `@asm(..): "..." @end;`, whose `@asm` has already been read
+    ///
+    /// the whole block is read even if it's malformed, in which case `None` is returned
+    /// after reporting why
+    pub(crate) fn parse_asm(&mut self, start: u32) -> Option<AsmBlock> {
+        let at = self.span(start, start + "@asm".len() as u32);
+        let Some((tokens, end)) = self.asm_tokens() else {
+            UnterminatedPreprocBlock::builder()
+                .message("this `@asm` is never closed".to_string())
+                .span(at, SpanKind::NoMeta)
+                .label(
+                    self.span(self.end, self.end),
+                    "expected `@end;` before the end of the file",
+                )
+                .report();
+            return None;
+        };
+
+        let Some((open, _)) = tokens
+            .split_first()
+            .filter(|(open, _)| open.kind == TokenKind::LeftParen)
+        else {
+            self.asm_error(at, "expected `(` after `@asm`");
+            return None;
+        };
+        let Some(close) = matching_paren(&tokens) else {
+            self.asm_error(self.token_span(*open), "this `(` is never closed");
+            return None;
+        };
+        let header = self.span(open.start, tokens[close].end());
+        let operands = self.asm_operands(&tokens[1..close])?;
+
+        let mut body = &tokens[close + 1..];
+        match body.split_first() {
+            Some((colon, rest)) if colon.kind == TokenKind::Colon => body = rest,
+            _ => {
+                self.asm_error(header, "expected `:` after the operands");
+                return None;
+            }
+        }
+
+        let mut lines = Vec::with_capacity(body.len());
+        let mut valid = true;
+        for &token in body {
+            if token.kind != TokenKind::Literal(LiteralKind::Str) {
+                let found = self.text(token).to_string();
+                self.asm_error(
+                    self.token_span(token),
+                    format!("expected a string of assembly, found `{found}`"),
+                );
+                valid = false;
+                continue;
+            }
+            match self.asm_line(token, &operands, header) {
+                Some(line) => lines.push(line),
+                None => valid = false,
+            }
+        }
+
+        valid.then(|| AsmBlock {
+            span: self.span(start, end),
+            operands,
+            lines,
+        })
+    }
+
+    /// every token up to the `@end` that closes an `@asm`, without trivia, and the end of
+    /// the block, which includes the `;` after `@end` if there's one
+    fn asm_tokens(&mut self) -> Option<(Vec<Token>, u32)> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.iterator.next() {
+            if token.kind.is_trivia() {
+                continue;
+            }
+            if token.kind == TokenKind::At
+                && let Some(name) = self.peek()
+                && name.kind == TokenKind::Ident
+                && self.text(name) == "end"
+            {
+                self.iterator.next();
+                let mut end = name.end();
+                let mut ahead = self.iterator.clone();
+                while let Some(next) = ahead.next() {
+                    if next.kind.is_trivia() {
+                        continue;
+                    }
+                    if next.kind == TokenKind::Semi {
+                        self.iterator = ahead;
+                        end = next.end();
+                    }
+                    break;
+                }
+                return Some((tokens, end));
+            }
+            tokens.push(token);
+        }
+        None
+    }
+
+    /// the operands between the parentheses after `@asm`, separated by commas
+    fn asm_operands(&self, list: &[Token]) -> Option<Vec<AsmOperand>> {
+        let mut operands: Vec<AsmOperand> = Vec::new();
+        let mut valid = true;
+        for tokens in split_list(list) {
+            let Some(operand) = self.asm_operand(tokens) else {
+                valid = false;
+                continue;
+            };
+            if let Some(first) = operands.iter().find(|first| first.name == operand.name) {
+                InvalidAsmBlock::builder()
+                    .message(format!("`{}` is bound more than once", operand.name))
+                    .span(operand.span, SpanKind::NoMeta)
+                    .label(first.span, "it's first bound here")
+                    .report();
+                valid = false;
+                continue;
+            }
+            operands.push(operand);
+        }
+        valid.then_some(operands)
+    }
+
+    /// `name => direction(class) expr`
+    fn asm_operand(&self, tokens: &[Token]) -> Option<AsmOperand> {
+        let span = self.span(tokens[0].start, tokens[tokens.len() - 1].end());
+        let [name, arrow, direction, open, class, close, expr @ ..] = tokens else {
+            self.asm_error(span, "expected an operand, like `len => in(reg) len`");
+            return None;
+        };
+        let kinds = [name, arrow, direction, open, class, close].map(|token| token.kind);
+        if kinds
+            != [
+                TokenKind::Ident,
+                TokenKind::FatArrow,
+                TokenKind::Ident,
+                TokenKind::LeftParen,
+                TokenKind::Ident,
+                TokenKind::RightParen,
+            ]
+            || expr.is_empty()
+        {
+            self.asm_error(span, "expected an operand, like `len => in(reg) len`");
+            return None;
+        }
+
+        let Some(direction) = AsmDirection::from_name(self.text(*direction)) else {
+            InvalidAsmBlock::builder()
+                .message(format!("`{}` isn't a direction", self.text(*direction)))
+                .span(self.token_span(*direction), SpanKind::NoMeta)
+                .help("the directions are `in`, `out` and `inout`")
+                .report();
+            return None;
+        };
+        Some(AsmOperand {
+            name: self.text(*name).to_string(),
+            direction,
+            class: self.text(*class).to_string(),
+            expr: self.span(expr[0].start, expr[expr.len() - 1].end()),
+            span,
+        })
+    }
+
+    /// a string of assembly, split at its `${name}` placeholders
+    fn asm_line(
+        &self,
+        token: Token,
+        operands: &[AsmOperand],
+        header: Span,
+    ) -> Option<Vec<AsmPiece>> {
+        let text = self.text(token);
+        let content = &text[1..text.len() - 1];
+        // where `content` starts in the source
+        let offset = token.start + 1;
+
+        let mut pieces = Vec::new();
+        let mut valid = true;
+        let mut rest = 0;
+        while let Some(found) = content[rest..].find("${") {
+            let open = rest + found;
+            push_text(&mut pieces, &content[rest..open]);
+            let Some(len) = content[open..].find('}') else {
+                let at = offset + open as u32;
+                self.asm_error(self.span(at, at + 2), "this `${` is never closed");
+                return None;
+            };
+            let close = open + len + 1;
+            let name = content[open + 2..close - 1].trim();
+            match operands.iter().position(|operand| operand.name == name) {
+                Some(idx) => pieces.push(AsmPiece::Operand(idx)),
+                None => {
+                    let declared = operands
+                        .iter()
+                        .map(|operand| format!("`{}`", operand.name))
+                        .collect::<Vec<_>>();
+                    let help = if declared.is_empty() {
+                        "this `@asm` doesn't bind any operands".to_string()
+                    } else {
+                        format!("the operands are {}", declared.join(", "))
+                    };
+                    UnknownAsmOperand::builder()
+                        .message(format!("`${{{name}}}` isn't an operand of this `@asm`"))
+                        .span(
+                            self.span(offset + open as u32, offset + close as u32),
+                            SpanKind::NoMeta,
+                        )
+                        .label(header, "the operands are bound here")
+                        .help(help)
+                        .report();
+                    valid = false;
+                }
+            }
+            rest = close;
+        }
+        push_text(&mut pieces, &content[rest..]);
+        valid.then_some(pieces)
+    }
+
+    fn token_span(&self, token: Token) -> Span {
+        self.span(token.start, token.end())
+    }
+
+    fn asm_error(&self, span: Span, message: impl Into<String>) {
+        InvalidAsmBlock::builder()
+            .message(message.into())
+            .span(span, SpanKind::NoMeta)
+            .report();
+    }
+}
+
+/// the index of the `)` matching the `(` that `tokens` starts with
+fn matching_paren(tokens: &[Token]) -> Option<usize> {
+    let mut depth = 0usize;
+    for (idx, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// adds the text between two placeholders, if there is any
+fn push_text(pieces: &mut Vec<AsmPiece>, raw: &str) {
+    if raw.is_empty() {
+        return;
+    }
+    // invalid escapes are already reported by the tokenizer
+    let text = unescape(raw, Mode::Str).unwrap_or_else(|| raw.to_string());
+    pieces.push(AsmPiece::Text(text));
+}
+
+#[test]
+#[cfg(test)]
+fn test_asm_blocks() {
+    use crate::{
+        betac_parser::traits::Source,
+        betac_tokenizer::run_tokenizer,
+        betac_util::{ptr::Ptr, source_map::FileId},
+    };
+
+    let parse = |text: &str| {
+        let tokens = run_tokenizer(text, FileId::DUMMY).collect::<Vec<_>>();
+        let source: Ptr<dyn Source> = Ptr::new(text.to_string());
+        let mut inner = ParseInner::new(
+            tokens.into_iter().skip(2),
+            source,
+            FileId::DUMMY,
+            text.len() as u32,
+        );
+        let block = inner.parse_asm(0);
+        // the whole block is read, even if it's malformed
+        let after = inner.iterator.find(|token| !token.kind.is_trivia());
+        (block, after.map(|token| inner.text(token).to_string()))
+    };
+
+    let (block, after) = parse(
+        "@asm(len => in(reg) len, code => inout(reg) code):\n    \"mov rdx, ${len};\"\n    \"mov ${code}, rax;\"\n@end;\nnext",
+    );
+    let block = block.unwrap();
+    assert_eq!(after.as_deref(), Some("next"));
+    assert_eq!(block.operands.len(), 2);
+    assert_eq!(block.operands[1].direction, AsmDirection::InOut);
+    assert_eq!(block.operands[1].class, "reg");
+    assert_eq!(block.operands[1].name, "code");
+    assert_eq!(
+        block.lines,
+        [
+            vec![
+                AsmPiece::Text("mov rdx, ".to_string()),
+                AsmPiece::Operand(0),
+                AsmPiece::Text(";".to_string())
+            ],
+            vec![
+                AsmPiece::Text("mov ".to_string()),
+                AsmPiece::Operand(1),
+                AsmPiece::Text(", rax;".to_string())
+            ],
+        ]
+    );
+
+    // every placeholder has to be an operand
+    let (block, after) = parse("@asm(code => in(reg) code): \"mov rax, ${exit};\" @end; next");
+    assert!(block.is_none());
+    assert_eq!(after.as_deref(), Some("next"));
+
+    // and every operand needs a direction
+    let (block, _) = parse("@asm(code => into(reg) code): \"syscall;\" @end;");
+    assert!(block.is_none());
+}
+
+#[test]
+#[cfg(test)]
+fn test_asm_in_functions() {
+    use crate::{
+        betac_errors::Emitter,
+        betac_parser::{traits::Parse, GlobalParser},
+        betac_tokenizer::run_tokenizer,
+        betac_util::source_map::SourceMap,
+    };
+
+    // `@asm` blocks are used in function bodies, where they're checked like anywhere else
+    let text = "pub defun main() => Void {\n    let code => 0;\n    \
+        @asm(code => in(reg) code): \"mov ${nope};\" @end;\n}\nlet after => 1;\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("asm_in_function.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let mut parser = GlobalParser::new(file, text.to_string(), tokens.into_iter());
+    while parser.next_expression() {}
+
+    assert_eq!(
+        Emitter::messages_in(file),
+        vec!["`${nope}` isn't an operand of this `@asm`".to_string()]
+    );
+}
@@ -0,0 +1,331 @@
+use crate::{
+    betac_ast::{
+        attr::{AttrArg, AttrArgs, AttrKind, Attribute, ItemKind},
+        Span,
+    },
+    betac_errors::{
+        parser_errors::{InvalidAttribute, MisplacedAttribute, UnknownAttribute},
+        Reportable, SpanKind,
+    },
+    betac_tokenizer::{
+        literal::{unescape, Mode},
+        token::{Kw, LiteralKind, Token, TokenKind},
+    },
+};
+
+use super::{split_list, ParseInner};
+
+impl<Iter> ParseInner<Iter>
+where
+    Iter: Iterator<Item = Token> + Clone,
+{
+    /// `@[Name, Name(arg)]`, whose `@` has already been read
+    ///
+    /// every attribute is checked against `AttrKind`'s table and the item after it,
+    /// and the ones that are valid wait in `self.attributes` for that item
+    pub(crate) fn parse_attributes(&mut self, start: u32) {
+        // the `[`
+        self.iterator.next();
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+        let close = loop {
+            let Some(token) = self.iterator.next() else {
+                InvalidAttribute::builder()
+                    .message("this `@[` is never closed".to_string())
+                    .span(self.span(start, start + 2), SpanKind::NoMeta)
+                    .label(
+                        self.span(self.end, self.end),
+                        "expected `]` before the end of the file",
+                    )
+                    .report();
+                return;
+            };
+            match token.kind {
+                kind if kind.is_trivia() => continue,
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightBrace if depth == 0 => break token,
+                TokenKind::RightBrace => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        };
+        let group = self.span(start, close.end());
+
+        let (item, item_span) = match self.next_item() {
+            Ok(item) => item,
+            Err(found) => {
+                let builder = MisplacedAttribute::builder()
+                    .message("attributes have to be put on an item".to_string())
+                    .span(group, SpanKind::NoMeta);
+                let builder = match found {
+                    Some(token) => builder.label(
+                        self.span(token.start, token.end()),
+                        format!("expected an item, found `{}`", self.text(token)),
+                    ),
+                    None => builder.note("there isn't anything after them"),
+                };
+                builder.report();
+                return;
+            }
+        };
+
+        for entry in split_list(&tokens) {
+            let Some(attribute) = self.attribute(entry) else {
+                continue;
+            };
+            let name = attribute.kind.name();
+            if !attribute.kind.targets().contains(&item) {
+                let targets = attribute
+                    .kind
+                    .targets()
+                    .iter()
+                    .map(|target| target.description())
+                    .collect::<Vec<_>>();
+                MisplacedAttribute::builder()
+                    .message(format!(
+                        "`@[{name}]` can't be put on {}",
+                        item.description()
+                    ))
+                    .span(attribute.span, SpanKind::NoMeta)
+                    .label(item_span, format!("this is {}", item.description()))
+                    .help(format!("it can only be put on {}", targets.join(" or ")))
+                    .report();
+                continue;
+            }
+            if let Some(first) = self
+                .attributes
+                .iter()
+                .find(|first| first.kind == attribute.kind)
+            {
+                InvalidAttribute::builder()
+                    .message(format!("`@[{name}]` is already on this item"))
+                    .span(attribute.span, SpanKind::NoMeta)
+                    .label(first.span, "it's first put here")
+                    .report();
+                continue;
+            }
+            self.attributes.push(attribute);
+        }
+    }
+
+    /// the sort of item after an attribute, and the span of its keyword, or the token
+    /// that's there instead, which is `None` at the end of the file
+    ///
+    /// this skips trivia, other attributes, and modifiers like `pub(pack)`
+    fn next_item(&self) -> Result<(ItemKind, Span), Option<Token>> {
+        let mut ahead = self.iterator.clone();
+        let mut prev = None;
+        // how deep the lookahead is in another `@[..]`, or the `(pack)` of `pub(pack)`
+        let mut depth = 0usize;
+        while let Some(token) = ahead.next() {
+            let kind = token.kind;
+            if kind.is_trivia() {
+                continue;
+            }
+            match kind {
+                TokenKind::LeftBrace | TokenKind::LeftParen if depth > 0 => depth += 1,
+                TokenKind::RightBrace | TokenKind::RightParen if depth > 0 => depth -= 1,
+                _ if depth > 0 => {}
+                TokenKind::At
+                    if ahead.clone().next().map(|next| next.kind) == Some(TokenKind::LeftBrace) => {
+                }
+                TokenKind::LeftBrace if prev == Some(TokenKind::At) => depth += 1,
+                TokenKind::LeftParen if prev == Some(TokenKind::Keyword(Kw::Pub)) => depth += 1,
+                TokenKind::Keyword(
+                    Kw::Pub | Kw::Priv | Kw::Unsafe | Kw::Static | Kw::Const | Kw::Extern,
+                ) => {}
+                TokenKind::Keyword(kw) if let Some(item) = ItemKind::from_keyword(kw) => {
+                    return Ok((item, self.span(token.start, token.end())));
+                }
+                _ => return Err(Some(token)),
+            }
+            prev = Some(kind);
+        }
+        Err(None)
+    }
+
+    /// `Name` or `Name(arg)`, checked against `AttrKind`'s table
+    fn attribute(&self, tokens: &[Token]) -> Option<Attribute> {
+        let span = self.span(tokens[0].start, tokens[tokens.len() - 1].end());
+        let (name, args) = match tokens {
+            [name] if name.kind == TokenKind::Ident => (*name, &[][..]),
+            [name, open, args @ .., close]
+                if name.kind == TokenKind::Ident
+                    && open.kind == TokenKind::LeftParen
+                    && close.kind == TokenKind::RightParen =>
+            {
+                (*name, args)
+            }
+            _ => {
+                self.attribute_error(span, "expected an attribute, like `NoReturn` or `Repr(C)`");
+                return None;
+            }
+        };
+
+        let text = self.text(name);
+        let Some(kind) = AttrKind::from_name(text) else {
+            let names = AttrKind::ALL.map(|kind| format!("`{}`", kind.name()));
+            let help = match AttrKind::ALL
+                .into_iter()
+                .find(|kind| kind.name().eq_ignore_ascii_case(text))
+            {
+                Some(kind) => format!(
+                    "attributes are case sensitive, so this has to be `{}`",
+                    kind.name()
+                ),
+                None => format!("the built-in attributes are {}", names.join(", ")),
+            };
+            UnknownAttribute::builder()
+                .message(format!("unknown attribute `@[{text}]`"))
+                .span(self.span(name.start, name.end()), SpanKind::NoMeta)
+                .help(help)
+                .report();
+            return None;
+        };
+
+        let name = kind.name();
+        let arg = match split_list(args)[..] {
+            [] => None,
+            [&[arg]] => Some(arg),
+            [&[_, ..]] => {
+                self.attribute_error(span, format!("the argument of `@[{name}]` is one token"));
+                return None;
+            }
+            _ => {
+                self.attribute_error(span, format!("`@[{name}]` takes one argument"));
+                return None;
+            }
+        };
+
+        let arg = match (kind.args(), arg) {
+            (AttrArgs::Nothing | AttrArgs::OptionalString | AttrArgs::OptionalChoice(_), None) => {
+                None
+            }
+            (AttrArgs::Choice(choices), None) => {
+                InvalidAttribute::builder()
+                    .message(format!("`@[{name}]` needs an argument"))
+                    .span(span, SpanKind::NoMeta)
+                    .help(format!("it takes {}", one_of(choices)))
+                    .report();
+                return None;
+            }
+            (AttrArgs::Nothing, Some(_)) => {
+                self.attribute_error(span, format!("`@[{name}]` doesn't take an argument"));
+                return None;
+            }
+            (AttrArgs::OptionalString, Some(arg))
+                if arg.kind == TokenKind::Literal(LiteralKind::Str) =>
+            {
+                let text = self.text(arg);
+                // invalid escapes are already reported by the tokenizer
+                Some(AttrArg::Str(unescape(&text[1..text.len() - 1], Mode::Str)?))
+            }
+            (AttrArgs::OptionalString, Some(arg)) => {
+                self.attribute_error(
+                    self.span(arg.start, arg.end()),
+                    format!("`@[{name}]` takes a string"),
+                );
+                return None;
+            }
+            (AttrArgs::Choice(choices) | AttrArgs::OptionalChoice(choices), Some(arg))
+                if arg.kind == TokenKind::Ident && choices.contains(&self.text(arg)) =>
+            {
+                Some(AttrArg::Choice(self.text(arg).to_string()))
+            }
+            (AttrArgs::Choice(choices) | AttrArgs::OptionalChoice(choices), Some(arg)) => {
+                InvalidAttribute::builder()
+                    .message(format!(
+                        "`{}` isn't an argument of `@[{name}]`",
+                        self.text(arg)
+                    ))
+                    .span(self.span(arg.start, arg.end()), SpanKind::NoMeta)
+                    .help(format!("it takes {}", one_of(choices)))
+                    .report();
+                return None;
+            }
+        };
+        Some(Attribute { kind, arg, span })
+    }
+
+    fn attribute_error(&self, span: Span, message: impl Into<String>) {
+        InvalidAttribute::builder()
+            .message(message.into())
+            .span(span, SpanKind::NoMeta)
+            .report();
+    }
+}
+
+/// "`A`, `B` or `C`"
+fn one_of(choices: &[&str]) -> String {
+    let quoted = choices
+        .iter()
+        .map(|choice| format!("`{choice}`"))
+        .collect::<Vec<_>>();
+    match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {last}", rest.join(", ")),
+        _ => quoted.concat(),
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_attributes() {
+    use crate::{
+        betac_parser::traits::Source,
+        betac_tokenizer::run_tokenizer,
+        betac_util::{ptr::Ptr, source_map::FileId},
+    };
+
+    let parse = |text: &str| {
+        let tokens = run_tokenizer(text, FileId::DUMMY).collect::<Vec<_>>();
+        let source: Ptr<dyn Source> = Ptr::new(text.to_string());
+        let mut inner = ParseInner::new(
+            tokens.into_iter().skip(1),
+            source,
+            FileId::DUMMY,
+            text.len() as u32,
+        );
+        inner.parse_attributes(0);
+        // more groups of attributes for the same item
+        while let Some(token) = inner.iterator.find(|token| !token.kind.is_trivia())
+            && token.kind == TokenKind::At
+        {
+            inner.parse_attributes(token.start);
+        }
+        inner
+            .attributes
+            .into_iter()
+            .map(|attribute| (attribute.kind, attribute.arg))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        parse("@[NoReturn, Inline(Never)]\n/// docs\n@[Deprecated(\"use `exit`\")]\npub(pack) unsafe defun sysExit() => Void;"),
+        [
+            (AttrKind::NoReturn, None),
+            (AttrKind::Inline, Some(AttrArg::Choice("Never".to_string()))),
+            (AttrKind::Deprecated, Some(AttrArg::Str("use `exit`".to_string()))),
+        ]
+    );
+    assert_eq!(
+        parse("@[Repr(C)] pub extern obj Header;"),
+        [(AttrKind::Repr, Some(AttrArg::Choice("C".to_string())))]
+    );
+
+    // unknown attributes, bad arguments, and repeated attributes are left out
+    assert_eq!(
+        parse("@[noreturn, Repr, Inline(Sometimes), Cold(1), Export(x)] defun f() => Void;"),
+        []
+    );
+    assert_eq!(
+        parse("@[Test, Test] defun test() => Void;"),
+        [(AttrKind::Test, None)]
+    );
+    // and so are the ones that can't be put on the item, or aren't on an item at all
+    assert_eq!(
+        parse("@[NoReturn, Export] const let X: Int32 => 1;"),
+        [(AttrKind::Export, None)]
+    );
+    assert_eq!(parse("@[Cold] x => 1;"), []);
+    assert_eq!(parse("@[Cold]"), []);
+}
@@ -1,12 +1,20 @@
+mod asm;
 pub mod assign;
+mod attr;
 pub mod pproc;
+mod start;
 pub mod traits;
 
 use crate::{
-    betac_ast::{AstNode, AstToken, AtomicMetadata, Metadata, NoOp, SyntaxTree},
-    betac_errors::option::ResultExtension,
-    betac_tokenizer::token::{Token, TokenKind},
-    betac_util::{linked_list::LinkedList, ptr::Ptr, small_vec::SmallVec},
+    betac_ast::{
+        attr::Attribute, AstNode, AstToken, AtomicMetadata, Metadata, NoOp, Span, SyntaxTree,
+    },
+    betac_errors::{option::UnexpectedResult, Reportable},
+    betac_runner::Session,
+    betac_tokenizer::token::{DocStyle, Kw, Token, TokenKind},
+    betac_util::{
+        linked_list::LinkedList, ptr::Ptr, small_vec::SmallVec, source_map::FileId, trace::trace,
+    },
 };
 
 use traits::{Context, ContextKind, Parse, Source};
@@ -14,24 +22,94 @@ use traits::{Context, ContextKind, Parse, Source};
 struct ParseInner<Iter> {
     iterator: Iter,
     source: Ptr<dyn Source>,
+    file: FileId,
+    /// the length of the source, used as the end of the last token
+    end: u32,
+    /// `///` doc comments waiting for the next item
+    docs: Vec<Span>,
+    /// `//!` doc comments, which document the whole file
+    module_docs: Vec<Span>,
+    /// `@[Attribute]`s waiting for the next item, which have already been checked against it
+    attributes: Vec<Attribute>,
+    /// the signature of every function, for checking the function `@start` names
+    signatures: Vec<start::Signature>,
+    /// how many `pack name { .. }` bodies the parser is in
+    packs: usize,
 }
 
 impl<Iter> ParseInner<Iter>
 where
     Iter: Iterator<Item = Token> + Clone,
 {
-    pub(crate) fn new(iter: Iter, source: Ptr<dyn Source>) -> Self {
+    pub(crate) fn new(iter: Iter, source: Ptr<dyn Source>, file: FileId, end: u32) -> Self {
         Self {
             iterator: iter,
             source,
+            file,
+            end,
+            docs: Vec::new(),
+            module_docs: Vec::new(),
+            attributes: Vec::new(),
+            signatures: Vec::new(),
+            packs: 0,
         }
     }
 
+    /// makes a span in the current file, covering `start..end`
+    #[inline(always)]
+    pub(crate) fn span(&self, start: u32, end: u32) -> Span {
+        Span::new(self.file, start, end.saturating_sub(start))
+    }
+
     #[inline(always)]
     pub(crate) fn peek(&self) -> Option<Token> {
         self.iterator.clone().next()
     }
 
+    /// the start of the next token, or the end of the source if there isn't one
+    #[inline(always)]
+    pub(crate) fn next_start(&self) -> u32 {
+        self.peek().map(|token| token.start).unwrap_or(self.end)
+    }
+
+    /// the source text of `token`
+    ///
+    /// this doesn't look at the next token, since the preprocessor can leave
+    /// gaps between tokens, or move them out of order, like when it unrolls a `@for`
+    pub(crate) fn text(&self, token: Token) -> &str {
+        // SAFETY: `token` came from the tokenizer or the preprocessor,
+        // which only make tokens that are inside of the source
+        unsafe {
+            self.source
+                .reconstruct_from_start_end_unchecked(token.start, token.end())
+        }
+    }
+
+    /// panic-mode error recovery, called after `caught` has been reported
+    ///
+    /// skips tokens until just past the next `;` or `}`, or until the next token
+    /// starts a new top-level item (a keyword where `Kw::starts_item`, or a `@` macro).
+    /// if `caught` was itself a `;` or `}`, there is nothing to skip
+    pub(crate) fn recover(&mut self, caught: TokenKind) {
+        if matches!(caught, TokenKind::Semi | TokenKind::RightBracket) {
+            return;
+        }
+
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Semi | TokenKind::RightBracket => {
+                    self.iterator.next();
+                    return;
+                }
+                TokenKind::At => return,
+                TokenKind::Keyword(kw) if kw.starts_item() => return,
+                _ => {
+                    self.iterator.next();
+                }
+            }
+        }
+    }
+
     pub(crate) fn take_until(&mut self, mut f: impl FnMut(Token) -> bool) -> SmallVec<Token> {
         let mut vec = crate::svec![];
         while let Some(token) = self.iterator.next()
@@ -42,6 +120,100 @@ where
         vec
     }
 
+    /// skips an item that isn't parsed yet, up to the `;` that ends it, or the `}` that
+    /// closes its body
+    pub(crate) fn skip_item(&mut self) {
+        let mut depth = 0usize;
+        self.take_until(|token| match token.kind {
+            TokenKind::LeftBracket => {
+                depth += 1;
+                false
+            }
+            TokenKind::RightBracket => {
+                depth = depth.saturating_sub(1);
+                depth == 0
+            }
+            TokenKind::Semi => depth == 0,
+            _ => false,
+        });
+    }
+
+    /// skips a statement in a function body, which isn't parsed yet, up to the `;` that ends
+    /// it, or up to the `}` that closes the body, like the `c + b` in `{ let c => a; c + b }`
+    fn skip_statement(&mut self, first: Token) {
+        let mut depth = match first.kind {
+            TokenKind::Semi => return,
+            TokenKind::LeftBracket => 1usize,
+            _ => 0,
+        };
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::LeftBracket => depth += 1,
+                TokenKind::RightBracket if depth == 0 => return,
+                TokenKind::RightBracket => depth -= 1,
+                TokenKind::Semi if depth == 0 => {
+                    self.iterator.next();
+                    return;
+                }
+                _ => {}
+            }
+            self.iterator.next();
+        }
+    }
+
+    /// reads the body of the function after `defun`, up to the `}` that closes it,
+    /// or the `;` of a function that's only declared
+    ///
+    /// the statements in it are skipped, but its macros and items are parsed like any others
+    fn parse_body(&mut self, defun: Token) {
+        let open = self
+            .iterator
+            .by_ref()
+            .find(|token| matches!(token.kind, TokenKind::LeftBracket | TokenKind::Semi));
+        let Some(open) = open.filter(|token| token.kind == TokenKind::LeftBracket) else {
+            return;
+        };
+
+        let mut ctx = FunctionContext::new();
+        loop {
+            match self.peek() {
+                Some(token) if token.kind == TokenKind::RightBracket => {
+                    self.iterator.next();
+                    return;
+                }
+                Some(_) => {
+                    self.parse_expression(&mut ctx);
+                }
+                None => {
+                    UnexpectedResult::builder()
+                        .span(self.span(defun.start, open.end()))
+                        .message("the body of this function is never closed".to_string())
+                        .report();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// reads the `(pack)` of `pub(pack)`, whose `pub` has already been read, if it's there
+    fn pub_in_pack(&mut self) -> bool {
+        let mut ahead = self
+            .iterator
+            .clone()
+            .filter(|token| !token.kind.is_trivia())
+            .map(|token| token.kind);
+        let scoped = [ahead.next(), ahead.next(), ahead.next()]
+            == [
+                Some(TokenKind::LeftParen),
+                Some(TokenKind::Keyword(Kw::Pack)),
+                Some(TokenKind::RightParen),
+            ];
+        if scoped {
+            self.take_until(|token| token.kind == TokenKind::RightParen);
+        }
+        scoped
+    }
+
     /// the main function for parsing
     /// this returns a boolean, representing whether the iterator was terminated or not\n
     /// `false` represents that the iterator is done\n
@@ -51,86 +223,237 @@ where
             return false;
         };
 
-        println!("next_47: {next_token:?}");
+        trace!(Parser, Trace, "next token: {next_token:?}");
+        match next_token.kind {
+            TokenKind::DocComment(style) => {
+                let span = self.span(next_token.start, next_token.end());
+                match style {
+                    DocStyle::Outer => self.docs.push(span),
+                    DocStyle::Inner => self.module_docs.push(span),
+                }
+                return true;
+            }
+            kind if kind.is_trivia() => return true,
+            // the tokenizer has already reported these
+            TokenKind::Unknown => return true,
+            _ => {}
+        }
+
+        // statements aren't parsed yet, but the macros and items in a body are
+        if matches!(ctx.context_kind(), ContextKind::Function)
+            && !matches!(
+                next_token.kind,
+                TokenKind::At
+                    | TokenKind::Keyword(
+                        Kw::Defun | Kw::Obj | Kw::Comp | Kw::Trait | Kw::Extend | Kw::Alias
+                    )
+            )
+        {
+            self.skip_statement(next_token);
+            self.docs.clear();
+            self.attributes.clear();
+            return true;
+        }
+
         let (metadata, result): (Ptr<dyn AstNode>, bool) = match next_token {
             // preprocessor
             Token {
                 kind: TokenKind::At,
                 start,
+                ..
             } => {
-                println!("entered pproc");
+                trace!(Parser, Debug, "entering preprocessor macro at: {start}");
 
                 if self.peek().is_some_and(|t| t.kind == TokenKind::LeftBrace) {
-                    println!("found a tag!")
+                    trace!(Parser, Debug, "found a tag at: {start}");
+                    self.parse_attributes(start);
+                    // like modifiers, attributes are part of the next item
+                    return true;
                 }
 
-                let token = match unsafe {
-                    self.source
-                        .reconstruct_from_start_end_unchecked(start + 1, self.peek().unwrap().start)
-                        .trim()
-                } {
-                    "start" => {
-                        let tokens = self.take_until(|token| token.kind != TokenKind::Semi);
-                        self::pproc::parse_at_start(tokens)
+                if self.peek().is_none() {
+                    UnexpectedResult::builder()
+                        .span(self.span(start, self.end))
+                        .message("expected a preprocessor macro after `@`".to_string())
+                        .report();
+                    return false;
+                }
+
+                // the macro's name is the identifier right after the `@`
+                let name = match self.peek() {
+                    Some(token) if token.kind == TokenKind::Ident => {
+                        self.iterator.next();
+                        self.text(token)
                     }
-                    caught => super::catch!(caught),
+                    _ => "",
                 };
-                (Ptr::new(token), true)
+
+                match name {
+                    "start" => {
+                        self.record_start(start);
+                        let tokens = self.take_until(|token| token.kind == TokenKind::Semi);
+                        (Ptr::new(self::pproc::parse_at_start(tokens)), true)
+                    }
+                    "asm" => match self.parse_asm(start) {
+                        Some(block) => (Ptr::new(block), true),
+                        None => (Ptr::new(NoOp), true),
+                    },
+                    caught => super::catch!(pproc caught, start, self),
+                }
             }
             Token {
-                kind: TokenKind::Ident,
+                kind: TokenKind::Keyword(Kw::Let),
                 start,
+                ..
+            } => match ctx.context_kind() {
+                ContextKind::Global => {
+                    let tokens = self.take_until(|token| token.kind == TokenKind::Semi);
+                    trace!(Parser, Trace, "global assignment tokens: {tokens:?}");
+                    self::assign::parse_global_assignment(
+                        tokens,
+                        AtomicMetadata::get().to_metadata(),
+                    );
+                    (Ptr::new(NoOp), true)
+                }
+                ContextKind::Function | ContextKind::Object => {
+                    let caught = Kw::Let.name();
+                    super::catch!(caught, start, self)
+                }
+            },
+            // modifiers are part of the next item, so its doc comments are kept
+            Token {
+                kind:
+                    TokenKind::Keyword(
+                        modifier @ (Kw::Static
+                        | Kw::Pub
+                        | Kw::Priv
+                        | Kw::Const
+                        | Kw::Extern
+                        | Kw::Unsafe),
+                    ),
+                ..
             } => {
-                let next = self.peek().unwrap_or_emit();
-                println!("start: {start}");
-                println!("next: {next:?}");
-                println!("len: {}", self.source.length());
-                match unsafe {
-                    self.source
-                        .reconstruct_from_start_end_unchecked(start, next.start)
-                } {
-                    "let" => {
-                        match ctx.context_kind() {
-                            ContextKind::Function => {}
-                            ContextKind::Global => {
-                                let tokens = self.take_until(|token| token.kind != TokenKind::Semi);
-                                println!("tokens: {tokens:?}");
-                                self::assign::parse_global_assignment(
-                                    tokens,
-                                    AtomicMetadata::get().to_metadata(),
-                                );
-                            }
-                            ContextKind::Object => {}
-                        }
-
-                        todo!()
-                    }
-                    "static" => {
-                        AtomicMetadata::get().add_flag(Metadata::STATIC);
-                        (Ptr::new(NoOp), true)
-                    }
-                    caught => super::catch!(caught),
+                let flag = match modifier {
+                    Kw::Static => Metadata::STATIC,
+                    Kw::Pub if self.pub_in_pack() => Metadata::PUBLIC_IN_PACK,
+                    Kw::Pub => Metadata::PUBLIC,
+                    Kw::Const => Metadata::CONST,
+                    Kw::Extern => Metadata::EXTERN,
+                    Kw::Unsafe => Metadata::UNSAFE,
+                    // items are private unless they're marked otherwise
+                    _ => return true,
+                };
+                AtomicMetadata::get().add_flag(flag);
+                return true;
+            }
+            // functions aren't parsed yet, but the signatures of the ones that aren't nested
+            // are kept for `@start`, and their bodies are read for the macros in them
+            Token {
+                kind: TokenKind::Keyword(Kw::Defun),
+                ..
+            } => {
+                if !matches!(ctx.context_kind(), ContextKind::Function) {
+                    self.record_signature(next_token);
+                }
+                self.parse_body(next_token);
+                (Ptr::new(NoOp), true)
+            }
+            // the items in the body of a pack are read like any others, and `pack name;`
+            // doesn't have anything in it to read
+            Token {
+                kind: TokenKind::Keyword(Kw::Pack),
+                ..
+            } => {
+                let end = self
+                    .iterator
+                    .by_ref()
+                    .find(|token| matches!(token.kind, TokenKind::LeftBracket | TokenKind::Semi));
+                if end.is_some_and(|token| token.kind == TokenKind::LeftBracket) {
+                    self.packs += 1;
                 }
+                (Ptr::new(NoOp), true)
             }
             Token {
-                kind: TokenKind::NewLine,
+                kind: TokenKind::RightBracket,
                 ..
-            } => (Ptr::new(NoOp), true),
+            } if self.packs > 0 => {
+                self.packs -= 1;
+                return true;
+            }
+            // these aren't parsed yet
+            Token {
+                kind:
+                    TokenKind::Keyword(
+                        Kw::Obj | Kw::Comp | Kw::Trait | Kw::Extend | Kw::Import | Kw::Alias,
+                    ),
+                ..
+            } => {
+                self.skip_item();
+                (Ptr::new(NoOp), true)
+            }
+            Token {
+                kind: TokenKind::Ident | TokenKind::Keyword(_),
+                start,
+                ..
+            } => {
+                trace!(
+                    Parser,
+                    Trace,
+                    "ident at: {start}..{}, source length: {}",
+                    next_token.end(),
+                    self.end
+                );
+                let caught = self.text(next_token);
+                super::catch!(caught, start, self)
+            }
             caught => super::catch!(tok caught, self),
         };
 
+        // doc comments and attributes only belong to the item right after them
+        let docs = std::mem::take(&mut self.docs);
+        let attributes = std::mem::take(&mut self.attributes);
+
         // if it is a dummy, we don't push
         if metadata.is_dummy() {
             result
         } else {
             // it's not a dummy, so we push
-            let token = AstToken::new(metadata);
+            let token = AstToken::annotated(metadata, docs, attributes);
             ctx.current_syntax_tree().push(&token);
             result
         }
     }
 }
 
+/// splits a list, like the operands of an `@asm`, at the commas that aren't nested in
+/// parentheses or brackets. a trailing comma is allowed
+fn split_list(list: &[Token]) -> Vec<&[Token]> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, token) in list.iter().enumerate() {
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => depth += 1,
+            TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket => {
+                depth = depth.saturating_sub(1)
+            }
+            TokenKind::Comma if depth == 0 => {
+                items.push(&list[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < list.len() {
+        items.push(&list[start..]);
+    }
+    // empty items, like the one in `@asm(,)`, are skipped
+    items
+        .into_iter()
+        .filter(|tokens| !tokens.is_empty())
+        .collect()
+}
+
 pub struct GlobalContext {
     tree: SyntaxTree,
     //source: Sso<'static>,
@@ -165,6 +488,40 @@ impl Context for GlobalContext {
     }
 }
 
+/// the body of a function, whose statements aren't parsed yet
+struct FunctionContext {
+    tree: SyntaxTree,
+}
+
+impl FunctionContext {
+    const fn new() -> Self {
+        Self {
+            tree: LinkedList::new(),
+        }
+    }
+}
+
+impl Context for FunctionContext {
+    #[inline(always)]
+    fn context_kind(&self) -> traits::ContextKind {
+        ContextKind::Function
+    }
+
+    fn symbol_is_in_scope(&self, _token: Token) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn current_syntax_tree(&mut self) -> &mut SyntaxTree {
+        &mut self.tree
+    }
+
+    #[inline(always)]
+    fn complete(self: Box<Self>) -> SyntaxTree {
+        self.tree
+    }
+}
+
 pub struct GlobalParser<Iter> {
     inner: ParseInner<Iter>,
     ctx: Box<dyn Context>,
@@ -174,13 +531,30 @@ impl<Iter> GlobalParser<Iter>
 where
     Iter: Iterator<Item = Token> + Clone,
 {
-    pub fn new(source: String, iter: Iter) -> Self {
+    pub fn new(file: FileId, source: String, iter: Iter) -> Self {
+        let end = source.len() as u32;
         let source: Ptr<dyn Source> = Ptr::new(source);
         Self {
-            inner: ParseInner::new(iter, source),
+            inner: ParseInner::new(iter, source, file, end),
             ctx: Box::new(GlobalContext::new()),
         }
     }
+
+    /// checks the program's `@start`, after the whole file has been parsed. a file only
+    /// needs one when it's built into a program, but one that's there is always checked
+    pub fn check_start(&self) {
+        let start = Session::start_point();
+        if start.is_some() || Session::has_build_mode_flag_set() {
+            self.inner.check_start(start)
+        }
+    }
+}
+
+impl<Iter> GlobalParser<Iter> {
+    /// the spans of the file's `//!` doc comments, in source order
+    pub fn module_docs(&self) -> &[Span] {
+        &self.inner.module_docs
+    }
 }
 
 impl<Iter> Parse for GlobalParser<Iter>
@@ -197,3 +571,72 @@ where
         self.inner.parse_expression(&mut *self.ctx)
     }
 }
+
+#[test]
+#[cfg(test)]
+fn test_global_let() {
+    use crate::{
+        betac_errors::Emitter, betac_tokenizer::run_tokenizer, betac_util::source_map::SourceMap,
+    };
+
+    let text = "let x: Int32 => 1;\nlet y => x;\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("global_let.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let mut parser = GlobalParser::new(file, text.to_string(), tokens.into_iter());
+    while parser.next_expression() {}
+
+    // the whole statement is read, so nothing after its first token is left over
+    assert_eq!(Emitter::messages_in(file), Vec::<String>::new());
+}
+
+#[test]
+#[cfg(test)]
+fn test_defun() {
+    use crate::{
+        betac_errors::Emitter, betac_tokenizer::run_tokenizer, betac_util::source_map::SourceMap,
+    };
+
+    let text = "defun add(a: Int32, b: Int32) => Int32 { let c => { a }; c + b }\n\
+        defun exit(code: Int32) => Void;\nlet x => 1;\n\
+        defun outer() => Void {\n    defun inner() => Int32 { ret 1; }\n    \
+        unsafe { inner(); }\n    @[Inline] defun last() => Void {}\n}\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("defun.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let mut parser = GlobalParser::new(file, text.to_string(), tokens.into_iter());
+    while parser.next_expression() {}
+
+    // the statements in a body are skipped without reporting anything, and only the functions
+    // that aren't nested can be started at
+    assert_eq!(Emitter::messages_in(file), Vec::<String>::new());
+    assert_eq!(
+        parser
+            .inner
+            .signatures
+            .iter()
+            .map(|sig| sig.name.as_str())
+            .collect::<Vec<_>>(),
+        ["add", "exit", "outer"]
+    );
+}
+
+#[test]
+#[cfg(test)]
+fn test_library_files() {
+    use crate::{
+        betac_errors::Emitter,
+        betac_preproc::{cfg::Cfg, preprocess},
+        betac_tokenizer::run_tokenizer,
+        betac_util::source_map::SourceMap,
+    };
+
+    let text = include_str!("../../library/core/sys/unix.blp");
+    let file = SourceMap::with_mut(|mut map| map.add_file("library/core/sys/unix.blp", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = preprocess(text, &tokens, file, &Cfg::host());
+    let mut parser = GlobalParser::new(file, expanded.text, expanded.tokens.into_iter());
+    while parser.next_expression() {}
+
+    // modifiers, packs, and the items that aren't parsed yet are all accepted
+    assert_eq!(Emitter::messages_in(file), Vec::<String>::new());
+    assert_eq!(parser.inner.packs, 0);
+}
@@ -0,0 +1,287 @@
+use crate::{
+    betac_ast::Span,
+    betac_errors::{
+        parser_errors::{DuplicateEntryPoint, InvalidEntryPoint, MissingEntryPoint},
+        Reportable, SpanKind,
+    },
+    betac_runner::{Session, StartPoint},
+    betac_tokenizer::token::{Kw, Token, TokenKind},
+};
+
+use super::{split_list, ParseInner};
+
+/// `defun name(..) => Ret`, which is kept so the function `@start` names can be checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Signature {
+    pub(crate) name: String,
+    /// the type of every parameter, without whitespace
+    pub(crate) params: Vec<String>,
+    /// the return type without whitespace, which is `None` if there isn't a `=>`
+    pub(crate) ret: Option<String>,
+    pub(crate) generic: bool,
+    /// from `defun` to the end of the return type
+    pub(crate) span: Span,
+}
+
+impl Signature {
+    /// whether a program can start at this function.
+    /// it takes nothing or the arguments of the program, and returns nothing or an exit code
+    pub(crate) fn can_start(&self) -> bool {
+        let params = self.params.len() <= 1 && self.params.iter().all(|ty| ty == "&[Str]");
+        let ret = matches!(self.ret.as_deref(), None | Some("Void" | "Int32"));
+        !self.generic && params && ret
+    }
+}
+
+impl<Iter> ParseInner<Iter>
+where
+    Iter: Iterator<Item = Token> + Clone,
+{
+    /// records where the program starts, for the `@start` at `start`. its tokens aren't
+    /// read, so they're still there for `pproc::parse_at_start`
+    pub(crate) fn record_start(&mut self, start: u32) {
+        let tokens = self
+            .iterator
+            .clone()
+            .take_while(|token| token.kind != TokenKind::Semi)
+            .filter(|token| !token.kind.is_trivia())
+            .collect::<Vec<_>>();
+        let point = match tokens[..] {
+            [name] if name.kind == TokenKind::Ident => {
+                let span = self.span(name.start, name.end());
+                match self.text(name) {
+                    "NO_START" => StartPoint::NoStart(span),
+                    name => StartPoint::Function(name.to_string(), span),
+                }
+            }
+            _ => {
+                InvalidEntryPoint::builder()
+                    .message(
+                        "expected the name of a function, or `NO_START`, after `@start`"
+                            .to_string(),
+                    )
+                    .span(
+                        self.span(start, start + "@start".len() as u32),
+                        SpanKind::NoMeta,
+                    )
+                    .help("the entry point is set like `@start main;`")
+                    .report();
+                return;
+            }
+        };
+
+        let span = point.span();
+        if let Err(first) = Session::set_start_point(point) {
+            DuplicateEntryPoint::builder()
+                .message("the program already has an entry point".to_string())
+                .span(span, SpanKind::NoMeta)
+                .label(first.span(), "it's first set here")
+                .report();
+        }
+    }
+
+    /// records the signature of the function after `defun`, if it's well-formed.
+    /// nothing is read, since the function is still parsed like any other item
+    pub(crate) fn record_signature(&mut self, defun: Token) {
+        let mut ahead = self
+            .iterator
+            .clone()
+            .filter(|token| !token.kind.is_trivia());
+        let Some(name) = ahead.next().filter(|name| name.kind == TokenKind::Ident) else {
+            return;
+        };
+
+        let mut next = ahead.next();
+        let generic = next.is_some_and(|token| token.kind == TokenKind::Lt);
+        if generic {
+            next = ahead
+                .by_ref()
+                .find(|token| token.kind == TokenKind::LeftParen);
+        }
+        if next.is_none_or(|token| token.kind != TokenKind::LeftParen) {
+            return;
+        }
+
+        let mut depth = 0usize;
+        let mut params = Vec::new();
+        let mut end = name.end();
+        for token in ahead.by_ref() {
+            match token.kind {
+                TokenKind::RightParen if depth == 0 => {
+                    end = token.end();
+                    break;
+                }
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth -= 1,
+                _ => {}
+            }
+            params.push(token);
+        }
+        // `name: Type`, where the type is everything after the first `:`
+        let params = split_list(&params)
+            .into_iter()
+            .map(|param| {
+                let colon = param
+                    .iter()
+                    .position(|token| token.kind == TokenKind::Colon);
+                self.joined(&param[colon.map_or(0, |colon| colon + 1)..])
+            })
+            .collect();
+
+        let mut ret = None;
+        let mut next = ahead.next();
+        if next.is_some_and(|token| token.kind == TokenKind::Keyword(Kw::Throws)) {
+            next = ahead.next();
+        }
+        if next.is_some_and(|token| token.kind == TokenKind::FatArrow) {
+            let ty = ahead
+                .take_while(|token| {
+                    !matches!(
+                        token.kind,
+                        TokenKind::LeftBracket
+                            | TokenKind::Semi
+                            | TokenKind::Keyword(Kw::Throws | Kw::Requires)
+                    )
+                })
+                .collect::<Vec<_>>();
+            if let Some(last) = ty.last() {
+                end = last.end();
+            }
+            ret = Some(self.joined(&ty));
+        }
+
+        self.signatures.push(Signature {
+            name: self.text(name).to_string(),
+            params,
+            ret,
+            generic,
+            span: self.span(defun.start, end),
+        });
+    }
+
+    /// checks that `start`, the program's entry point, is there, and that it names a function
+    /// in this file that a program can start at. a package, with `@start NO_START;`, doesn't
+    /// have one
+    pub(crate) fn check_start(&self, start: Option<StartPoint>) {
+        let (name, span) = match start {
+            Some(StartPoint::NoStart(_)) => return,
+            Some(StartPoint::Function(name, span)) => (name, span),
+            None => {
+                MissingEntryPoint::builder()
+                    .message("this program doesn't have an entry point".to_string())
+                    .span(self.span(0, 0), SpanKind::NoMeta)
+                    .help("add `@start main;`, or `@start NO_START;` if this is a package")
+                    .report();
+                return;
+            }
+        };
+
+        let mut candidates = self.signatures.iter().filter(|sig| sig.name == name);
+        let Some(first) = candidates.clone().next() else {
+            MissingEntryPoint::builder()
+                .message(format!(
+                    "there isn't a function called `{name}` to start at"
+                ))
+                .span(span, SpanKind::NoMeta)
+                .help(format!("add `defun {name}() => Void`"))
+                .report();
+            return;
+        };
+        if candidates.any(Signature::can_start) {
+            return;
+        }
+        InvalidEntryPoint::builder()
+            .message(format!("the program can't start at `{name}`"))
+            .span(first.span, SpanKind::NoMeta)
+            .label(span, "it's made the entry point here")
+            .note(
+                "the entry point isn't generic, takes nothing or `args: &[Str]`, \
+                and returns `Void` or `Int32`",
+            )
+            .report();
+    }
+
+    /// the text of `tokens`, without the whitespace between them
+    fn joined(&self, tokens: &[Token]) -> String {
+        tokens.iter().map(|&token| self.text(token)).collect()
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_entry_points() {
+    use super::{traits::Parse, GlobalParser};
+    use crate::{
+        betac_errors::Emitter, betac_tokenizer::run_tokenizer, betac_util::source_map::SourceMap,
+    };
+
+    // parses `text` as a file of its own, like `betac build` if `build` is set, checks its
+    // entry point, and returns the signatures it kept and what was reported
+    let parse = |name: &str, text: &str, build: bool| {
+        Session::with_start_point_reset(build, || {
+            let file = SourceMap::with_mut(|mut map| map.add_file(name, text));
+            let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+            let mut parser = GlobalParser::new(file, text.to_string(), tokens.into_iter());
+            while parser.next_expression() {}
+            parser.check_start();
+            (parser.inner.signatures, Emitter::messages_in(file))
+        })
+    };
+    let check = |name: &str, text: &str| parse(name, text, true);
+
+    let (sigs, errors) = check(
+        "start_main.beta",
+        "@start main;\n\
+        defun main(args: &[ Str ]) => Int32 { 0 }\n\
+        defun helper(a: Int32, b: &mut Int64) throws => Void;\n\
+        defun swap<T>(a: T) {}",
+    );
+    assert_eq!(errors, Vec::<String>::new());
+    let [main, helper, swap] = &sigs[..] else {
+        panic!("expected 3 signatures, found {sigs:?}");
+    };
+    assert_eq!(main.params, ["&[Str]"]);
+    assert_eq!(helper.params, ["Int32", "&mutInt64"]);
+    assert_eq!(helper.ret.as_deref(), Some("Void"));
+    assert_eq!(swap.ret, None);
+    assert!(main.can_start());
+    assert!(!helper.can_start() && !swap.can_start());
+
+    // a package doesn't have an entry point
+    let (_, errors) = check("start_package.beta", "@start NO_START;");
+    assert_eq!(errors, Vec::<String>::new());
+
+    // but a program does, and only one
+    let (_, errors) = check("start_missing.beta", "defun main() => Void {}");
+    assert_eq!(errors, ["this program doesn't have an entry point"]);
+    // when it's built, and not just compiled
+    let (_, errors) = parse("start_compiled.beta", "defun main() => Void {}", false);
+    assert_eq!(errors, Vec::<String>::new());
+    let (_, errors) = check(
+        "start_twice.beta",
+        "@start main;\n@start run;\ndefun main() => Void {}",
+    );
+    assert_eq!(errors, ["the program already has an entry point"]);
+
+    // which is a function in the file that a program can start at
+    let (_, errors) = check("start_unknown.beta", "@start run;\ndefun main() => Void {}");
+    assert_eq!(errors, ["there isn't a function called `run` to start at"]);
+    let (_, errors) = check(
+        "start_generic.beta",
+        "@start main;\ndefun main<T>(value: T) => T {}",
+    );
+    assert_eq!(errors, ["the program can't start at `main`"]);
+
+    // a `@start` that doesn't name anything isn't recorded
+    let (_, errors) = check(
+        "start_invalid.beta",
+        "@start main run;\ndefun main() => Void {}",
+    );
+    assert_eq!(
+        errors,
+        [
+            "expected the name of a function, or `NO_START`, after `@start`",
+            "this program doesn't have an entry point",
+        ]
+    );
+}
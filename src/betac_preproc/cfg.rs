@@ -0,0 +1,75 @@
+use crate::betac_runner::fx_hasher::FxHashMap;
+
+/// the target configuration that `@eval` conditions are checked against
+///
+/// every key maps to a single value, like `OS_FAMILY` to `UNIX`.
+/// names are compared as written, so `LINUX` and `linux` are different values
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cfg {
+    values: FxHashMap<String, String>,
+}
+
+impl Cfg {
+    /// a configuration without any keys
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// the configuration of the machine `betac` is running on
+    ///
+    /// `OS` is the upper case name rust uses for the OS, like `LINUX` or `MACOS`,
+    /// except that windows is `WIN32`. `OS_FAMILY` is `UNIX` or `WINDOWS`,
+    /// `ARCH` is the upper case architecture, like `X86_64`, and `CPU_BITS` is the pointer width
+    pub fn host() -> Self {
+        let os = match std::env::consts::OS {
+            "windows" => "WIN32".to_string(),
+            os => os.to_uppercase(),
+        };
+
+        let mut cfg = Self::empty();
+        cfg.set("OS", os);
+        cfg.set("OS_FAMILY", std::env::consts::FAMILY.to_uppercase());
+        cfg.set("ARCH", std::env::consts::ARCH.to_uppercase());
+        cfg.set("CPU_BITS", usize::BITS.to_string());
+        cfg
+    }
+
+    /// sets `key` to `value`, replacing the old value if there was one
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), value.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// parses the `KEY=VALUE` argument of `--cfg`
+    /// both sides are trimmed, and neither can be empty
+    pub fn parse_pair(arg: &str) -> Option<(String, String)> {
+        let (key, value) = arg.split_once('=')?;
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() || value.is_empty() {
+            return None;
+        }
+        Some((key.to_string(), value.to_string()))
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_cfg_pairs() {
+    assert_eq!(
+        Cfg::parse_pair("OS=LINUX"),
+        Some(("OS".to_string(), "LINUX".to_string()))
+    );
+    assert_eq!(
+        Cfg::parse_pair(" CPU_BITS = 32 "),
+        Some(("CPU_BITS".to_string(), "32".to_string()))
+    );
+    assert_eq!(Cfg::parse_pair("OS"), None);
+    assert_eq!(Cfg::parse_pair("=LINUX"), None);
+
+    let host = Cfg::host();
+    assert!(host.get("OS").is_some() && host.get("ARCH").is_some());
+    assert_eq!(host.get("CPU_BITS"), Some(usize::BITS.to_string().as_str()));
+}
@@ -0,0 +1,1404 @@
+//! a reader for the subset of C that `@external` understands, which translates the
+//! declarations in a header to beta_lang
+//!
+//! function prototypes, structs, enums, typedefs and `#define`s of integer constants are
+//! translated, and everything else, like globals or macros with parameters, is skipped.
+//! the header isn't run through a C preprocessor, so every branch of an `#if` is read,
+//! and only the first declaration of a name is kept
+//!
+//! the core C types become the core integer types, and pointers become `*unsafe T`, or
+//! `*unsafe mut T` if what they point to isn't `const`. beta_lang has no types for functions
+//! or unions, so function pointers are `*unsafe Void`, and unions are declared without fields,
+//! like structs that are only declared, or that have bitfields
+
+use crate::{
+    betac_ast::Span,
+    betac_errors::{preproc_errors::InvalidExternalHeader, Reportable, SpanKind},
+    betac_runner::fx_hasher::{FxHashMap, FxHashSet},
+    betac_tokenizer::token::{Kw, NumSuffix},
+    betac_util::{source_map::FileId, trace::trace},
+};
+
+use super::cfg::Cfg;
+
+/// words that don't change how a declaration is translated
+const IGNORED: &[&str] = &[
+    "extern",
+    "static",
+    "inline",
+    "__inline",
+    "__inline__",
+    "register",
+    "auto",
+    "volatile",
+    "__volatile__",
+    "restrict",
+    "__restrict",
+    "__restrict__",
+    "_Noreturn",
+    "__extension__",
+    "_Thread_local",
+    "__thread",
+    "__cdecl",
+    "__stdcall",
+    "__fastcall",
+];
+
+/// words with parentheses after them, neither of which change how a declaration is translated
+const IGNORED_CALLS: &[&str] = &[
+    "__attribute__",
+    "__attribute",
+    "__declspec",
+    "__asm__",
+    "__asm",
+    "asm",
+    "_Alignas",
+];
+
+/// the sizes of the C types that depend on the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataModel {
+    long_bits: u32,
+    pointer_bits: u32,
+}
+
+impl DataModel {
+    /// the data model of the target `cfg` describes
+    ///
+    /// pointers are `CPU_BITS` wide, and so is `long`, except on windows, where it's 32 bits
+    pub fn new(cfg: &Cfg) -> Self {
+        let pointer_bits = cfg
+            .get("CPU_BITS")
+            .and_then(|bits| bits.parse().ok())
+            .filter(|bits| matches!(bits, 16 | 32 | 64))
+            .unwrap_or(64);
+        let long_bits = match cfg.get("OS_FAMILY") {
+            Some("WINDOWS") => 32,
+            _ => pointer_bits.max(32),
+        };
+        Self {
+            long_bits,
+            pointer_bits,
+        }
+    }
+}
+
+/// the core integer type with `bits` bits
+fn int(bits: u32, signed: bool) -> NumSuffix {
+    match (bits, signed) {
+        (8, true) => NumSuffix::Int8,
+        (16, true) => NumSuffix::Int16,
+        (32, true) => NumSuffix::Int32,
+        (_, true) => NumSuffix::Int64,
+        (8, false) => NumSuffix::Uint8,
+        (16, false) => NumSuffix::Uint16,
+        (32, false) => NumSuffix::Uint32,
+        (_, false) => NumSuffix::Uint64,
+    }
+}
+
+/// the core type for a typedef from `stdint.h` or `stddef.h`, which every header can use
+fn builtin(name: &str, model: DataModel) -> Option<NumSuffix> {
+    Some(match name {
+        "int8_t" => NumSuffix::Int8,
+        "int16_t" => NumSuffix::Int16,
+        "int32_t" => NumSuffix::Int32,
+        "int64_t" => NumSuffix::Int64,
+        "uint8_t" => NumSuffix::Uint8,
+        "uint16_t" => NumSuffix::Uint16,
+        "uint32_t" => NumSuffix::Uint32,
+        "uint64_t" => NumSuffix::Uint64,
+        "size_t" | "uintptr_t" => int(model.pointer_bits, false),
+        "ssize_t" | "ptrdiff_t" | "intptr_t" => int(model.pointer_bits, true),
+        _ => return None,
+    })
+}
+
+/// a name that's a keyword in beta_lang gets a `_` after it, like `ret_`
+fn escape(name: &str) -> String {
+    match Kw::from_name(name) {
+        Some(_) => format!("{name}_"),
+        None => name.to_string(),
+    }
+}
+
+/// a declaration in beta_lang, translated from the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// the declaration, like `pub extern unsafe defun puts(s: *unsafe Int8) => Int32;`
+    pub text: String,
+    /// the name it was declared with in the header
+    pub origin: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Number,
+    Str,
+    Char,
+    /// `...`
+    Ellipsis,
+    /// a whole line that starts with `#`, like `#define`
+    Directive,
+    Punct(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CToken {
+    kind: Kind,
+    start: u32,
+    end: u32,
+}
+
+/// a parameter of a function, and its name, if it has one
+type Param = (Option<String>, CType);
+
+/// a C type, before it's translated
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CType {
+    Void,
+    Core(NumSuffix),
+    /// a typedef, or the tag of a struct or enum
+    Named(String),
+    Pointer {
+        to: Box<CType>,
+        mutable: bool,
+    },
+    Array {
+        of: Box<CType>,
+        /// `None` for `[]`
+        len: Option<u64>,
+        mutable: bool,
+    },
+    Function {
+        ret: Box<CType>,
+        params: Vec<Param>,
+        variadic: bool,
+    },
+    /// a struct or union without a tag, whose fields are in `Parser::anonymous`.
+    /// it's named after the typedef it's in, if it's in one
+    Anonymous(usize),
+}
+
+/// the beta_lang for `ty`, if it has one
+fn render(ty: &CType) -> Option<String> {
+    Some(match ty {
+        CType::Void => "Void".to_string(),
+        CType::Core(suffix) => suffix.name().to_string(),
+        CType::Named(name) => escape(name),
+        CType::Pointer { to, .. } if matches!(**to, CType::Function { .. }) => {
+            "*unsafe Void".to_string()
+        }
+        CType::Pointer { to, mutable: true } => format!("*unsafe mut {}", render(to)?),
+        CType::Pointer { to, mutable: false } => format!("*unsafe {}", render(to)?),
+        CType::Array {
+            of, len: Some(len), ..
+        } => format!("[{}; {len}]", render(of)?),
+        CType::Array { len: None, .. } | CType::Function { .. } | CType::Anonymous(_) => {
+            return None
+        }
+    })
+}
+
+/// parameters that are arrays or functions are really pointers to them
+fn decay(ty: &CType) -> CType {
+    match ty {
+        CType::Array { of, mutable, .. } => CType::Pointer {
+            to: of.clone(),
+            mutable: *mutable,
+        },
+        CType::Function { .. } => CType::Pointer {
+            to: Box::new(ty.clone()),
+            mutable: false,
+        },
+        _ => ty.clone(),
+    }
+}
+
+/// an integer constant, from a `#define` or an enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Const {
+    value: i128,
+    unsigned: bool,
+}
+
+impl Const {
+    /// the smallest of `Int32`, `Int64` and `Uint64` the value fits in,
+    /// or `Uint32` and `Uint64` if it's unsigned
+    fn ty(self) -> Option<NumSuffix> {
+        let fits = |suffix: NumSuffix| match suffix {
+            NumSuffix::Int32 => i32::try_from(self.value).is_ok(),
+            NumSuffix::Int64 => i64::try_from(self.value).is_ok(),
+            NumSuffix::Uint32 => u32::try_from(self.value).is_ok(),
+            _ => u64::try_from(self.value).is_ok(),
+        };
+        let candidates: &[NumSuffix] = match self.unsigned {
+            true => &[NumSuffix::Uint32, NumSuffix::Uint64],
+            false => &[NumSuffix::Int32, NumSuffix::Int64, NumSuffix::Uint64],
+        };
+        candidates.iter().copied().find(|suffix| fits(*suffix))
+    }
+}
+
+/// the value of an integer literal, like `0x1FUL`
+/// floats, and integers that don't fit in a `u64`, aren't constants
+fn int_literal(text: &str) -> Option<Const> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let unsigned = text[digits.len()..].contains(['u', 'U']);
+    let (digits, radix) = match digits.as_bytes() {
+        [b'0', b'x' | b'X', ..] => (&digits[2..], 16),
+        [b'0', b'b' | b'B', ..] => (&digits[2..], 2),
+        [b'0', _, ..] => (&digits[1..], 8),
+        _ => (digits, 10),
+    };
+    let value = u64::from_str_radix(&digits.replace('\'', ""), radix).ok()?;
+    Some(Const {
+        value: value as i128,
+        unsigned,
+    })
+}
+
+/// the value of a char literal, like `'a'` or `'\n'`
+fn char_literal(text: &str) -> Option<Const> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let value = match inner.as_bytes() {
+        [c] => *c as u32,
+        [b'\\', b'n'] => 10,
+        [b'\\', b't'] => 9,
+        [b'\\', b'r'] => 13,
+        [b'\\', b'x', hex @ ..] => u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?,
+        [b'\\', b'0'..=b'7', ..] => u32::from_str_radix(&inner[1..], 8).ok()?,
+        [b'\\', c] => *c as u32,
+        _ => return None,
+    };
+    Some(Const {
+        value: value as i128,
+        unsigned: false,
+    })
+}
+
+fn invalid(file: FileId, start: u32, end: u32, message: &str) -> InvalidExternalHeader {
+    InvalidExternalHeader::builder()
+        .message(message.to_string())
+        .span(Span::new(file, start, end - start), SpanKind::NoMeta)
+}
+
+/// where the line that starts at `idx` ends, with the lines it's continued on with a `\`.
+/// comments in it are skipped, even if they go on past the end of the line
+fn directive_end(source: &str, mut idx: usize) -> usize {
+    let bytes = source.as_bytes();
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\n' if !source[..idx].trim_end_matches('\r').ends_with('\\') => break,
+            b'/' if bytes.get(idx + 1) == Some(&b'*') => {
+                idx = source[idx + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| idx + end + 4);
+                continue;
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    idx
+}
+
+/// splits `source`, which starts at `offset` in `file`, into tokens, without whitespace
+/// and comments. comments and literals that are never closed are reported
+fn lex(source: &str, offset: u32, file: FileId) -> Vec<CToken> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+    let mut line_start = true;
+    while let Some(&c) = bytes.get(idx) {
+        let start = idx;
+        let kind = match c {
+            b'\n' => {
+                line_start = true;
+                idx += 1;
+                continue;
+            }
+            // the end of a line that goes on on the next one
+            b'\\' if source[idx + 1..].starts_with(['\n', '\r']) => {
+                idx += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            b'/' if bytes.get(idx + 1) == Some(&b'/') => {
+                idx = source[idx..]
+                    .find('\n')
+                    .map_or(bytes.len(), |end| idx + end);
+                continue;
+            }
+            b'/' if bytes.get(idx + 1) == Some(&b'*') => {
+                match source[idx + 2..].find("*/") {
+                    Some(end) => idx += end + 4,
+                    None => {
+                        let start = offset + start as u32;
+                        invalid(file, start, start + 2, "this comment is never closed").report();
+                        idx = bytes.len();
+                    }
+                }
+                continue;
+            }
+            b'#' if line_start => {
+                idx = directive_end(source, idx);
+                Kind::Directive
+            }
+            b'"' | b'\'' => {
+                idx += 1;
+                while let Some(&next) = bytes.get(idx)
+                    && next != c
+                    && next != b'\n'
+                {
+                    // an escape at the end of the file doesn't go past it
+                    idx = (idx + if next == b'\\' { 2 } else { 1 }).min(bytes.len());
+                }
+                if bytes.get(idx) == Some(&c) {
+                    idx += 1;
+                } else {
+                    let start = offset + start as u32;
+                    let what = match c {
+                        b'"' => "this string is never closed",
+                        _ => "this char is never closed",
+                    };
+                    invalid(file, start, start + 1, what).report();
+                }
+                match c {
+                    b'"' => Kind::Str,
+                    _ => Kind::Char,
+                }
+            }
+            c if c == b'_' || c == b'$' || c.is_ascii_alphabetic() => {
+                idx += 1;
+                while bytes
+                    .get(idx)
+                    .is_some_and(|c| *c == b'_' || *c == b'$' || c.is_ascii_alphanumeric())
+                {
+                    idx += 1;
+                }
+                Kind::Ident
+            }
+            c if c.is_ascii_digit()
+                || (c == b'.' && bytes.get(idx + 1).is_some_and(u8::is_ascii_digit)) =>
+            {
+                let hex = source[idx..].starts_with("0x") || source[idx..].starts_with("0X");
+                idx += 1;
+                while let Some(&next) = bytes.get(idx) {
+                    let exponent = match hex {
+                        true => matches!(bytes[idx - 1], b'p' | b'P'),
+                        false => matches!(bytes[idx - 1], b'e' | b'E'),
+                    };
+                    if !(next.is_ascii_alphanumeric()
+                        || matches!(next, b'_' | b'.' | b'\'')
+                        || (exponent && matches!(next, b'+' | b'-')))
+                    {
+                        break;
+                    }
+                    idx += 1;
+                }
+                Kind::Number
+            }
+            b'.' if source[idx..].starts_with("...") => {
+                idx += 3;
+                Kind::Ellipsis
+            }
+            c if c.is_ascii() => {
+                idx += 1;
+                Kind::Punct(c)
+            }
+            // there's nothing outside of comments and literals that isn't ascii
+            _ => {
+                idx += source[idx..].chars().next().map_or(1, char::len_utf8);
+                continue;
+            }
+        };
+        line_start = false;
+        tokens.push(CToken {
+            kind,
+            start: offset + start as u32,
+            end: offset + idx as u32,
+        });
+    }
+    tokens
+}
+
+/// a field of a struct
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    ty: CType,
+}
+
+/// what a declarator declares: its name, if it has one, and its type
+type Declared = (Option<(String, Span)>, CType);
+
+struct Parser<'a> {
+    source: &'a str,
+    file: FileId,
+    model: DataModel,
+    tokens: Vec<CToken>,
+    idx: usize,
+    /// the index of the closing bracket for every opening one
+    matching: Vec<usize>,
+    /// the values of the integer constants so far, from `#define`s and enums
+    consts: FxHashMap<String, Const>,
+    /// the names of the types and the values declared so far,
+    /// since only the first declaration of each is kept
+    types: FxHashSet<String>,
+    values: FxHashSet<String>,
+    /// every struct and union tag that's used, and where, so the ones that are never defined
+    /// can be declared without fields
+    tags: Vec<(String, Span)>,
+    /// the fields of every struct without a tag, which are `None` if they can't be translated
+    anonymous: Vec<Option<Vec<Field>>>,
+    items: Vec<Item>,
+}
+
+/// translates the declarations in the header `source`, which is registered as `file`
+///
+/// declarations that can't be translated are skipped, but a header whose comments, literals
+/// or brackets aren't closed is reported, since there's no telling where its declarations end
+pub fn translate(source: &str, file: FileId, model: DataModel) -> Vec<Item> {
+    let (directives, tokens) = lex(source, 0, file)
+        .into_iter()
+        .partition::<Vec<_>, _>(|token| token.kind == Kind::Directive);
+    let mut parser = Parser {
+        source,
+        file,
+        model,
+        matching: vec![usize::MAX; tokens.len()],
+        tokens,
+        idx: 0,
+        consts: FxHashMap::default(),
+        types: FxHashSet::default(),
+        values: FxHashSet::default(),
+        tags: Vec::new(),
+        anonymous: Vec::new(),
+        items: Vec::new(),
+    };
+    if !parser.balanced() {
+        return Vec::new();
+    }
+
+    for directive in directives {
+        parser.define(directive);
+    }
+    while parser.idx < parser.tokens.len() {
+        parser.top_level();
+    }
+    for (tag, span) in std::mem::take(&mut parser.tags) {
+        parser.obj(tag, span, None);
+    }
+
+    parser.items.sort_by_key(|item| item.origin.start_pos);
+    parser.items
+}
+
+impl<'a> Parser<'a> {
+    fn text(&self, token: CToken) -> &'a str {
+        &self.source[token.start as usize..token.end as usize]
+    }
+
+    fn span(&self, token: CToken) -> Span {
+        Span::new(self.file, token.start, token.end - token.start)
+    }
+
+    /// the word at `idx`, if there's one
+    fn word_at(&self, idx: usize) -> Option<&'a str> {
+        self.tokens
+            .get(idx)
+            .filter(|token| token.kind == Kind::Ident)
+            .map(|token| self.text(*token))
+    }
+
+    fn at_punct(&self, punct: u8) -> bool {
+        self.tokens
+            .get(self.idx)
+            .is_some_and(|token| token.kind == Kind::Punct(punct))
+    }
+
+    fn eat_punct(&mut self, punct: u8) -> bool {
+        let found = self.at_punct(punct);
+        if found {
+            self.idx += 1;
+        }
+        found
+    }
+
+    fn push(&mut self, text: String, origin: Span) {
+        self.items.push(Item { text, origin });
+    }
+
+    /// matches up the brackets, and reports the first one that isn't closed,
+    /// or that doesn't close anything
+    fn balanced(&mut self) -> bool {
+        let mut open = Vec::new();
+        for (idx, token) in self.tokens.iter().enumerate() {
+            let Kind::Punct(punct) = token.kind else {
+                continue;
+            };
+            let opening = match punct {
+                b'(' | b'[' | b'{' => {
+                    open.push(idx);
+                    continue;
+                }
+                b')' => b'(',
+                b']' => b'[',
+                b'}' => b'{',
+                _ => continue,
+            };
+            match open.pop() {
+                Some(start) if self.tokens[start].kind == Kind::Punct(opening) => {
+                    self.matching[start] = idx;
+                }
+                Some(start) => {
+                    let start = self.tokens[start];
+                    invalid(
+                        self.file,
+                        start.start,
+                        start.end,
+                        "this bracket is never closed",
+                    )
+                    .label(self.span(*token), "expected it to be closed before this")
+                    .report();
+                    return false;
+                }
+                None => {
+                    let message = format!("this `{}` doesn't close anything", punct as char);
+                    invalid(self.file, token.start, token.end, &message).report();
+                    return false;
+                }
+            }
+        }
+        let Some(&start) = open.last() else {
+            return true;
+        };
+        let start = self.tokens[start];
+        let eof = self.source.len() as u32;
+        invalid(
+            self.file,
+            start.start,
+            start.end,
+            "this bracket is never closed",
+        )
+        .label(
+            Span::new(self.file, eof, 0),
+            "expected it to be closed before the end of the header",
+        )
+        .report();
+        false
+    }
+
+    /// `#define NAME value`, if `value` is an integer constant
+    fn define(&mut self, directive: CToken) {
+        let text = &self.source[directive.start as usize + 1..directive.end as usize];
+        let tokens = lex(text, directive.start + 1, self.file);
+        let [keyword, name, value @ ..] = &tokens[..] else {
+            return;
+        };
+        if self.text(*keyword) != "define" || name.kind != Kind::Ident || value.is_empty() {
+            return;
+        }
+        // a macro with parameters
+        if value[0].kind == Kind::Punct(b'(') && value[0].start == name.end {
+            return;
+        }
+        let Some(value) = self.constant(value) else {
+            trace!(
+                Preproc,
+                Debug,
+                "skipped `#define {}` in a header",
+                self.text(*name)
+            );
+            return;
+        };
+        let Some(ty) = value.ty() else {
+            return;
+        };
+        self.constant_item(self.text(*name), self.span(*name), value, ty.name());
+    }
+
+    /// `pub const let NAME: Type => value;`
+    fn constant_item(&mut self, name: &str, span: Span, value: Const, ty: &str) {
+        if !self.values.insert(name.to_string()) {
+            return;
+        }
+        self.consts.insert(name.to_string(), value);
+        let text = format!("pub const let {}: {ty} => {};", escape(name), value.value);
+        self.push(text, span);
+    }
+
+    /// the value of the constant expression `tokens`, if it only uses integers,
+    /// the constants before it, casts, and arithmetic
+    fn constant(&self, tokens: &[CToken]) -> Option<Const> {
+        let mut idx = 0;
+        let value = self.binary(tokens, &mut idx, 0)?;
+        (idx == tokens.len()).then_some(value)
+    }
+
+    /// the binary operator at `idx`, its precedence, and how many tokens it takes
+    fn binary_op(&self, tokens: &[CToken], idx: usize) -> Option<(u8, u8, usize)> {
+        let punct = |idx: usize| match tokens.get(idx)?.kind {
+            Kind::Punct(punct) => Some(punct),
+            _ => None,
+        };
+        let op = punct(idx)?;
+        let doubled = punct(idx + 1) == Some(op) && tokens[idx + 1].start == tokens[idx].end;
+        Some(match (op, doubled) {
+            (b'|', false) => (op, 1, 1),
+            (b'^', false) => (op, 2, 1),
+            (b'&', false) => (op, 3, 1),
+            (b'<' | b'>', true) => (op, 4, 2),
+            (b'+' | b'-', false) => (op, 5, 1),
+            (b'*' | b'/' | b'%', false) => (op, 6, 1),
+            _ => return None,
+        })
+    }
+
+    fn binary(&self, tokens: &[CToken], idx: &mut usize, min: u8) -> Option<Const> {
+        let mut lhs = self.unary(tokens, idx)?;
+        while let Some((op, precedence, len)) = self.binary_op(tokens, *idx)
+            && precedence >= min
+        {
+            *idx += len;
+            let rhs = self.binary(tokens, idx, precedence + 1)?;
+            let value = match op {
+                b'|' => lhs.value | rhs.value,
+                b'^' => lhs.value ^ rhs.value,
+                b'&' => lhs.value & rhs.value,
+                b'<' => lhs
+                    .value
+                    .checked_shl(u32::try_from(rhs.value).ok().filter(|n| *n < 64)?)?,
+                b'>' => lhs
+                    .value
+                    .checked_shr(u32::try_from(rhs.value).ok().filter(|n| *n < 64)?)?,
+                b'+' => lhs.value.checked_add(rhs.value)?,
+                b'-' => lhs.value.checked_sub(rhs.value)?,
+                b'*' => lhs.value.checked_mul(rhs.value)?,
+                b'/' => lhs.value.checked_div(rhs.value)?,
+                _ => lhs.value.checked_rem(rhs.value)?,
+            };
+            if value < i64::MIN as i128 || value > u64::MAX as i128 {
+                return None;
+            }
+            lhs = Const {
+                value,
+                unsigned: lhs.unsigned || rhs.unsigned,
+            };
+        }
+        Some(lhs)
+    }
+
+    fn unary(&self, tokens: &[CToken], idx: &mut usize) -> Option<Const> {
+        let token = *tokens.get(*idx)?;
+        *idx += 1;
+        match token.kind {
+            Kind::Number => int_literal(self.text(token)),
+            Kind::Char => char_literal(self.text(token)),
+            Kind::Ident => self.consts.get(self.text(token)).copied(),
+            Kind::Punct(b'+') => self.unary(tokens, idx),
+            Kind::Punct(b'-') => {
+                let value = self.unary(tokens, idx)?;
+                Some(Const {
+                    value: -value.value,
+                    ..value
+                })
+            }
+            Kind::Punct(b'~') => {
+                let value = self.unary(tokens, idx)?;
+                Some(Const {
+                    value: !value.value,
+                    ..value
+                })
+            }
+            Kind::Punct(b'(') => {
+                let close = *idx
+                    + tokens[*idx..]
+                        .iter()
+                        .scan(1usize, |depth, token| {
+                            match token.kind {
+                                Kind::Punct(b'(') => *depth += 1,
+                                Kind::Punct(b')') => *depth -= 1,
+                                _ => {}
+                            }
+                            Some(*depth)
+                        })
+                        .position(|depth| depth == 0)?;
+                let inner = &tokens[*idx..close];
+                *idx = close + 1;
+                // a cast, like `(unsigned long)1`, which only names types
+                let cast = inner.iter().all(|token| {
+                    token.kind == Kind::Punct(b'*')
+                        || (token.kind == Kind::Ident
+                            && !self.consts.contains_key(self.text(*token)))
+                });
+                if cast && *idx < tokens.len() {
+                    let value = self.unary(tokens, idx)?;
+                    let unsigned = inner.iter().any(|token| {
+                        let text = self.text(*token);
+                        text == "unsigned" || text.starts_with("uint") || text == "size_t"
+                    });
+                    // the value would wrap around, which depends on how wide the type is
+                    if unsigned && value.value < 0 {
+                        return None;
+                    }
+                    return Some(Const {
+                        unsigned: value.unsigned || unsigned,
+                        ..value
+                    });
+                }
+                self.constant(inner)
+            }
+            _ => None,
+        }
+    }
+
+    /// one declaration, or whatever's in the way of the next one
+    fn top_level(&mut self) {
+        let start = self.idx;
+        match self.tokens[start].kind {
+            // a `}` at the top level closes an `extern "C" {`
+            Kind::Punct(b';' | b'}') => {
+                self.idx += 1;
+                return;
+            }
+            Kind::Ident
+                if self.word_at(start) == Some("extern")
+                    && self
+                        .tokens
+                        .get(start + 1)
+                        .is_some_and(|token| token.kind == Kind::Str) =>
+            {
+                self.idx += 2;
+                self.eat_punct(b'{');
+                return;
+            }
+            _ => {}
+        }
+
+        let parsed = match self.word_at(start) {
+            Some("typedef") => self.typedef(),
+            _ => self.declaration(),
+        };
+        if parsed.is_none() {
+            trace!(
+                Preproc,
+                Debug,
+                "skipped the declaration at {} of a header",
+                self.tokens[start].start
+            );
+            self.idx = start;
+            self.skip_declaration();
+        }
+    }
+
+    /// skips to the end of the declaration at `idx`, which is the `;` after it,
+    /// or the `}` of a function's body
+    fn skip_declaration(&mut self) {
+        let start = self.idx;
+        while let Some(&token) = self.tokens.get(self.idx) {
+            match token.kind {
+                Kind::Punct(b';') => {
+                    self.idx += 1;
+                    return;
+                }
+                Kind::Punct(b'(' | b'[' | b'{') => {
+                    let open = self.idx;
+                    self.idx = self.matching[open] + 1;
+                    // a function's body isn't followed by a `;`
+                    let body = token.kind == Kind::Punct(b'{')
+                        && open > start
+                        && self.tokens[open - 1].kind == Kind::Punct(b')');
+                    if body {
+                        return;
+                    }
+                }
+                // the end of an `extern "C" {` the declaration is in
+                Kind::Punct(b'}') => return,
+                _ => self.idx += 1,
+            }
+        }
+    }
+
+    /// skips the words in `IGNORED` and `IGNORED_CALLS`
+    fn skip_ignored(&mut self) {
+        while let Some(word) = self.word_at(self.idx) {
+            if IGNORED.contains(&word) {
+                self.idx += 1;
+            } else if IGNORED_CALLS.contains(&word) {
+                self.idx += 1;
+                if self.at_punct(b'(') {
+                    self.idx = self.matching[self.idx] + 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// `typedef T name;`, which becomes `pub alias name = T;`
+    fn typedef(&mut self) -> Option<()> {
+        self.idx += 1;
+        let (base, is_const) = self.specifiers()?;
+        loop {
+            let (name, ty) = self.declarator(base.clone(), is_const)?;
+            let (name, span) = name?;
+            self.alias(name, span, &ty);
+            if !self.eat_punct(b',') {
+                break;
+            }
+        }
+        self.eat_punct(b';').then_some(())
+    }
+
+    fn alias(&mut self, name: String, span: Span, ty: &CType) {
+        // these are already the core integer types
+        if builtin(&name, self.model).is_some() {
+            return;
+        }
+        let target = match ty {
+            CType::Anonymous(idx) => {
+                let fields = self.anonymous[*idx].clone();
+                self.obj(name, span, fields);
+                return;
+            }
+            // `typedef struct point point;`
+            CType::Named(tag) if *tag == name => return,
+            // a function type can only be used behind a pointer, and those are `*unsafe Void`
+            CType::Function { .. } => "Void".to_string(),
+            ty => match render(ty) {
+                Some(target) => target,
+                None => return,
+            },
+        };
+        if self.types.insert(name.clone()) {
+            self.push(format!("pub alias {} = {target};", escape(&name)), span);
+        }
+    }
+
+    /// a function prototype, or a struct or enum, along with the variables declared with it.
+    /// variables aren't declared, and functions with a body aren't either,
+    /// since they aren't in the library that's linked against
+    fn declaration(&mut self) -> Option<()> {
+        let (base, is_const) = self.specifiers()?;
+        if self.eat_punct(b';') {
+            return Some(());
+        }
+        loop {
+            let (name, ty) = self.declarator(base.clone(), is_const)?;
+            if let CType::Function { .. } = ty
+                && let Some((name, span)) = name
+            {
+                if self.at_punct(b'{') {
+                    self.idx = self.matching[self.idx] + 1;
+                    return Some(());
+                }
+                self.function(&name, span, &ty);
+            }
+            if !self.eat_punct(b',') {
+                break;
+            }
+        }
+        self.eat_punct(b';').then_some(())
+    }
+
+    /// `pub extern unsafe defun name(a: A, ..) => Ret;`
+    fn function(&mut self, name: &str, span: Span, ty: &CType) {
+        let CType::Function {
+            ret,
+            params,
+            variadic,
+        } = ty
+        else {
+            return;
+        };
+        let mut args = Vec::with_capacity(params.len());
+        for (idx, (param, ty)) in params.iter().enumerate() {
+            let Some(ty) = render(&decay(ty)) else {
+                trace!(Preproc, Debug, "skipped `{name}` in a header");
+                return;
+            };
+            let param = param.as_deref().map_or(format!("arg{idx}"), escape);
+            args.push(format!("{param}: {ty}"));
+        }
+        if *variadic {
+            args.push("...".to_string());
+        }
+        let Some(ret) = render(ret) else {
+            return;
+        };
+        if self.values.insert(name.to_string()) {
+            let text = format!(
+                "pub extern unsafe defun {}({}) => {ret};",
+                escape(name),
+                args.join(", ")
+            );
+            self.push(text, span);
+        }
+    }
+
+    /// `pub extern obj name { pub field: Type; .. }`, or `pub extern obj name;`
+    /// if its fields are unknown, or can't be translated
+    fn obj(&mut self, name: String, span: Span, fields: Option<Vec<Field>>) {
+        if !self.types.insert(name.clone()) {
+            return;
+        }
+        let fields = fields.and_then(|fields| {
+            fields
+                .iter()
+                .map(|field| {
+                    Some(format!(
+                        "    pub {}: {};\n",
+                        escape(&field.name),
+                        render(&field.ty)?
+                    ))
+                })
+                .collect::<Option<Vec<_>>>()
+        });
+        let text = match fields {
+            Some(fields) => format!("pub extern obj {} {{\n{}}}", escape(&name), fields.concat()),
+            None => format!("pub extern obj {};", escape(&name)),
+        };
+        self.push(text, span);
+    }
+
+    /// the type the specifiers at `idx` name, like `unsigned long` or `struct point`,
+    /// and whether it's `const`. a struct or enum that's defined here is declared too
+    fn specifiers(&mut self) -> Option<(CType, bool)> {
+        let mut is_const = false;
+        let mut signed = None;
+        let mut words = Vec::new();
+        let mut ty = None;
+        while let Some(word) = self.word_at(self.idx) {
+            match word {
+                "const" | "__const" | "__const__" => is_const = true,
+                "signed" | "__signed" | "__signed__" => signed = Some(true),
+                "unsigned" => signed = Some(false),
+                "void" | "char" | "short" | "int" | "long" | "float" | "double" | "_Bool"
+                | "bool" => words.push(word),
+                "struct" | "union" | "enum" if ty.is_none() && words.is_empty() => {
+                    ty = Some(self.tagged()?);
+                    continue;
+                }
+                word if IGNORED.contains(&word) || IGNORED_CALLS.contains(&word) => {
+                    self.skip_ignored();
+                    continue;
+                }
+                // a typedef, which can only come first
+                word if ty.is_none() && words.is_empty() && signed.is_none() => {
+                    ty = Some(match builtin(word, self.model) {
+                        Some(suffix) => CType::Core(suffix),
+                        None => CType::Named(word.to_string()),
+                    });
+                }
+                _ => break,
+            }
+            self.idx += 1;
+        }
+
+        let ty = match ty {
+            Some(ty) if words.is_empty() && signed.is_none() => ty,
+            Some(_) => return None,
+            None => self.core(&words, signed)?,
+        };
+        Some((ty, is_const))
+    }
+
+    /// the core type for a list of words like `unsigned long long`
+    fn core(&self, words: &[&str], signed: Option<bool>) -> Option<CType> {
+        let has = |word| words.contains(&word);
+        let longs = words.iter().filter(|word| **word == "long").count();
+        if words.is_empty() && signed.is_none() {
+            return None;
+        }
+        let signed = signed != Some(false);
+        let suffix = match () {
+            _ if has("void") => return Some(CType::Void),
+            _ if has("char") => int(8, signed),
+            _ if has("_Bool") || has("bool") => NumSuffix::Uint8,
+            _ if has("float") => NumSuffix::Float32,
+            // `long double` is wider than any core type
+            _ if has("double") && longs > 0 => return None,
+            _ if has("double") => NumSuffix::Float64,
+            _ if has("short") => int(16, signed),
+            _ if longs >= 2 => int(64, signed),
+            _ if longs == 1 => int(self.model.long_bits, signed),
+            _ => int(32, signed),
+        };
+        Some(CType::Core(suffix))
+    }
+
+    /// `struct`, `union` or `enum`, with a tag, a body, or both
+    fn tagged(&mut self) -> Option<CType> {
+        let keyword = self.word_at(self.idx)?;
+        self.idx += 1;
+        self.skip_ignored();
+        let tag = match self.tokens.get(self.idx) {
+            Some(&token) if token.kind == Kind::Ident => {
+                self.idx += 1;
+                Some((self.text(token).to_string(), self.span(token)))
+            }
+            _ => None,
+        };
+
+        if !self.at_punct(b'{') {
+            let (tag, span) = tag?;
+            return Some(match keyword {
+                // an enum's values are `Int32`s, whether it's declared or not
+                "enum" if !self.types.contains(&tag) => CType::Core(NumSuffix::Int32),
+                "enum" => CType::Named(tag),
+                _ => {
+                    self.tags.push((tag.clone(), span));
+                    CType::Named(tag)
+                }
+            });
+        }
+
+        let close = self.matching[self.idx];
+        self.idx += 1;
+        let ty = match (keyword, tag) {
+            ("enum", Some((tag, span))) => {
+                if self.types.insert(tag.clone()) {
+                    self.push(format!("pub alias {} = Int32;", escape(&tag)), span);
+                }
+                self.enumerators(close, &escape(&tag));
+                CType::Named(tag)
+            }
+            ("enum", None) => {
+                self.enumerators(close, NumSuffix::Int32.name());
+                CType::Core(NumSuffix::Int32)
+            }
+            // unions have no beta_lang equivalent, so only their tags are declared
+            (_, tag) => {
+                let fields = match keyword {
+                    "struct" => self.fields(close),
+                    _ => None,
+                };
+                match tag {
+                    Some((tag, span)) => {
+                        self.obj(tag.clone(), span, fields);
+                        CType::Named(tag)
+                    }
+                    None => {
+                        self.anonymous.push(fields);
+                        CType::Anonymous(self.anonymous.len() - 1)
+                    }
+                }
+            }
+        };
+        self.idx = close + 1;
+        Some(ty)
+    }
+
+    /// the `NAME = value, ..` of an enum, up to `close`, which become constants of type `ty`.
+    /// once a value can't be worked out, neither can the ones after it, so they're skipped
+    fn enumerators(&mut self, close: usize, ty: &str) {
+        let mut next = Const {
+            value: 0,
+            unsigned: false,
+        };
+        while self.idx < close {
+            let Some(&name) = self
+                .tokens
+                .get(self.idx)
+                .filter(|token| token.kind == Kind::Ident)
+            else {
+                return;
+            };
+            self.idx += 1;
+            if self.eat_punct(b'=') {
+                let start = self.idx;
+                while self.idx < close && !self.at_punct(b',') {
+                    match self.tokens[self.idx].kind {
+                        Kind::Punct(b'(') => self.idx = self.matching[self.idx] + 1,
+                        _ => self.idx += 1,
+                    }
+                }
+                let Some(value) = self.constant(&self.tokens[start..self.idx]) else {
+                    return;
+                };
+                next = value;
+            }
+            self.constant_item(self.text(name), self.span(name), next, ty);
+            next.value += 1;
+            self.eat_punct(b',');
+        }
+    }
+
+    /// the fields of a struct, up to `close`,
+    /// or `None` if they can't be translated, like when there are bitfields
+    fn fields(&mut self, close: usize) -> Option<Vec<Field>> {
+        let mut fields = Vec::new();
+        while self.idx < close {
+            if self.eat_punct(b';') {
+                continue;
+            }
+            let (base, is_const) = self.specifiers()?;
+            loop {
+                let (name, ty) = self.declarator(base.clone(), is_const)?;
+                if self.at_punct(b':') {
+                    return None;
+                }
+                let (name, _) = name?;
+                fields.push(Field { name, ty });
+                if !self.eat_punct(b',') {
+                    break;
+                }
+            }
+            if !self.eat_punct(b';') {
+                return None;
+            }
+        }
+        Some(fields)
+    }
+
+    /// the name a declarator declares, if it has one, and its type, which is built on `base`
+    ///
+    /// like in C, it's read from the inside out, so `*names[4]` is an array of pointers,
+    /// and `(*handler)(int)` is a pointer to a function
+    fn declarator(&mut self, base: CType, base_const: bool) -> Option<Declared> {
+        let mut ty = base;
+        let mut is_const = base_const;
+        self.skip_ignored();
+        while self.eat_punct(b'*') {
+            ty = CType::Pointer {
+                to: Box::new(ty),
+                mutable: !is_const,
+            };
+            is_const = false;
+            loop {
+                match self.word_at(self.idx) {
+                    Some("const" | "__const" | "__const__") => {
+                        is_const = true;
+                        self.idx += 1;
+                    }
+                    Some(word) if IGNORED.contains(&word) || IGNORED_CALLS.contains(&word) => {
+                        self.skip_ignored();
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        // a declarator in parentheses, like the `(*handler)` of a function pointer
+        let nested = self.at_punct(b'(')
+            && self
+                .tokens
+                .get(self.idx + 1)
+                .is_some_and(|token| matches!(token.kind, Kind::Punct(b'*' | b'(' | b'^')));
+        if nested {
+            let open = self.idx;
+            let close = self.matching[open];
+            self.idx = close + 1;
+            let ty = self.suffixes(ty, is_const)?;
+            let after = self.idx;
+            self.idx = open + 1;
+            let declared = self.declarator(ty, false)?;
+            if self.idx != close {
+                return None;
+            }
+            self.idx = after;
+            return Some(declared);
+        }
+
+        let name = match self.tokens.get(self.idx) {
+            Some(&token) if token.kind == Kind::Ident => {
+                self.idx += 1;
+                Some((self.text(token).to_string(), self.span(token)))
+            }
+            _ => None,
+        };
+        let ty = self.suffixes(ty, is_const)?;
+        self.skip_ignored();
+        Some((name, ty))
+    }
+
+    /// the `[..]`s and `(..)`s after a declarator's name, applied to `ty` from the inside out
+    fn suffixes(&mut self, ty: CType, is_const: bool) -> Option<CType> {
+        enum Suffix {
+            Array(Option<u64>),
+            Function(Vec<Param>, bool),
+        }
+
+        let mut suffixes = Vec::new();
+        loop {
+            if self.at_punct(b'[') {
+                let open = self.idx;
+                let close = self.matching[open];
+                let len = match close - open {
+                    1 => None,
+                    _ => Some(
+                        u64::try_from(self.constant(&self.tokens[open + 1..close])?.value).ok()?,
+                    ),
+                };
+                suffixes.push(Suffix::Array(len));
+                self.idx = close + 1;
+            } else if self.at_punct(b'(') {
+                let (params, variadic) = self.params()?;
+                suffixes.push(Suffix::Function(params, variadic));
+            } else {
+                break;
+            }
+        }
+
+        let mut ty = ty;
+        for suffix in suffixes.into_iter().rev() {
+            ty = match suffix {
+                Suffix::Array(len) => CType::Array {
+                    of: Box::new(ty),
+                    len,
+                    mutable: !is_const,
+                },
+                Suffix::Function(params, variadic) => CType::Function {
+                    ret: Box::new(ty),
+                    params,
+                    variadic,
+                },
+            };
+        }
+        Some(ty)
+    }
+
+    /// the parameters of a function, and whether it takes a variable number of arguments
+    fn params(&mut self) -> Option<(Vec<Param>, bool)> {
+        let close = self.matching[self.idx];
+        self.idx += 1;
+        let mut params = Vec::new();
+        let mut variadic = false;
+        // `(void)` takes no arguments
+        if self.word_at(self.idx) == Some("void") && self.idx + 1 == close {
+            self.idx = close + 1;
+            return Some((params, variadic));
+        }
+        while self.idx < close {
+            if self.tokens[self.idx].kind == Kind::Ellipsis {
+                variadic = true;
+                self.idx += 1;
+            } else {
+                let (base, is_const) = self.specifiers()?;
+                let (name, ty) = self.declarator(base, is_const)?;
+                params.push((name.map(|(name, _)| name), ty));
+            }
+            if !self.eat_punct(b',') {
+                break;
+            }
+        }
+        if self.idx != close {
+            return None;
+        }
+        self.idx = close + 1;
+        Some((params, variadic))
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_header_translation() {
+    let linux = DataModel {
+        long_bits: 64,
+        pointer_bits: 64,
+    };
+    let translate = |source: &str, model| {
+        translate(source, FileId::DUMMY, model)
+            .into_iter()
+            .map(|item| item.text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let header = r#"
+#ifndef POINT_H
+#define POINT_H
+#include <stddef.h>
+
+#define POINT_MAX 0x10
+#define POINT_FLAG (1u << 3)
+#define POINT_NAME "point"
+#define POINT_SQUARE(x) ((x) * (x))
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct point {
+    int x, y;
+    const char *name; /* not owned */
+    unsigned char tag[4];
+} point_t;
+typedef struct { long a; } pair;
+struct node { struct node *next; int bits : 3; };
+typedef struct handle handle;
+
+enum color { RED, GREEN = POINT_MAX, BLUE };
+typedef enum { OFF = -1, ON = 1 } mode;
+typedef void (*callback)(int code, void *data);
+
+size_t point_len(const point_t *p);
+int printf(const char *restrict format, ...);
+void ret(int obj);
+static inline int twice(int x) { return x * 2; }
+extern int errno;
+int main(int argc, char *argv[]);
+void each(handle *h, callback cb) __attribute__((nonnull));
+
+#ifdef __cplusplus
+}
+#endif
+#endif
+"#;
+    assert_eq!(
+        translate(header, linux),
+        "pub const let POINT_MAX: Int32 => 16;
+pub const let POINT_FLAG: Uint32 => 8;
+pub extern obj point {
+    pub x: Int32;
+    pub y: Int32;
+    pub name: *unsafe Int8;
+    pub tag: [Uint8; 4];
+}
+pub alias point_t = point;
+pub extern obj pair {
+    pub a: Int64;
+}
+pub extern obj node;
+pub extern obj handle;
+pub alias color = Int32;
+pub const let RED: color => 0;
+pub const let GREEN: color => 16;
+pub const let BLUE: color => 17;
+pub const let OFF: Int32 => -1;
+pub const let ON: Int32 => 1;
+pub alias mode = Int32;
+pub alias callback = *unsafe Void;
+pub extern unsafe defun point_len(p: *unsafe point_t) => Uint64;
+pub extern unsafe defun printf(format: *unsafe Int8, ...) => Int32;
+pub extern unsafe defun ret_(obj_: Int32) => Void;
+pub extern unsafe defun main(argc: Int32, argv: *unsafe mut *unsafe mut Int8) => Int32;
+pub extern unsafe defun each(h: *unsafe mut handle, cb: callback) => Void;"
+    );
+
+    // `long` is 32 bits on windows
+    let windows = DataModel {
+        long_bits: 32,
+        pointer_bits: 64,
+    };
+    assert_eq!(
+        translate("unsigned long count(void);", windows),
+        "pub extern unsafe defun count() => Uint32;"
+    );
+
+    // there's no telling where the declarations end if a bracket isn't closed
+    assert_eq!(translate("struct point { int x;\nint f(void);", linux), "");
+
+    // and a literal that's cut off in the middle of an escape is still never closed
+    assert_eq!(translate("#define QUOTE '\\", linux), "");
+    assert_eq!(
+        translate("int f(void);\nchar *s = \"abc\\", linux),
+        "pub extern unsafe defun f() => Int32;"
+    );
+}
@@ -0,0 +1,54 @@
+use crate::{betac_ast::Span, betac_runner::fx_hasher::FxHashMap};
+
+use super::{cfg::Cfg, expr::Value};
+
+/// a constant defined with `@def NAME => ..;`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Def {
+    pub value: Value,
+    /// the name in the `@def`, where redefinitions point back to
+    pub span: Span,
+}
+
+/// the names a preprocessor expression can use
+///
+/// there's one of these for every file, since `@def`s are local to the file they're in.
+/// a `@def` shadows a configuration key with the same name
+#[derive(Debug)]
+pub struct Env<'a> {
+    cfg: &'a Cfg,
+    defs: FxHashMap<String, Def>,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(cfg: &'a Cfg) -> Self {
+        Self {
+            cfg,
+            defs: FxHashMap::default(),
+        }
+    }
+
+    /// the target configuration the file is preprocessed for
+    pub fn cfg(&self) -> &'a Cfg {
+        self.cfg
+    }
+
+    /// defines `name`, unless it's already defined,
+    /// in which case the first definition is kept and returned
+    pub fn define(&mut self, name: &str, def: Def) -> Result<(), &Def> {
+        // `entry` would need an owned key, even when the name is taken
+        if self.defs.contains_key(name) {
+            return Err(&self.defs[name]);
+        }
+        self.defs.insert(name.to_string(), def);
+        Ok(())
+    }
+
+    /// the value of `name`, from a `@def` or the target configuration
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        match self.defs.get(name) {
+            Some(def) => Some(def.value.clone()),
+            None => self.cfg.get(name).map(Value::from_cfg),
+        }
+    }
+}
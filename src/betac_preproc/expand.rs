@@ -0,0 +1,126 @@
+use crate::{
+    betac_ast::Span,
+    betac_tokenizer::token::TokenKind,
+    betac_util::source_map::{FileId, SourceMap},
+};
+
+use super::Expanded;
+
+/// where a line of the output came from: the file and 1-based line, and what made it,
+/// like "in this `@for`, where `N = 2`", if the preprocessor did
+type Origin = (FileId, u32, Option<String>);
+
+impl Expanded {
+    /// the code the parser sees, as source text, for `betac expand`
+    ///
+    /// with `markers`, a `// from file:line` comment is put before every line that doesn't
+    /// come right after the one before it in the source, like the lines of a `@for`
+    ///
+    /// this isn't always code that can be compiled again: the locals of a macro expansion are
+    /// renamed to names like `$m1_tmp`, which the parser sees as one name, but which are lexed
+    /// as a `$` and a name when written out
+    pub fn render(&self, file: FileId, markers: bool) -> String {
+        let mut out = String::with_capacity(self.text.len());
+        let mut line = String::new();
+        // where the first token of `line` that isn't trivia came from
+        let mut origin: Option<Origin> = None;
+        // where the next line comes from, if it follows the last one
+        let mut expected: Option<Origin> = None;
+        let mut prev: Option<TokenKind> = None;
+        let mut prev_end = 0;
+
+        SourceMap::with(|map| {
+            for token in &self.tokens {
+                if token.kind == TokenKind::NewLine {
+                    if markers {
+                        push_marker(&mut out, &map, origin.take(), &mut expected);
+                    }
+                    out.push_str(&line);
+                    out.push('\n');
+                    line.clear();
+                    prev = None;
+                    continue;
+                }
+
+                // tokens that were next to each other in the source aren't split up, but the
+                // ones the preprocessor put together are, so they're still read the same way
+                if !token.kind.is_trivia()
+                    && prev.is_some_and(|prev| !prev.is_trivia())
+                    && prev_end != token.start
+                {
+                    line.push(' ');
+                }
+                if markers && origin.is_none() && !token.kind.is_trivia() {
+                    let (span, frames) = map.resolve(Span::new(file, token.start, 0));
+                    origin = map.lookup(span).map(|loc| {
+                        let made_by = frames.into_iter().next().map(|(_, what)| what);
+                        (loc.file, loc.line, made_by)
+                    });
+                }
+                line.push_str(&self.text[token.start as usize..token.end() as usize]);
+                prev = Some(token.kind);
+                prev_end = token.end();
+            }
+            if !line.is_empty() {
+                if markers {
+                    push_marker(&mut out, &map, origin, &mut expected);
+                }
+                out.push_str(&line);
+                out.push('\n');
+            }
+        });
+        out
+    }
+}
+
+/// puts a marker before a line that came from `origin`, unless it follows the line before
+/// it, and moves `expected` to the line after it. blank lines follow whatever's before them
+fn push_marker(
+    out: &mut String,
+    map: &SourceMap,
+    origin: Option<Origin>,
+    expected: &mut Option<Origin>,
+) {
+    let Some(origin) = origin else {
+        if let Some((_, line, _)) = expected {
+            *line += 1;
+        }
+        return;
+    };
+    if expected.as_ref() != Some(&origin) {
+        let (file, line, made_by) = &origin;
+        let name = map.get(*file).map_or("<unknown>", |file| file.name());
+        out.push_str(&format!("// from {name}:{line}"));
+        if let Some(made_by) = made_by {
+            out.push_str(&format!(", {made_by}"));
+        }
+        out.push('\n');
+    }
+    let (file, line, made_by) = origin;
+    *expected = Some((file, line + 1, made_by));
+}
+
+#[test]
+#[cfg(test)]
+fn test_expanded_output() {
+    use super::{cfg::Cfg, preprocess};
+    use crate::betac_tokenizer::run_tokenizer;
+
+    let text = "@for(constexpr let I: Uint64 => 0..2):\n    let x => I;\n@end;\nlet y => 1;\nlet z => 2;\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("expand.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = preprocess(text, &tokens, file, &Cfg::empty());
+
+    assert_eq!(
+        expanded.render(file, false),
+        "\n    let x => 0Uint64;\n\n    let x => 1Uint64;\n\nlet y => 1;\nlet z => 2;\n"
+    );
+    // every copy of the loop is marked, and so is the line after it
+    assert_eq!(
+        expanded.render(file, true),
+        "\n\
+        // from expand.beta:2, in this `@for`, where `I = 0`\n    let x => 0Uint64;\n\n\
+        // from expand.beta:2, in this `@for`, where `I = 1`\n    let x => 1Uint64;\n\n\
+        // from expand.beta:4\nlet y => 1;\nlet z => 2;\n"
+    );
+}
@@ -0,0 +1,577 @@
+use crate::{
+    betac_ast::Span,
+    betac_errors::{
+        preproc_errors::{
+            InvalidPreprocCondition, InvalidPreprocLoop, InvalidPreprocTemplate, PreprocOverflow,
+            UndefinedPreprocName,
+        },
+        Reportable, SpanKind,
+    },
+    betac_tokenizer::{
+        literal::int_value,
+        token::{LiteralKind, Token, TokenKind},
+    },
+    betac_util::source_map::FileId,
+};
+
+use super::env::Env;
+
+/// the value of a preprocessor expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    /// a name that isn't defined anywhere, like `LINUX`,
+    /// or the value of a configuration key that isn't a number
+    Word(String),
+}
+
+impl Value {
+    /// a configuration value is a number if it looks like one, and a word otherwise
+    pub(super) fn from_cfg(value: &str) -> Self {
+        value
+            .parse()
+            .map(Self::Int)
+            .unwrap_or_else(|_| Self::Word(value.to_string()))
+    }
+
+    /// how the value is named in errors
+    fn describe(&self) -> String {
+        match self {
+            Self::Bool(_) => "a condition".to_string(),
+            Self::Int(value) => format!("the number `{value}`"),
+            Self::Word(word) => format!("the name `{word}`"),
+        }
+    }
+}
+
+/// a value, and the span of the expression it came from
+#[derive(Debug, Clone)]
+struct Operand {
+    value: Value,
+    span: Span,
+    /// whether this is a name that isn't a `@def` or a configuration key.
+    /// those can only be compared with a value from the configuration, like the `LINUX`
+    /// in `OS == LINUX`; anywhere else, they're reported as undefined
+    undefined: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinOp {
+    fn from_kind(kind: TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::PipePipe => Self::Or,
+            TokenKind::AndAnd => Self::And,
+            TokenKind::EqEq => Self::Eq,
+            TokenKind::NotEq => Self::Ne,
+            TokenKind::Lt => Self::Lt,
+            TokenKind::LtEq => Self::Le,
+            TokenKind::Gt => Self::Gt,
+            TokenKind::GtEq => Self::Ge,
+            TokenKind::Plus => Self::Add,
+            TokenKind::Minus => Self::Sub,
+            TokenKind::Star => Self::Mul,
+            TokenKind::ForwardSlash => Self::Div,
+            TokenKind::Percent => Self::Rem,
+            _ => return None,
+        })
+    }
+
+    /// operators with a higher precedence bind tighter
+    const fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Eq | Self::Ne => 3,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 4,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Rem => 6,
+        }
+    }
+
+    const fn symbol(self) -> &'static str {
+        match self {
+            Self::Or => "||",
+            Self::And => "&&",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+        }
+    }
+}
+
+/// evaluates a preprocessor expression, like the condition of an `@eval`
+/// the value of a `@def`, or a bound of a `@for`
+///
+/// ```text
+/// expr    := unary (binop unary)*
+/// unary   := ('!' | '-') unary | primary
+/// primary := name | integer | '(' expr ')'
+/// binop   := '||' | '&&' | '==' | '!=' | '<' | '<=' | '>' | '>='
+///          | '+' | '-' | '*' | '/' | '%'
+/// ```
+/// the operators are listed from loosest to tightest, in the same groups as in rust,
+/// and all of them are left associative. numbers are `Int64`s, and overflowing one is an error.
+/// names are looked up in the `Env`, and stand for themselves if they aren't defined
+pub struct Evaluator<'a> {
+    text: &'a str,
+    file: FileId,
+    env: &'a Env<'a>,
+    /// the tokens of the expression, without trivia
+    tokens: Vec<Token>,
+    idx: usize,
+    /// where the expression ends, which is where a missing operand is reported
+    end: u32,
+    place: Place,
+}
+
+/// what an expression is for, which changes how names that aren't defined are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Place {
+    /// a condition, or the value of a `@def`
+    Value,
+    /// a bound of a `@for`
+    Bound,
+    /// an argument of a template
+    Argument,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(text: &'a str, file: FileId, env: &'a Env<'a>, tokens: &[Token], end: u32) -> Self {
+        Self {
+            text,
+            file,
+            env,
+            tokens: tokens
+                .iter()
+                .filter(|token| !token.kind.is_trivia())
+                .copied()
+                .collect(),
+            idx: 0,
+            end,
+            place: Place::Value,
+        }
+    }
+
+    /// evaluates the whole expression as a condition
+    /// every error is reported here, and makes this return `None`
+    pub fn condition(mut self) -> Option<bool> {
+        let operand = self.all()?;
+        self.truth(&operand)
+    }
+
+    /// evaluates the whole expression as a number or a condition, which is what a `@def` holds
+    /// every error is reported here, and makes this return `None`
+    pub fn constant(mut self) -> Option<Value> {
+        let operand = self.all()?;
+        match operand.value {
+            Value::Word(_) if operand.undefined => {
+                self.undefined(&operand);
+                None
+            }
+            Value::Word(word) => {
+                self.error(
+                    operand.span,
+                    format!("expected a number or a condition, found the name `{word}`"),
+                );
+                None
+            }
+            value => Some(value),
+        }
+    }
+
+    /// evaluates the whole expression as a bound of a `@for`, which has to be a number
+    /// every error is reported here, and makes this return `None`
+    pub fn bound(mut self) -> Option<i64> {
+        self.place = Place::Bound;
+        let operand = self.all()?;
+        self.number(&operand)
+    }
+
+    /// evaluates the whole expression as an argument of a template, which has to be a number
+    /// every error is reported here, and makes this return `None`
+    pub fn argument(mut self) -> Option<i64> {
+        self.place = Place::Argument;
+        let operand = self.all()?;
+        self.number(&operand)
+    }
+
+    fn all(&mut self) -> Option<Operand> {
+        let operand = self.expr(0)?;
+        if let Some(token) = self.tokens.get(self.idx) {
+            self.error(
+                self.span(token.start, token.end()),
+                format!("expected an operator, found `{}`", self.token_text(token)),
+            );
+            return None;
+        }
+        Some(operand)
+    }
+
+    fn span(&self, start: u32, end: u32) -> Span {
+        Span::new(self.file, start, end - start)
+    }
+
+    fn token_text(&self, token: &Token) -> &'a str {
+        &self.text[token.start as usize..token.end() as usize]
+    }
+
+    fn span_text(&self, span: Span) -> &'a str {
+        &self.text[span.start_pos as usize..span.end_pos() as usize]
+    }
+
+    fn error(&self, span: Span, message: String) {
+        InvalidPreprocCondition::builder()
+            .message(message)
+            .span(span, SpanKind::NoMeta)
+            .report();
+    }
+
+    fn undefined(&self, operand: &Operand) {
+        let Value::Word(name) = &operand.value else {
+            unreachable!("only names can be undefined")
+        };
+        let constant = format!("`{name}` isn't a compile-time constant");
+        match self.place {
+            Place::Value => UndefinedPreprocName::builder()
+                .message(format!("cannot find `{name}` in this file"))
+                .span(operand.span, SpanKind::NoMeta)
+                .help(format!(
+                    "define it with `@def {name} => ..;`, or pass `--cfg {name}=..`"
+                ))
+                .report(),
+            Place::Bound => InvalidPreprocLoop::builder()
+                .message(constant)
+                .span(operand.span, SpanKind::NoMeta)
+                .note("the bounds of a `@for` can only use numbers, `@def`s and `--cfg` keys")
+                .help(format!("define it with `@def {name} => ..;`"))
+                .report(),
+            Place::Argument => InvalidPreprocTemplate::builder()
+                .message(constant)
+                .span(operand.span, SpanKind::NoMeta)
+                .note("template arguments can only use numbers, `@def`s and `--cfg` keys")
+                .help(format!("define it with `@def {name} => ..;`"))
+                .report(),
+        }
+    }
+
+    fn overflow(&self, span: Span, message: String) {
+        PreprocOverflow::builder()
+            .message(message)
+            .span(span, SpanKind::NoMeta)
+            .note("numbers in preprocessor expressions are `Int64`s")
+            .report();
+    }
+
+    fn expr(&mut self, min_precedence: u8) -> Option<Operand> {
+        let mut lhs = self.unary()?;
+        while let Some(token) = self.tokens.get(self.idx)
+            && let Some(op) = BinOp::from_kind(token.kind)
+            && op.precedence() >= min_precedence
+        {
+            self.idx += 1;
+            // `false && ..` and `true || ..` are decided by their left side, so the right side
+            // isn't evaluated, like the `10 / N` in `N != 0 && 10 / N > 2`
+            if matches!(op, BinOp::And | BinOp::Or) {
+                let truth = self.truth(&lhs)?;
+                if truth == (op == BinOp::Or) {
+                    let end = self.skip(op.precedence() + 1)?;
+                    lhs = Operand {
+                        value: Value::Bool(truth),
+                        span: self.span(lhs.span.start_pos, end),
+                        undefined: false,
+                    };
+                    continue;
+                }
+            }
+            let rhs = self.expr(op.precedence() + 1)?;
+            lhs = self.apply(op, lhs, rhs)?;
+        }
+        Some(lhs)
+    }
+
+    /// steps over an expression like `expr` would, without evaluating it, so only its syntax
+    /// is checked. returns where it ends
+    fn skip(&mut self, min_precedence: u8) -> Option<u32> {
+        let mut end = self.skip_operand()?;
+        while let Some(token) = self.tokens.get(self.idx)
+            && let Some(op) = BinOp::from_kind(token.kind)
+            && op.precedence() >= min_precedence
+        {
+            self.idx += 1;
+            end = self.skip(op.precedence() + 1)?;
+        }
+        Some(end)
+    }
+
+    fn skip_operand(&mut self) -> Option<u32> {
+        let Some(&token) = self.tokens.get(self.idx) else {
+            self.error(
+                self.span(self.end, self.end),
+                "expected a name, a number or `(`".to_string(),
+            );
+            return None;
+        };
+        self.idx += 1;
+        match token.kind {
+            TokenKind::Not | TokenKind::Minus => self.skip_operand(),
+            TokenKind::Ident | TokenKind::Literal(LiteralKind::Int { .. }) => Some(token.end()),
+            TokenKind::LeftParen => {
+                self.skip(0)?;
+                match self.tokens.get(self.idx) {
+                    Some(close) if close.kind == TokenKind::RightParen => {
+                        self.idx += 1;
+                        Some(close.end())
+                    }
+                    _ => {
+                        let at = self.tokens.get(self.idx).map_or(self.end, |t| t.start);
+                        self.error(self.span(at, at), "expected `)`".to_string());
+                        None
+                    }
+                }
+            }
+            _ => {
+                self.error(
+                    self.span(token.start, token.end()),
+                    format!(
+                        "expected a name, a number or `(`, found `{}`",
+                        self.token_text(&token)
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<Operand> {
+        let Some(&token) = self.tokens.get(self.idx) else {
+            return self.primary();
+        };
+        let (value, operand) = match token.kind {
+            TokenKind::Not => {
+                self.idx += 1;
+                let operand = self.unary()?;
+                (Value::Bool(!self.truth(&operand)?), operand)
+            }
+            TokenKind::Minus => {
+                self.idx += 1;
+                let operand = self.unary()?;
+                let span = self.span(token.start, operand.span.end_pos());
+                let Some(value) = self.number(&operand)?.checked_neg() else {
+                    self.overflow(span, "attempt to negate with overflow".to_string());
+                    return None;
+                };
+                (Value::Int(value), operand)
+            }
+            _ => return self.primary(),
+        };
+        Some(Operand {
+            value,
+            span: self.span(token.start, operand.span.end_pos()),
+            undefined: false,
+        })
+    }
+
+    fn primary(&mut self) -> Option<Operand> {
+        let Some(&token) = self.tokens.get(self.idx) else {
+            self.error(
+                self.span(self.end, self.end),
+                "expected a name, a number or `(`".to_string(),
+            );
+            return None;
+        };
+        self.idx += 1;
+
+        let span = self.span(token.start, token.end());
+        let (value, undefined) = match token.kind {
+            TokenKind::Ident => {
+                let name = self.token_text(&token);
+                match self.env.lookup(name) {
+                    Some(value) => (value, false),
+                    None => (Value::Word(name.to_string()), true),
+                }
+            }
+            TokenKind::Literal(LiteralKind::Int { base, .. }) => {
+                match int_value(self.token_text(&token), base).and_then(|v| v.try_into().ok()) {
+                    Some(value) => (Value::Int(value), false),
+                    None => {
+                        self.overflow(span, "integer literal is too large".to_string());
+                        return None;
+                    }
+                }
+            }
+            TokenKind::LeftParen => {
+                let inner = self.expr(0)?;
+                match self.tokens.get(self.idx) {
+                    Some(close) if close.kind == TokenKind::RightParen => {
+                        self.idx += 1;
+                        return Some(Operand {
+                            span: self.span(token.start, close.end()),
+                            ..inner
+                        });
+                    }
+                    _ => {
+                        let at = self.tokens.get(self.idx).map_or(self.end, |t| t.start);
+                        self.error(self.span(at, at), "expected `)`".to_string());
+                        return None;
+                    }
+                }
+            }
+            _ => {
+                self.error(
+                    span,
+                    format!(
+                        "expected a name, a number or `(`, found `{}`",
+                        self.token_text(&token)
+                    ),
+                );
+                return None;
+            }
+        };
+        Some(Operand {
+            value,
+            span,
+            undefined,
+        })
+    }
+
+    fn apply(&self, op: BinOp, lhs: Operand, rhs: Operand) -> Option<Operand> {
+        let span = self.span(lhs.span.start_pos, rhs.span.end_pos());
+        let value = match op {
+            BinOp::Or => Value::Bool(self.truth(&lhs)? | self.truth(&rhs)?),
+            BinOp::And => Value::Bool(self.truth(&lhs)? & self.truth(&rhs)?),
+            BinOp::Eq | BinOp::Ne => {
+                // `OS == LINUX` is fine, but in `FOO == LINUX`, `FOO` is likely a typo
+                if lhs.undefined && (rhs.undefined || !matches!(rhs.value, Value::Word(_))) {
+                    self.undefined(&lhs);
+                    return None;
+                }
+                if rhs.undefined && !matches!(lhs.value, Value::Word(_)) {
+                    self.undefined(&rhs);
+                    return None;
+                }
+                if std::mem::discriminant(&lhs.value) != std::mem::discriminant(&rhs.value) {
+                    InvalidPreprocCondition::builder()
+                        .message(format!(
+                            "can't compare {} with {}",
+                            lhs.value.describe(),
+                            rhs.value.describe()
+                        ))
+                        .span(lhs.span, SpanKind::NoMeta)
+                        .label(rhs.span, "compared with this")
+                        .report();
+                    return None;
+                }
+                Value::Bool((lhs.value == rhs.value) == (op == BinOp::Eq))
+            }
+            _ => {
+                let (a, b) = (self.number(&lhs)?, self.number(&rhs)?);
+                match op {
+                    BinOp::Lt => Value::Bool(a < b),
+                    BinOp::Le => Value::Bool(a <= b),
+                    BinOp::Gt => Value::Bool(a > b),
+                    BinOp::Ge => Value::Bool(a >= b),
+                    _ if matches!(op, BinOp::Div | BinOp::Rem) && b == 0 => {
+                        PreprocOverflow::builder()
+                            .message(format!("attempt to calculate `{a} {} 0`", op.symbol()))
+                            .span(span, SpanKind::NoMeta)
+                            .label(rhs.span, "this is zero")
+                            .report();
+                        return None;
+                    }
+                    _ => {
+                        let value = match op {
+                            BinOp::Add => a.checked_add(b),
+                            BinOp::Sub => a.checked_sub(b),
+                            BinOp::Mul => a.checked_mul(b),
+                            BinOp::Div => a.checked_div(b),
+                            _ => a.checked_rem(b),
+                        };
+                        let Some(value) = value else {
+                            self.overflow(
+                                span,
+                                format!("`{a} {} {b}` overflows an `Int64`", op.symbol()),
+                            );
+                            return None;
+                        };
+                        Value::Int(value)
+                    }
+                }
+            }
+        };
+        Some(Operand {
+            value,
+            span,
+            undefined: false,
+        })
+    }
+
+    /// the value of an operand that has to be a number
+    fn number(&self, operand: &Operand) -> Option<i64> {
+        match &operand.value {
+            Value::Int(value) => Some(*value),
+            _ if operand.undefined => {
+                self.undefined(operand);
+                None
+            }
+            value => {
+                self.error(
+                    operand.span,
+                    format!("expected a number, found {}", value.describe()),
+                );
+                None
+            }
+        }
+    }
+
+    /// a condition is true, and a number is true if it isn't `0`;
+    /// a name on its own isn't a condition
+    fn truth(&self, operand: &Operand) -> Option<bool> {
+        match &operand.value {
+            Value::Bool(value) => Some(*value),
+            Value::Int(value) => Some(*value != 0),
+            Value::Word(_) if operand.undefined => {
+                self.undefined(operand);
+                None
+            }
+            Value::Word(word) => {
+                let text = self.span_text(operand.span);
+                let message = if text == word {
+                    format!("`{word}` is a name, not a condition")
+                } else {
+                    format!("`{text}` is the name `{word}`, not a condition")
+                };
+                InvalidPreprocCondition::builder()
+                    .message(message)
+                    .span(operand.span, SpanKind::NoMeta)
+                    .help("compare it with `==` or `!=`, like `OS == LINUX`")
+                    .report();
+                None
+            }
+        }
+    }
+}
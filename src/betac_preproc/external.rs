@@ -0,0 +1,221 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use crate::{
+    betac_ast::Span,
+    betac_errors::{preproc_errors::MissingExternalHeader, Reportable, SpanKind},
+    betac_runner::{fx_hasher::FxHashMap, Session},
+    betac_tokenizer::{
+        literal::{unescape, Mode},
+        run_tokenizer,
+        token::{LiteralKind, Token, TokenKind},
+    },
+    betac_util::{
+        source_map::{FileId, SourceMap},
+        trace::trace,
+    },
+};
+
+use super::{
+    cheader::{self, DataModel},
+    Piece, Preprocessor,
+};
+
+/// the declarations made for every header that's been read, for every data model it's been
+/// read with
+type Headers = FxHashMap<(PathBuf, DataModel), Arc<[Piece]>>;
+
+/// every header is only read once, even if it's used by many files
+static HEADERS: LazyLock<Mutex<Headers>> = LazyLock::new(|| Mutex::new(FxHashMap::default()));
+
+impl Preprocessor<'_> {
+    /// `@external "header.h": .. @end;`, which declares what's in a C header
+    ///
+    /// the declarations replace the `@external "header.h":`, and are followed by its body,
+    /// which is preprocessed like any other code
+    pub(super) fn external(&mut self, at: Token) {
+        let statement = self.at_statement_start();
+        let start = self.idx;
+        self.idx += 2;
+        let header = self.header_name();
+        let call_site = self.span(at.start, self.prev_end());
+
+        let body = self.idx;
+        let Some(end) = self.block_end("`@external`", call_site, statement) else {
+            return;
+        };
+        let body = self.tokens[body..end].to_vec();
+
+        let mut tokens = Vec::new();
+        if let Some((name, span)) = header
+            && let Some(pieces) = self.header(&name, span)
+        {
+            let description = format!("in the declarations from `{name}`");
+            tokens = self.emit(pieces, call_site, description);
+        }
+        tokens.extend(body);
+        self.replace(start..self.idx, tokens);
+    }
+
+    /// reads the `"header.h":` after an `@external`
+    fn header_name(&mut self) -> Option<(String, Span)> {
+        let next = self.skip_trivia(self.idx);
+        let name = match self.tokens.get(next) {
+            Some(&token) if token.kind == TokenKind::Literal(LiteralKind::Str) => token,
+            _ => {
+                self.expected(self.idx, "the name of a header after `@external`");
+                self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+                return None;
+            }
+        };
+        self.idx = next + 1;
+        if !self.eat(TokenKind::Colon) {
+            self.expected(self.idx, "`:` after the header");
+        }
+
+        let text = self.token_text(&name);
+        // invalid escapes are already reported by the tokenizer
+        let path = unescape(&text[1..text.len() - 1], Mode::Str)?;
+        Some((path, self.span(name.start, name.end())))
+    }
+
+    /// the declarations for the header `name`, which is read the first time it's used
+    fn header(&self, name: &str, span: Span) -> Option<Vec<Piece>> {
+        let Some(path) = self.find_header(name) else {
+            MissingExternalHeader::builder()
+                .message(format!("couldn't find the header `{name}`"))
+                .span(span, SpanKind::NoMeta)
+                .note("headers are looked for next to this file, and in every `-I` directory")
+                .report();
+            return None;
+        };
+        let key = (path, DataModel::new(self.env.cfg()));
+        if let Some(pieces) = HEADERS.lock().unwrap().get(&key) {
+            return Some(pieces.to_vec());
+        }
+
+        let source = match std::fs::read(&key.0) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(err) => {
+                MissingExternalHeader::builder()
+                    .message(format!("couldn't read the header `{name}`: {err}"))
+                    .span(span, SpanKind::NoMeta)
+                    .report();
+                return None;
+            }
+        };
+        let file = SourceMap::with_mut(|mut map| {
+            map.add_file(key.0.display().to_string(), source.as_str())
+        });
+        let items = cheader::translate(&source, file, key.1);
+        trace!(
+            Preproc,
+            Info,
+            "`{}`: {} declarations",
+            key.0.display(),
+            items.len()
+        );
+
+        let mut pieces = Vec::new();
+        for item in items {
+            let text = item.text + "\n";
+            for token in run_tokenizer(&text, FileId::DUMMY) {
+                let piece = &text[token.start as usize..token.end() as usize];
+                pieces.push(Piece::new(token.kind, piece, item.origin));
+            }
+        }
+        let pieces = Arc::<[Piece]>::from(pieces);
+        HEADERS.lock().unwrap().insert(key, pieces.clone());
+        Some(pieces.to_vec())
+    }
+
+    /// where the header `name` is, looking next to the file first, and then in the `-I`
+    /// directories, in order. the path is canonical, so it can be used to cache the header
+    fn find_header(&self, name: &str) -> Option<PathBuf> {
+        // a file that isn't registered is in the working directory
+        let dir = SourceMap::with(|map| {
+            let file = map.get(self.file)?;
+            Path::new(file.name()).parent().map(Path::to_path_buf)
+        })
+        .unwrap_or_default();
+        let found = std::iter::once(dir)
+            .chain(Session::include_dirs())
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())?;
+        Some(std::fs::canonicalize(&found).unwrap_or(found))
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_external_headers() {
+    use super::{cfg::Cfg, expand_file_for_test};
+    use crate::{
+        betac_errors::Emitter,
+        betac_parser::{traits::Parse, GlobalParser},
+    };
+
+    /// removes the directory when the test ends, even if an assertion fails
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    let dir = TempDir(std::env::temp_dir().join(format!("betac_external_{}", std::process::id())));
+    std::fs::create_dir_all(&dir.0).unwrap();
+    std::fs::write(
+        dir.0.join("api.h"),
+        "#define API_VERSION 3\nint api_init(void);\n",
+    )
+    .unwrap();
+
+    let cfg = Cfg::empty();
+    let expand = |text: &str| {
+        let path = dir.0.join("main.beta").display().to_string();
+        let file = SourceMap::with_mut(|mut map| map.add_file(path, text));
+        (expand_file_for_test(text, file, &cfg), file)
+    };
+    let cached = || {
+        let key = (
+            std::fs::canonicalize(dir.0.join("api.h")).unwrap(),
+            DataModel::new(&cfg),
+        );
+        HEADERS.lock().unwrap().get(&key).cloned()
+    };
+
+    // headers are looked for next to the file, and the body is kept after the declarations
+    let declared = "pub const let API_VERSION : Int32 => 3 ; \
+        pub extern unsafe defun api_init ( ) => Int32 ;";
+    assert_eq!(
+        expand("@external \"api.h\":\n    let x => 1;\n@end;").0,
+        format!("{declared} let x => 1 ;")
+    );
+    let first = cached().unwrap();
+
+    // the second time, the header comes from the cache, instead of being read again
+    assert_eq!(expand("@external \"api.h\": @end;").0, declared);
+    assert!(Arc::ptr_eq(&first, &cached().unwrap()));
+
+    // the declarations are items the parser accepts
+    let text = "@external \"api.h\":\n    let x => 1;\n@end;\n";
+    let path = dir.0.join("parsed.beta").display().to_string();
+    let file = SourceMap::with_mut(|mut map| map.add_file(path, text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = super::preprocess(text, &tokens, file, &cfg);
+    let mut parser = GlobalParser::new(file, expanded.text, expanded.tokens.into_iter());
+    while parser.next_expression() {}
+    assert_eq!(Emitter::messages_in(file), Vec::<String>::new());
+
+    // a header that can't be found is reported, and doesn't declare anything
+    let (expanded, file) = expand("@external \"missing.h\": @end; after;");
+    assert_eq!(expanded, "after ;");
+    assert_eq!(
+        Emitter::messages_in(file),
+        vec!["couldn't find the header `missing.h`".to_string()]
+    );
+}
@@ -0,0 +1,979 @@
+use crate::{
+    betac_ast::Span,
+    betac_errors::{
+        preproc_errors::{
+            InvalidPreprocMacro, PreprocMacroMismatch, PreprocRecursionLimit, PreprocRedefinition,
+        },
+        Reportable, SpanKind,
+    },
+    betac_runner::fx_hasher::{FxHashMap, FxHashSet},
+    betac_tokenizer::token::{Kw, Token, TokenKind},
+    betac_util::trace::trace,
+};
+
+use super::{Macro, Piece, Preprocessor};
+
+/// how deep macro calls can be nested in the code their expansions make
+const MAX_DEPTH: usize = 64;
+/// the most macro calls a single file can expand, which stops macros that call
+/// themselves more than once from growing without end, even if they don't nest deeply
+const MAX_EXPANSIONS: usize = 65536;
+
+/// what a parameter of a macro matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fragment {
+    /// a name
+    Ident,
+    /// a literal, like `1` or `"text"`
+    Lit,
+    /// a single token, or everything in a pair of brackets
+    Tt,
+    /// the tokens up to the next `,`, `;` or `=>` that isn't in brackets
+    Expr,
+}
+
+impl Fragment {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ident" => Some(Self::Ident),
+            "lit" => Some(Self::Lit),
+            "tt" => Some(Self::Tt),
+            "expr" => Some(Self::Expr),
+            _ => None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Ident => "a name",
+            Self::Lit => "a literal",
+            Self::Tt => "a token",
+            Self::Expr => "an expression",
+        }
+    }
+}
+
+/// how many times a repetition can match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RepeatOp {
+    /// `*`
+    Any,
+    /// `+`
+    AtLeastOne,
+    /// `?`
+    AtMostOne,
+}
+
+/// a piece of the pattern of a macro
+enum Matcher {
+    /// a token that has to be in the call, like the `,` in `$a: expr, $b: expr`
+    Token(String),
+    /// `$name: fragment`
+    Param { name: String, fragment: Fragment },
+    /// `$(..) sep op`
+    Repeat {
+        matchers: Vec<Matcher>,
+        separator: Option<String>,
+        op: RepeatOp,
+    },
+}
+
+/// a piece of the body of a macro
+enum Transcriber {
+    /// a token that's copied as is, unless it's a local the macro declares,
+    /// which gets a name of its own for every expansion
+    Token { token: Token, local: bool },
+    /// `$name`, which is replaced with what the parameter matched
+    Param { name: String },
+    /// `$(..) sep op`, which is copied once for every time its parameters matched
+    Repeat {
+        body: Vec<Transcriber>,
+        separator: Option<Token>,
+    },
+}
+
+/// what a parameter matched in a call
+enum Captured {
+    One {
+        tokens: Vec<Token>,
+        fragment: Fragment,
+    },
+    /// what a parameter inside of a repetition matched, every time it was repeated
+    Many(Vec<Captured>),
+}
+
+type Captures = FxHashMap<String, Captured>;
+
+/// a `@macro defun name(..): .. @end;`
+pub(super) struct MacroDef {
+    /// the `@macro defun name(..):`
+    pub(super) span: Span,
+    pattern: Vec<Matcher>,
+    /// how many repetitions every parameter is inside of
+    params: FxHashMap<String, usize>,
+    body: Vec<Transcriber>,
+}
+
+/// a call that doesn't match the pattern of its macro
+struct Mismatch {
+    /// where in the call it stopped matching
+    at: usize,
+    expected: String,
+}
+
+impl Mismatch {
+    fn new(at: usize, expected: impl Into<String>) -> Self {
+        Self {
+            at,
+            expected: expected.into(),
+        }
+    }
+}
+
+/// the parameters that `body` repeats over, which are inside of more than `depth` repetitions
+fn drivers<'b>(
+    body: &'b [Transcriber],
+    params: &FxHashMap<String, usize>,
+    depth: usize,
+    found: &mut Vec<&'b str>,
+) {
+    for item in body {
+        match item {
+            Transcriber::Token { .. } => {}
+            Transcriber::Param { name } if params[name] > depth => found.push(name),
+            Transcriber::Param { .. } => {}
+            Transcriber::Repeat { body, .. } => drivers(body, params, depth, found),
+        }
+    }
+}
+
+/// the names of the parameters in `matchers`
+fn param_names<'m>(matchers: &'m [Matcher], names: &mut Vec<&'m str>) {
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(_) => {}
+            Matcher::Param { name, .. } => names.push(name),
+            Matcher::Repeat { matchers, .. } => param_names(matchers, names),
+        }
+    }
+}
+
+/// what `name` matched at `indices`, which is one of the times every repetition around it matched
+fn lookup<'c>(captures: &'c Captures, name: &str, indices: &[usize]) -> &'c Captured {
+    let mut captured = &captures[name];
+    for &idx in indices {
+        match captured {
+            Captured::Many(all) => captured = &all[idx],
+            Captured::One { .. } => break,
+        }
+    }
+    captured
+}
+
+impl Preprocessor<'_> {
+    /// `@macro defun name(..): .. @end;`, which defines a macro
+    ///
+    /// the pattern in the parentheses is made of tokens, which have to be in every call,
+    /// parameters like `$x: expr`, and repetitions like `$($x: expr),*`.
+    /// the body is only preprocessed once the macro is called, with the parameters replaced
+    pub(super) fn macro_def(&mut self, at: Token) {
+        let statement = self.at_statement_start();
+        self.idx += 2;
+        let header = self.macro_header();
+        let span = self.span(at.start, self.prev_end());
+
+        let body = self.idx;
+        let Some(end) = self.block_end("`@macro`", span, statement) else {
+            return;
+        };
+        let Some((name, pattern, params)) = header else {
+            return;
+        };
+        let name_span = self.span(name.start, name.end());
+        let name = self.token_text(&name).to_string();
+
+        let body = self.tokens[body..end].to_vec();
+        let locals = self.locals(&body);
+        let Some(body) = self.transcriber(&body, &params, &locals, 0) else {
+            return;
+        };
+        trace!(
+            Preproc,
+            Debug,
+            "`@macro` at {}: `{name}` with {} parameters",
+            at.start,
+            params.len()
+        );
+
+        if Macro::from_name(&name) != Macro::Other {
+            InvalidPreprocMacro::builder()
+                .message(format!("`@{name}` is a built-in macro"))
+                .span(name_span, SpanKind::NoMeta)
+                .help("give the macro a different name")
+                .report();
+            return;
+        }
+        let first = match (self.macros.get(&name), self.templates.get(&name)) {
+            (Some(first), _) => Some(first.span),
+            (_, Some(first)) => Some(first.span),
+            (None, None) => None,
+        };
+        if let Some(first) = first {
+            PreprocRedefinition::builder()
+                .message(format!("the macro `@{name}` is defined more than once"))
+                .span(name_span, SpanKind::NoMeta)
+                .label(first, "first defined here")
+                .note("macros and templates share their names, and can't be changed")
+                .report();
+            return;
+        }
+        self.macros.insert(
+            name,
+            MacroDef {
+                span,
+                pattern,
+                params,
+                body,
+            },
+        );
+    }
+
+    /// reads the `defun name(..):` after a `@macro`
+    /// returns the name of the macro, its pattern, and its parameters
+    fn macro_header(&mut self) -> Option<(Token, Vec<Matcher>, FxHashMap<String, usize>)> {
+        if !self.eat(TokenKind::Keyword(Kw::Defun)) {
+            self.expected(self.idx, "`defun` after `@macro`");
+            self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+            return None;
+        }
+        let name = self.skip_trivia(self.idx);
+        if self.tokens.get(name).map(|token| token.kind) != Some(TokenKind::Ident) {
+            self.expected(self.idx, "the name of the macro");
+            self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+            return None;
+        }
+        let name_token = self.tokens[name];
+        self.idx = name + 1;
+
+        let open = self.skip_trivia(self.idx);
+        if self.tokens.get(open).map(|token| token.kind) != Some(TokenKind::LeftParen) {
+            self.expected(self.idx, "`(` after the name of the macro");
+            self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+            return None;
+        }
+        let Some(close) = self.matching_paren(open) else {
+            self.idx = self.tokens.len();
+            return None;
+        };
+        let code = self.tokens[open + 1..close]
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .copied()
+            .collect::<Vec<_>>();
+        let mut params = FxHashMap::default();
+        let pattern = self.pattern(&code, 0, &mut params);
+
+        self.idx = close + 1;
+        if !self.eat(TokenKind::Colon) {
+            self.expected(self.idx, "`:` after the pattern");
+        }
+        Some((name_token, pattern?, params))
+    }
+
+    fn malformed(&self, token: &Token, message: String) {
+        InvalidPreprocMacro::builder()
+            .message(message)
+            .span(self.span(token.start, token.end()), SpanKind::NoMeta)
+            .report();
+    }
+
+    /// the index of the token that closes the `(` at `open` in `tokens`
+    fn close_paren(tokens: &[Token], open: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        tokens[open..]
+            .iter()
+            .position(|token| {
+                match token.kind {
+                    TokenKind::LeftParen => depth += 1,
+                    TokenKind::RightParen => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|close| open + close)
+    }
+
+    /// the `*`, `+` or `?` after the repetition that ends at `close`, with the separator before it
+    /// if there's one. returns them along with the index of the token after them
+    fn repeat_op(
+        &self,
+        tokens: &[Token],
+        close: usize,
+    ) -> Option<(Option<Token>, RepeatOp, usize)> {
+        let op = |idx: Option<usize>| match tokens[idx?].kind {
+            TokenKind::Star => Some(RepeatOp::Any),
+            TokenKind::Plus => Some(RepeatOp::AtLeastOne),
+            TokenKind::Question => Some(RepeatOp::AtMostOne),
+            _ => None,
+        };
+        let mut code = (close + 1..tokens.len()).filter(|&idx| !tokens[idx].kind.is_trivia());
+        let (first, second) = (code.next(), code.next());
+        if let Some(op) = op(first) {
+            return Some((None, op, first? + 1));
+        }
+        if let Some(op) = op(second) {
+            return Some((Some(tokens[first?]), op, second? + 1));
+        }
+        self.malformed(
+            &tokens[close],
+            "expected `*`, `+` or `?` after the repetition".to_string(),
+        );
+        None
+    }
+
+    /// the pattern of a macro, from the tokens between its parentheses, without trivia
+    /// collects how many repetitions every parameter is inside of, starting at `depth`
+    fn pattern(
+        &self,
+        tokens: &[Token],
+        depth: usize,
+        params: &mut FxHashMap<String, usize>,
+    ) -> Option<Vec<Matcher>> {
+        let mut matchers = Vec::new();
+        let mut idx = 0;
+        while let Some(token) = tokens.get(idx) {
+            if token.kind != TokenKind::Dollar {
+                matchers.push(Matcher::Token(self.token_text(token).to_string()));
+                idx += 1;
+                continue;
+            }
+
+            match tokens.get(idx + 1).map(|token| token.kind) {
+                Some(TokenKind::Ident) => {
+                    let name = self.token_text(&tokens[idx + 1]).to_string();
+                    let fragment = match tokens.get(idx + 2..idx + 4) {
+                        Some([colon, fragment]) if colon.kind == TokenKind::Colon => {
+                            Fragment::from_name(self.token_text(fragment))
+                        }
+                        _ => None,
+                    };
+                    let Some(fragment) = fragment else {
+                        self.malformed(
+                            &tokens[idx + 1],
+                            format!("expected a fragment after `${name}`, like `${name}: expr`"),
+                        );
+                        return None;
+                    };
+                    if params.insert(name.clone(), depth).is_some() {
+                        self.malformed(
+                            &tokens[idx + 1],
+                            format!("`${name}` is a parameter more than once"),
+                        );
+                        return None;
+                    }
+                    matchers.push(Matcher::Param { name, fragment });
+                    idx += 4;
+                }
+                Some(TokenKind::LeftParen) => {
+                    let Some(close) = Self::close_paren(tokens, idx + 1) else {
+                        self.malformed(&tokens[idx + 1], "this `(` is never closed".to_string());
+                        return None;
+                    };
+                    let inner = self.pattern(&tokens[idx + 2..close], depth + 1, params)?;
+                    if inner.is_empty() {
+                        self.malformed(&tokens[idx], "a repetition can't be empty".to_string());
+                        return None;
+                    }
+                    let (separator, op, next) = self.repeat_op(tokens, close)?;
+                    matchers.push(Matcher::Repeat {
+                        matchers: inner,
+                        separator: separator.map(|token| self.token_text(&token).to_string()),
+                        op,
+                    });
+                    idx = next;
+                }
+                _ => {
+                    self.malformed(token, "expected a parameter or `(` after `$`".to_string());
+                    return None;
+                }
+            }
+        }
+        Some(matchers)
+    }
+
+    /// the names the body of a macro binds, which are renamed in every expansion, so they
+    /// can't clash with the names around the call. these are the names of `let`s, including
+    /// the variables of `@for(constexpr let N ..)`, and the parameters of functions
+    fn locals(&self, body: &[Token]) -> FxHashSet<String> {
+        let code = body
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .collect::<Vec<_>>();
+        let mut locals = FxHashSet::default();
+        for (idx, token) in code.iter().enumerate() {
+            match token.kind {
+                TokenKind::Keyword(Kw::Let) => {
+                    let name = match code.get(idx + 1) {
+                        Some(next) if next.kind == TokenKind::Keyword(Kw::Mut) => code.get(idx + 2),
+                        next => next,
+                    };
+                    if let Some(name) = name
+                        && name.kind == TokenKind::Ident
+                    {
+                        locals.insert(self.token_text(name).to_string());
+                    }
+                }
+                TokenKind::Keyword(Kw::Defun) => self.fn_params(&code[idx + 1..], &mut locals),
+                _ => {}
+            }
+        }
+        locals
+    }
+
+    /// the `name: Type` parameters of the function whose name starts `code`
+    fn fn_params(&self, code: &[&Token], locals: &mut FxHashSet<String>) {
+        let Some(open) = code
+            .iter()
+            .take_while(|token| !matches!(token.kind, TokenKind::LeftBracket | TokenKind::Semi))
+            .position(|token| token.kind == TokenKind::LeftParen)
+        else {
+            return;
+        };
+        let mut depth = 0usize;
+        for (idx, token) in code.iter().enumerate().skip(open) {
+            match token.kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                // `$name: Type` is a parameter of the macro instead
+                TokenKind::Ident
+                    if depth == 1
+                        && matches!(
+                            code[idx - 1].kind,
+                            TokenKind::LeftParen | TokenKind::Comma | TokenKind::Keyword(Kw::Mut)
+                        )
+                        && code
+                            .get(idx + 1)
+                            .is_some_and(|next| next.kind == TokenKind::Colon) =>
+                {
+                    locals.insert(self.token_text(token).to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// the body of a macro, which is inside of `depth` repetitions
+    fn transcriber(
+        &self,
+        body: &[Token],
+        params: &FxHashMap<String, usize>,
+        locals: &FxHashSet<String>,
+        depth: usize,
+    ) -> Option<Vec<Transcriber>> {
+        let mut items = Vec::new();
+        let mut prev = None;
+        let mut idx = 0;
+        while let Some(&token) = body.get(idx) {
+            if token.kind != TokenKind::Dollar {
+                // a name after `@` is a macro, and a name after `.` is a field,
+                // neither of which can be a local
+                let local = token.kind == TokenKind::Ident
+                    && locals.contains(self.token_text(&token))
+                    && !matches!(prev, Some(TokenKind::At | TokenKind::Dot));
+                items.push(Transcriber::Token { token, local });
+                if !token.kind.is_trivia() {
+                    prev = Some(token.kind);
+                }
+                idx += 1;
+                continue;
+            }
+
+            match body.get(idx + 1).map(|token| token.kind) {
+                Some(TokenKind::Ident) => {
+                    let name = self.token_text(&body[idx + 1]).to_string();
+                    let span = self.span(token.start, body[idx + 1].end());
+                    match params.get(&name) {
+                        None => {
+                            InvalidPreprocMacro::builder()
+                                .message(format!("`${name}` isn't a parameter of this macro"))
+                                .span(span, SpanKind::NoMeta)
+                                .report();
+                            return None;
+                        }
+                        Some(&param) if param > depth => {
+                            InvalidPreprocMacro::builder()
+                                .message(format!("`${name}` is still repeating at this depth"))
+                                .span(span, SpanKind::NoMeta)
+                                .help(format!("use it inside of a repetition, like `$(${name})*`"))
+                                .report();
+                            return None;
+                        }
+                        Some(_) => items.push(Transcriber::Param { name }),
+                    }
+                    idx += 2;
+                }
+                Some(TokenKind::LeftParen) => {
+                    let Some(close) = Self::close_paren(body, idx + 1) else {
+                        self.malformed(&body[idx + 1], "this `(` is never closed".to_string());
+                        return None;
+                    };
+                    let inner =
+                        self.transcriber(&body[idx + 2..close], params, locals, depth + 1)?;
+                    let mut found = Vec::new();
+                    drivers(&inner, params, depth, &mut found);
+                    if found.is_empty() {
+                        self.malformed(
+                            &token,
+                            "this repetition doesn't use a parameter that repeats".to_string(),
+                        );
+                        return None;
+                    }
+
+                    let (separator, _, next) = self.repeat_op(body, close)?;
+                    items.push(Transcriber::Repeat {
+                        body: inner,
+                        separator,
+                    });
+                    idx = next;
+                }
+                _ => {
+                    self.malformed(&token, "expected a parameter or `(` after `$`".to_string());
+                    return None;
+                }
+            }
+            prev = Some(TokenKind::Dollar);
+        }
+        Some(items)
+    }
+
+    /// `@name(..)`, which is replaced with the body of the macro, for what's in the parentheses
+    ///
+    /// the expansion is preprocessed again, so it can call other macros,
+    /// which can only be nested `MAX_DEPTH` deep
+    pub(super) fn call_macro(&mut self, at: Token) {
+        let statement = self.at_statement_start();
+        let start = self.idx;
+        let name = self.token_text(&self.tokens[start + 1]).to_string();
+        self.idx += 2;
+
+        let open = self.skip_trivia(self.idx);
+        if self.tokens.get(open).map(|token| token.kind) != Some(TokenKind::LeftParen) {
+            self.expected(self.idx, "`(` after the name of the macro");
+            self.replace(start..self.idx, []);
+            return;
+        }
+        let Some(close) = self.matching_paren(open) else {
+            self.idx = self.tokens.len();
+            return;
+        };
+        self.idx = close + 1;
+        let call_site = self.span(at.start, self.prev_end());
+
+        let pieces = self.macro_pieces(&name, open, close, call_site);
+        // a call that can't be expanded is dropped, since its errors are already reported
+        let Some(pieces) = pieces else {
+            self.replace(start..self.idx, []);
+            return;
+        };
+
+        // like after an `@end`, the `;` after a call that makes whole statements belongs to it
+        let ends_statement = pieces
+            .iter()
+            .rev()
+            .find(|piece| !piece.kind.is_trivia())
+            .is_some_and(|piece| matches!(piece.kind, TokenKind::Semi | TokenKind::RightBracket));
+        if statement && ends_statement {
+            self.eat(TokenKind::Semi);
+        }
+
+        let tokens = self.emit(pieces, call_site, format!("in this expansion of `@{name}`"));
+        let len = tokens.len();
+        self.replace(start..self.idx, tokens);
+        self.frames.push(start + len);
+    }
+
+    /// the expansion of the macro `name` for the call with the parentheses at `open` and `close`
+    fn macro_pieces(
+        &mut self,
+        name: &str,
+        open: usize,
+        close: usize,
+        call_site: Span,
+    ) -> Option<Vec<Piece>> {
+        if self.frames.len() >= MAX_DEPTH || self.expansions >= MAX_EXPANSIONS {
+            let note = match self.frames.len() >= MAX_DEPTH {
+                true => format!("macro calls can be nested at most {MAX_DEPTH} deep"),
+                false => format!("a file can expand at most {MAX_EXPANSIONS} macro calls"),
+            };
+            // every call after the limit would report the same thing
+            if !self.exhausted {
+                PreprocRecursionLimit::builder()
+                    .message(format!(
+                        "reached the recursion limit while expanding `@{name}`"
+                    ))
+                    .span(call_site, SpanKind::NoMeta)
+                    .label(self.macros[name].span, "the macro is defined here")
+                    .note(note)
+                    .report();
+            }
+            self.exhausted = true;
+            return None;
+        }
+        self.expansions += 1;
+
+        let def = &self.macros[name];
+        let input = (open + 1..close)
+            .filter(|&idx| !self.tokens[idx].kind.is_trivia())
+            .collect::<Vec<_>>();
+        let mut captures = Captures::default();
+        let mut pos = 0;
+        let matched = self
+            .match_seq(&def.pattern, &input, &mut pos, &mut captures)
+            .and_then(|()| match input.get(pos) {
+                Some(_) => Err(Mismatch::new(pos, "`)`")),
+                None => Ok(()),
+            });
+        if let Err(mismatch) = matched {
+            let token = input.get(mismatch.at).map_or(close, |&idx| idx);
+            let token = self.tokens[token];
+            let found = match input.get(mismatch.at) {
+                Some(_) => format!("`{}`", self.token_text(&token)),
+                None => "`)`".to_string(),
+            };
+            PreprocMacroMismatch::builder()
+                .message(format!("expected {}, found {found}", mismatch.expected))
+                .span(self.span(token.start, token.end()), SpanKind::NoMeta)
+                .label(def.span, format!("`@{name}` is defined here"))
+                .report();
+            return None;
+        }
+
+        // `$` can't be in a name that's written out, so the caller can't use these names
+        let prefix = format!("$m{}_", self.expansions);
+        let mut pieces = Vec::new();
+        let transcribed = self.transcribe(
+            def,
+            &def.body,
+            &captures,
+            &mut Vec::new(),
+            &prefix,
+            &mut pieces,
+        );
+        if let Err(message) = transcribed {
+            PreprocMacroMismatch::builder()
+                .message(message)
+                .span(call_site, SpanKind::NoMeta)
+                .label(def.span, format!("`@{name}` is defined here"))
+                .report();
+            return None;
+        }
+        trace!(
+            Preproc,
+            Debug,
+            "expanded `@{name}` into {} tokens",
+            pieces.len()
+        );
+        Some(pieces)
+    }
+
+    /// matches `matchers` against the tokens of a call at `input[pos..]`
+    fn match_seq(
+        &self,
+        matchers: &[Matcher],
+        input: &[usize],
+        pos: &mut usize,
+        captures: &mut Captures,
+    ) -> Result<(), Mismatch> {
+        for matcher in matchers {
+            match matcher {
+                Matcher::Token(text) => match input.get(*pos) {
+                    Some(&idx) if self.token_text(&self.tokens[idx]) == text => *pos += 1,
+                    _ => return Err(Mismatch::new(*pos, format!("`{text}`"))),
+                },
+                Matcher::Param { name, fragment } => {
+                    let len = self.fragment_len(*fragment, &input[*pos..]);
+                    let Some(len) = len.filter(|&len| len > 0) else {
+                        return Err(Mismatch::new(*pos, fragment.describe()));
+                    };
+                    let tokens = self.tokens[input[*pos]..=input[*pos + len - 1]].to_vec();
+                    captures.insert(
+                        name.clone(),
+                        Captured::One {
+                            tokens,
+                            fragment: *fragment,
+                        },
+                    );
+                    *pos += len;
+                }
+                Matcher::Repeat {
+                    matchers,
+                    separator,
+                    op,
+                } => {
+                    let mut iterations = Vec::new();
+                    let mut first_error = None;
+                    while *op != RepeatOp::AtMostOne || iterations.is_empty() {
+                        let mut at = *pos;
+                        if let Some(separator) = separator
+                            && !iterations.is_empty()
+                        {
+                            match input.get(at) {
+                                Some(&idx) if self.token_text(&self.tokens[idx]) == separator => {
+                                    at += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        let before = at;
+                        let mut inner = Captures::default();
+                        match self.match_seq(matchers, input, &mut at, &mut inner) {
+                            // an iteration that doesn't take any tokens would repeat forever
+                            Ok(()) if at > before => {
+                                *pos = at;
+                                iterations.push(inner);
+                            }
+                            Ok(()) => break,
+                            Err(mismatch) => {
+                                first_error.get_or_insert(mismatch);
+                                break;
+                            }
+                        }
+                    }
+                    if *op == RepeatOp::AtLeastOne && iterations.is_empty() {
+                        return Err(first_error
+                            .unwrap_or_else(|| Mismatch::new(*pos, "at least one repetition")));
+                    }
+
+                    let mut names = Vec::new();
+                    param_names(matchers, &mut names);
+                    for name in names {
+                        let all = iterations
+                            .iter_mut()
+                            .map(|iteration| iteration.remove(name).unwrap())
+                            .collect();
+                        captures.insert(name.to_string(), Captured::Many(all));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// how many of the tokens at the start of `input` a `fragment` matches
+    fn fragment_len(&self, fragment: Fragment, input: &[usize]) -> Option<usize> {
+        let kind = |idx: usize| self.tokens[input[idx]].kind;
+        let opens = |kind| {
+            matches!(
+                kind,
+                TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket
+            )
+        };
+        let closes = |kind| {
+            matches!(
+                kind,
+                TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket
+            )
+        };
+        match fragment {
+            _ if input.is_empty() => None,
+            Fragment::Ident => (kind(0) == TokenKind::Ident).then_some(1),
+            Fragment::Lit => matches!(kind(0), TokenKind::Literal(_)).then_some(1),
+            Fragment::Tt if opens(kind(0)) => {
+                let mut depth = 0usize;
+                (0..input.len())
+                    .find(|&idx| {
+                        match kind(idx) {
+                            kind if opens(kind) => depth += 1,
+                            kind if closes(kind) => depth -= 1,
+                            _ => {}
+                        }
+                        depth == 0
+                    })
+                    .map(|close| close + 1)
+            }
+            Fragment::Tt => (!closes(kind(0))).then_some(1),
+            Fragment::Expr => {
+                let mut depth = 0usize;
+                let len = (0..input.len())
+                    .find(|&idx| match kind(idx) {
+                        kind if opens(kind) => {
+                            depth += 1;
+                            false
+                        }
+                        kind if closes(kind) => match depth {
+                            0 => true,
+                            _ => {
+                                depth -= 1;
+                                false
+                            }
+                        },
+                        TokenKind::Comma | TokenKind::Semi | TokenKind::FatArrow => depth == 0,
+                        _ => false,
+                    })
+                    .unwrap_or(input.len());
+                Some(len)
+            }
+        }
+    }
+
+    /// copies `body` into `pieces`, for the times the repetitions around it are at in `indices`
+    /// returns a message if two parameters in one repetition matched a different number of times
+    fn transcribe(
+        &self,
+        def: &MacroDef,
+        body: &[Transcriber],
+        captures: &Captures,
+        indices: &mut Vec<usize>,
+        prefix: &str,
+        pieces: &mut Vec<Piece>,
+    ) -> Result<(), String> {
+        for item in body {
+            match item {
+                Transcriber::Token { token, local: true } => {
+                    let text = format!("{prefix}{}", self.token_text(token));
+                    let origin = self.span(token.start, token.end());
+                    pieces.push(Piece::new(token.kind, text, origin));
+                }
+                Transcriber::Token { token, .. } => pieces.push(self.piece(token)),
+                Transcriber::Param { name } => {
+                    let Captured::One { tokens, fragment } = lookup(captures, name, indices) else {
+                        unreachable!("parameters are only used where they stop repeating")
+                    };
+                    // an expression is kept together, like `$x * 2` for `1 + 1` is `(1 + 1) * 2`
+                    let code = tokens
+                        .iter()
+                        .filter(|token| !token.kind.is_trivia())
+                        .count();
+                    let group = *fragment == Fragment::Expr && code > 1;
+                    let span = self.span(tokens[0].start, tokens[tokens.len() - 1].end());
+                    if group {
+                        pieces.push(Piece::new(TokenKind::LeftParen, "(", span));
+                    }
+                    pieces.extend(tokens.iter().map(|token| self.piece(token)));
+                    if group {
+                        pieces.push(Piece::new(TokenKind::RightParen, ")", span));
+                    }
+                }
+                Transcriber::Repeat { body, separator } => {
+                    let mut found = Vec::new();
+                    drivers(body, &def.params, indices.len(), &mut found);
+                    let mut count = None::<(usize, &str)>;
+                    for name in found {
+                        let Captured::Many(all) = lookup(captures, name, indices) else {
+                            continue;
+                        };
+                        match count {
+                            Some((count, first)) if count != all.len() => {
+                                return Err(format!(
+                                    "`${first}` repeats {count} times, but `${name}` repeats {} times",
+                                    all.len()
+                                ));
+                            }
+                            Some(_) => {}
+                            None => count = Some((all.len(), name)),
+                        }
+                    }
+
+                    for idx in 0..count.map_or(0, |(count, _)| count) {
+                        if let Some(separator) = separator
+                            && idx > 0
+                        {
+                            pieces.push(self.piece(separator));
+                        }
+                        indices.push(idx);
+                        self.transcribe(def, body, captures, indices, prefix, pieces)?;
+                        indices.pop();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_macro_expansion() {
+    use super::{cfg::Cfg, expand_for_test};
+
+    let cfg = Cfg::empty();
+    let expand = |text: &str| expand_for_test(text, &cfg);
+
+    // expressions are kept together, and repetitions repeat for every time they matched
+    assert_eq!(
+        expand("@macro defun square($x: expr): $x * $x @end; let a => @square(b + 1);"),
+        "let a => ( b + 1 ) * ( b + 1 ) ;"
+    );
+    assert_eq!(
+        expand("@macro defun sum($($x: expr),*): 0 $(+ $x)* @end; let s => @sum(1, 2, a * 3);"),
+        "let s => 0 + 1 + 2 + ( a * 3 ) ;"
+    );
+
+    // the locals a macro declares don't clash with the caller's
+    assert_eq!(
+        expand(
+            "@macro defun swap($a: ident, $b: ident): let tmp => $a; $a = $b; $b = tmp; @end;
+            defun f() { let tmp => 1; @swap(tmp, y); }"
+        ),
+        "defun f ( ) { let tmp => 1 ; let $m1_tmp => tmp ; tmp = y ; y = $m1_tmp ; }"
+    );
+    // and neither do parameters or loop variables, even with names the caller uses
+    assert_eq!(
+        expand(
+            "@macro defun helpers($e: expr):
+                let x => $e;
+                defun add(x: Int32, mut y: Int32) => Int32 { x + y }
+                @for(constexpr let I => 0..2): f(x, $e, I); @end;
+            @end;
+            let x__m0 => 1; @helpers(x__m0 + I);"
+        ),
+        "let x__m0 => 1 ; let $m1_x => ( x__m0 + I ) ; \
+        defun add ( $m1_x : Int32 , mut $m1_y : Int32 ) => Int32 { $m1_x + $m1_y } \
+        f ( $m1_x , ( x__m0 + I ) , 0 ) ; f ( $m1_x , ( x__m0 + I ) , 1 ) ;"
+    );
+    assert_eq!(
+        expand(
+            "@macro defun getter($name: ident, $ty: tt): defun $name() => $ty { ret 0; } @end;
+            @getter(zero, Int64);"
+        ),
+        "defun zero ( ) => Int64 { ret 0 ; }"
+    );
+
+    // the `@eval` of an `@else @eval(..):` in the body doesn't need an `@end` of its own
+    let mut linux = Cfg::empty();
+    linux.set("OS", "LINUX");
+    assert_eq!(
+        expand_for_test(
+            "@macro defun pick($n: expr):
+                @eval(OS == WIN): a; @else @eval(OS == LINUX): b($n); @end;
+            @end;
+            @pick(1); after;",
+            &linux
+        ),
+        "b ( 1 ) ; after ;"
+    );
+
+    // expansions are preprocessed again, so macros can call themselves
+    assert_eq!(
+        expand(
+            "@macro defun count($($x: tt $($rest: tt)*)?): $(1 + @count($($rest)*) +)? 0 @end;
+            let n => @count(a b c);"
+        ),
+        "let n => 1 + 1 + 1 + 0 + 0 + 0 + 0 ;"
+    );
+
+    // but only so deep, or so often
+    assert_eq!(
+        expand("@macro defun forever(): @forever() @end; @forever(); after;"),
+        "; after ;"
+    );
+    assert_eq!(
+        expand("@macro defun twice(): @twice() @twice() @end; @twice(); after;"),
+        "; after ;"
+    );
+}
@@ -0,0 +1,1243 @@
+//! the preprocessor, which runs over the tokens of a file before the parser sees them
+//!
+//! it handles conditional compilation: the conditions of `@eval(..):` and `@else @eval(..):`
+//! are checked against the target `Cfg` and the file's `@def` constants, and the tokens of
+//! every branch that isn't taken are dropped, along with the macros themselves.
+//! `@for` loops are unrolled, and `@template`s and `@macro`s are expanded where they're used.
+//! `@external` is replaced with the declarations in the C header it names.
+//! every other macro is passed on to the parser as is
+
+pub mod cfg;
+mod cheader;
+pub mod env;
+mod expand;
+pub mod expr;
+mod external;
+mod macros;
+mod template;
+
+use std::ops::Range;
+
+use crate::{
+    betac_ast::Span,
+    betac_errors::{
+        general_errors::UnexpectedTokenInInput,
+        preproc_errors::{
+            InvalidPreprocLoop, InvalidPreprocTemplate, PreprocRedefinition, UnmatchedPreprocMacro,
+            UnterminatedPreprocBlock,
+        },
+        Reportable, SpanKind,
+    },
+    betac_runner::fx_hasher::FxHashMap,
+    betac_tokenizer::token::{Base, Kw, LiteralKind, NumSuffix, Token, TokenKind},
+    betac_util::{
+        source_map::{Expansion, FileId, SourceMap},
+        trace::trace,
+    },
+};
+
+use cfg::Cfg;
+use env::{Def, Env};
+use expr::Evaluator;
+use macros::MacroDef;
+use template::Template;
+
+/// the most copies a single `@for` can make
+const MAX_UNROLL: usize = 4096;
+
+/// the macros the preprocessor cares about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Macro {
+    Eval,
+    Else,
+    End,
+    Def,
+    For,
+    Template,
+    /// `@macro`, which defines another macro
+    Define,
+    External,
+    /// the other block macros, which are closed by an `@end` too, like `@asm`.
+    /// they're passed through, but still have to be tracked, so that an `@end`
+    /// inside an `@eval` branch doesn't close the `@eval` early
+    Block,
+    /// everything else, like `@start`, or a call to a template or a macro
+    Other,
+}
+
+impl Macro {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "eval" => Self::Eval,
+            "else" => Self::Else,
+            "end" => Self::End,
+            "def" => Self::Def,
+            "for" => Self::For,
+            "template" => Self::Template,
+            "macro" => Self::Define,
+            "external" => Self::External,
+            "asm" => Self::Block,
+            _ => Self::Other,
+        }
+    }
+
+    /// whether this macro needs an `@end`
+    fn opens_block(self) -> bool {
+        matches!(
+            self,
+            Self::Eval | Self::For | Self::Template | Self::Define | Self::External | Self::Block
+        )
+    }
+}
+
+enum Block {
+    /// an `@eval` chain, up to its `@end`
+    Eval {
+        /// the `@eval(..):` that opened the chain
+        span: Span,
+        /// whether the tokens of the current branch are kept
+        active: bool,
+        /// whether a branch has been taken already, so none of the later ones can be.
+        /// this is also set when the whole chain is inside a branch that isn't taken
+        taken: bool,
+        /// whether the chain has reached its final `@else:`
+        finished: bool,
+        /// whether the chain is where a statement or an item could start,
+        /// in which case the `;` after its `@end` belongs to it, instead of to the code around it
+        statement: bool,
+    },
+    /// any other block macro
+    Other,
+}
+
+/// the variable of a `@for`, or a parameter of a `@template`
+struct Variable {
+    name: String,
+    ty: Option<NumSuffix>,
+    /// from the name to the type, like `N: Uint64`
+    span: Span,
+}
+
+/// the macro a `Variable` belongs to, which decides how its errors are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    Loop,
+    Template,
+}
+
+/// what a name in copied code is replaced with
+enum Replacement {
+    /// a number, like the value of a `@for`'s variable
+    Int(i64, Option<NumSuffix>),
+    /// another name, like the name of a template's instance
+    Ident(String),
+}
+
+/// a token the preprocessor makes, before it's added to the working text
+#[derive(Debug, Clone)]
+struct Piece {
+    kind: TokenKind,
+    text: String,
+    /// the token it was copied from
+    origin: Span,
+}
+
+impl Piece {
+    fn new(kind: TokenKind, text: impl Into<String>, origin: Span) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+            origin,
+        }
+    }
+}
+
+/// the code the parser sees, once the preprocessor is done with a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expanded {
+    /// the source of the file, followed by the code the preprocessor made.
+    /// every `Expansion` in it is registered in the `SourceMap`
+    pub text: String,
+    pub tokens: Vec<Token>,
+}
+
+/// runs the preprocessor over the `tokens` of `text`
+pub fn preprocess(text: &str, tokens: &[Token], file: FileId, cfg: &Cfg) -> Expanded {
+    Preprocessor {
+        text: text.to_string(),
+        file,
+        env: Env::new(cfg),
+        tokens: tokens.to_vec(),
+        idx: 0,
+        out: Vec::with_capacity(tokens.len()),
+        last: None,
+        blocks: Vec::new(),
+        templates: FxHashMap::default(),
+        macros: FxHashMap::default(),
+        frames: Vec::new(),
+        expansions: 0,
+        exhausted: false,
+    }
+    .run()
+}
+
+struct Preprocessor<'a> {
+    text: String,
+    file: FileId,
+    env: Env<'a>,
+    /// unrolled loops replace themselves with their copies in here, so they're preprocessed next,
+    /// and new instances of templates are added to the end
+    tokens: Vec<Token>,
+    idx: usize,
+    out: Vec<Token>,
+    /// the kind of the last token kept that isn't trivia
+    last: Option<TokenKind>,
+    blocks: Vec<Block>,
+    templates: FxHashMap<String, Template>,
+    macros: FxHashMap<String, MacroDef>,
+    /// where the tokens of every macro expansion that's being preprocessed end, innermost last
+    frames: Vec<usize>,
+    /// how many macro calls have been expanded
+    expansions: usize,
+    /// whether a macro call has hit the recursion limit, which is only reported once
+    exhausted: bool,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn run(mut self) -> Expanded {
+        while let Some(&token) = self.tokens.get(self.idx) {
+            while self.frames.last().is_some_and(|&end| self.idx >= end) {
+                self.frames.pop();
+            }
+            if let Some(name) = self.macro_name(self.idx) {
+                match name {
+                    Macro::Eval => {
+                        self.eval(token);
+                        continue;
+                    }
+                    Macro::Else => {
+                        self.else_branch(token);
+                        continue;
+                    }
+                    Macro::Def => {
+                        self.def();
+                        continue;
+                    }
+                    Macro::For if self.active() => {
+                        self.for_loop(token);
+                        continue;
+                    }
+                    Macro::Template if self.active() => {
+                        self.template(token);
+                        continue;
+                    }
+                    Macro::Define if self.active() => {
+                        self.macro_def(token);
+                        continue;
+                    }
+                    Macro::External if self.active() => {
+                        self.external(token);
+                        continue;
+                    }
+                    Macro::Other
+                        if self.active()
+                            && self
+                                .templates
+                                .contains_key(self.token_text(&self.tokens[self.idx + 1])) =>
+                    {
+                        self.instantiate(token);
+                        continue;
+                    }
+                    Macro::Other
+                        if self.active()
+                            && self
+                                .macros
+                                .contains_key(self.token_text(&self.tokens[self.idx + 1])) =>
+                    {
+                        self.call_macro(token);
+                        continue;
+                    }
+                    Macro::End => match self.blocks.last() {
+                        Some(Block::Eval { .. }) => {
+                            self.end();
+                            continue;
+                        }
+                        Some(Block::Other) => {
+                            self.blocks.pop();
+                        }
+                        None => {
+                            self.unmatched(token, "`@end` without a block to close");
+                            self.idx += 2;
+                            self.eat(TokenKind::Semi);
+                            continue;
+                        }
+                    },
+                    Macro::For
+                    | Macro::Template
+                    | Macro::Define
+                    | Macro::External
+                    | Macro::Block => self.blocks.push(Block::Other),
+                    Macro::Other => {}
+                }
+            }
+
+            if self.active() {
+                if !token.kind.is_trivia() {
+                    self.last = Some(token.kind);
+                }
+                self.out.push(token);
+            }
+            self.idx += 1;
+        }
+
+        let eof = self.span(self.text.len() as u32, self.text.len() as u32);
+        for block in self.blocks.iter().rev() {
+            if let Block::Eval { span, .. } = block {
+                UnterminatedPreprocBlock::builder()
+                    .message("this `@eval` is never closed".to_string())
+                    .span(*span, SpanKind::NoMeta)
+                    .label(eof, "expected `@end;` before the end of the file")
+                    .report();
+            }
+        }
+        Expanded {
+            text: self.text,
+            tokens: self.out,
+        }
+    }
+
+    fn span(&self, start: u32, end: u32) -> Span {
+        Span::new(self.file, start, end.saturating_sub(start))
+    }
+
+    fn token_text(&self, token: &Token) -> &str {
+        &self.text[token.start as usize..token.end() as usize]
+    }
+
+    /// the macro at `idx`, if there's one
+    fn macro_name(&self, idx: usize) -> Option<Macro> {
+        match self.tokens.get(idx..idx + 2)? {
+            [at, name]
+                if at.kind == TokenKind::At
+                    && matches!(name.kind, TokenKind::Ident | TokenKind::Keyword(_)) =>
+            {
+                Some(Macro::from_name(self.token_text(name)))
+            }
+            _ => None,
+        }
+    }
+
+    /// the index of the first token at or after `idx` that isn't trivia
+    fn skip_trivia(&self, idx: usize) -> usize {
+        idx + self.tokens[idx.min(self.tokens.len())..]
+            .iter()
+            .take_while(|token| token.kind.is_trivia())
+            .count()
+    }
+
+    /// skips past the next token if it's a `kind`, and returns whether it was
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        let next = self.skip_trivia(self.idx);
+        let found = self
+            .tokens
+            .get(next)
+            .is_some_and(|token| token.kind == kind);
+        if found {
+            self.idx = next + 1;
+        }
+        found
+    }
+
+    /// skips tokens up to and including the first one where `end` is true
+    fn skip_past(&mut self, end: impl Fn(TokenKind) -> bool) {
+        while let Some(token) = self.tokens.get(self.idx) {
+            self.idx += 1;
+            if end(token.kind) {
+                break;
+            }
+        }
+    }
+
+    /// the end of the last token that was consumed
+    fn prev_end(&self) -> u32 {
+        self.idx
+            .checked_sub(1)
+            .map_or(0, |idx| self.tokens[idx].end())
+    }
+
+    /// whether every open `@eval` is in a branch that's kept
+    fn active(&self) -> bool {
+        self.blocks.iter().all(|block| match block {
+            Block::Eval { active, .. } => *active,
+            Block::Other => true,
+        })
+    }
+
+    /// whether the code kept so far ends where a statement or an item could start
+    fn at_statement_start(&self) -> bool {
+        self.last.is_none_or(|kind| {
+            matches!(
+                kind,
+                TokenKind::Semi | TokenKind::LeftBracket | TokenKind::RightBracket
+            )
+        })
+    }
+
+    /// reports that the first token at or after `idx` that isn't trivia should be `what`
+    fn expected(&self, idx: usize, what: &str) {
+        let next = self.skip_trivia(idx);
+        let (span, found) = match self.tokens.get(next) {
+            Some(token) => (
+                self.span(token.start, token.end()),
+                format!("`{}`", self.token_text(token)),
+            ),
+            None => (
+                self.span(self.prev_end(), self.prev_end()),
+                "the end of the file".to_string(),
+            ),
+        };
+        UnexpectedTokenInInput::builder()
+            .message(format!("expected {what}, found {found}"))
+            .span(span, SpanKind::NoMeta)
+            .report();
+    }
+
+    fn unmatched(&self, at: Token, message: &str) {
+        let end = self.tokens[self.idx + 1].end();
+        UnmatchedPreprocMacro::builder()
+            .message(message.to_string())
+            .span(self.span(at.start, end), SpanKind::NoMeta)
+            .report();
+    }
+
+    /// the index of the `)` that closes the `(` at `open`
+    /// reports the `(` if it's never closed
+    fn matching_paren(&self, open: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        let close = self.tokens[open..].iter().position(|token| {
+            match token.kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        });
+        if close.is_none() {
+            let start = self.tokens[open].start;
+            UnexpectedTokenInInput::builder()
+                .message("this `(` is never closed".to_string())
+                .span(self.span(start, start + 1), SpanKind::NoMeta)
+                .report();
+        }
+        close.map(|close| open + close)
+    }
+
+    /// the index of the `@end` that closes the block whose body starts at `body`
+    fn matching_end(&self, body: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        // the `@eval` of an `@else @eval(..):` continues its chain instead of opening a block
+        let mut chained = None;
+        for idx in body..self.tokens.len() {
+            match self.macro_name(idx) {
+                Some(Macro::End) if depth == 0 => return Some(idx),
+                Some(Macro::End) => depth -= 1,
+                Some(Macro::Else) => chained = Some(self.skip_trivia(idx + 2)),
+                Some(Macro::Eval) if chained == Some(idx) => {}
+                Some(name) if name.opens_block() => depth += 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// reads the `(..):` after an `@eval`, and returns whether the condition holds
+    ///
+    /// the condition is only evaluated if `evaluate` is set, so conditions in branches
+    /// that aren't taken don't report errors. a malformed condition is reported, and is false
+    fn condition(&mut self, evaluate: bool) -> bool {
+        let open = self.skip_trivia(self.idx);
+        if self.tokens.get(open).map(|token| token.kind) != Some(TokenKind::LeftParen) {
+            self.expected(self.idx, "`(` after `@eval`");
+            // skip the rest of the macro, so its tokens don't end up in the output
+            self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+            return false;
+        }
+
+        let Some(close) = self.matching_paren(open) else {
+            self.idx = self.tokens.len();
+            return false;
+        };
+
+        let holds = evaluate
+            && Evaluator::new(
+                &self.text,
+                self.file,
+                &self.env,
+                &self.tokens[open + 1..close],
+                self.tokens[close].start,
+            )
+            .condition()
+            .unwrap_or(false);
+
+        self.idx = close + 1;
+        if !self.eat(TokenKind::Colon) {
+            self.expected(self.idx, "`:` after the condition");
+        }
+        holds
+    }
+
+    /// `@def NAME => ..;`, which defines a constant for the rest of the file
+    ///
+    /// the value is folded right away, so it can only use the `@def`s before it.
+    /// a `@def` in a branch that isn't taken doesn't define anything
+    fn def(&mut self) {
+        let evaluate = self.active();
+        self.idx += 2;
+
+        let next = self.skip_trivia(self.idx);
+        let name = match self.tokens.get(next) {
+            Some(&token) if token.kind == TokenKind::Ident => token,
+            _ => {
+                self.expected(self.idx, "a name after `@def`");
+                self.skip_past(|kind| kind == TokenKind::Semi);
+                return;
+            }
+        };
+        self.idx = next + 1;
+        if !self.eat(TokenKind::FatArrow) {
+            self.expected(self.idx, "`=>` after the name");
+            self.skip_past(|kind| kind == TokenKind::Semi);
+            return;
+        }
+
+        let start = self.idx;
+        let Some(semi) = self.tokens[start..]
+            .iter()
+            .position(|token| token.kind == TokenKind::Semi)
+            .map(|semi| start + semi)
+        else {
+            self.idx = self.tokens.len();
+            self.expected(self.idx, "`;` after the value");
+            return;
+        };
+        self.idx = semi + 1;
+        if !evaluate {
+            return;
+        }
+
+        let value = Evaluator::new(
+            &self.text,
+            self.file,
+            &self.env,
+            &self.tokens[start..semi],
+            self.tokens[semi].start,
+        )
+        .constant();
+        let Some(value) = value else {
+            return;
+        };
+        trace!(Preproc, Debug, "`@def` at {}: {value:?}", name.start);
+
+        let span = self.span(name.start, name.end());
+        let text = self.token_text(&name).to_string();
+        if let Err(first) = self.env.define(&text, Def { value, span }) {
+            PreprocRedefinition::builder()
+                .message(format!("`{text}` is defined more than once"))
+                .span(span, SpanKind::NoMeta)
+                .label(first.span, "first defined here")
+                .note("`@def`s can't be changed once they're defined")
+                .report();
+        }
+    }
+
+    /// `@for(constexpr let N: Type => ..):`, which copies its body once for every value of `N`
+    ///
+    /// the copies replace the loop, so the macros in them are preprocessed like any other code.
+    /// every copy is added to the `SourceMap` as an `Expansion`, so its spans lead back to the body
+    fn for_loop(&mut self, at: Token) {
+        let statement = self.at_statement_start();
+        let start = self.idx;
+        self.idx += 2;
+        let binding = self.loop_header();
+        let call_site = self.span(at.start, self.prev_end());
+
+        let body = self.idx;
+        let Some(end) = self.block_end("`@for`", call_site, statement) else {
+            return;
+        };
+        // a loop that can't be unrolled is dropped, since its errors are already reported
+        let Some((var, values)) = binding else {
+            self.replace(start..self.idx, []);
+            return;
+        };
+        trace!(
+            Preproc,
+            Debug,
+            "`@for` at {}: {} copies",
+            at.start,
+            values.len()
+        );
+
+        let body = self.tokens[body..end].to_vec();
+        let mut copies = Vec::new();
+        for value in values {
+            let substitutions = [(var.name.clone(), Replacement::Int(value, var.ty))];
+            let description = format!("in this `@for`, where `{} = {value}`", var.name);
+            copies.extend(self.expand(&body, &substitutions, call_site, description));
+        }
+        self.replace(start..self.idx, copies);
+    }
+
+    /// replaces the tokens in `range` with `tokens`, and goes back to the start of them,
+    /// so whatever replaced them is preprocessed next
+    fn replace(&mut self, range: Range<usize>, tokens: impl IntoIterator<Item = Token>) {
+        let len = self.tokens.len();
+        self.tokens.splice(range.clone(), tokens);
+        // the macro expansions around the range move by as much as it grew or shrank
+        for end in &mut self.frames {
+            *end = *end + self.tokens.len() - len;
+        }
+        self.idx = range.start;
+    }
+
+    /// skips past the `@end` that closes the block whose body starts here, and returns its index
+    /// a block that's never closed is reported, and everything after it is skipped
+    fn block_end(&mut self, what: &str, call_site: Span, statement: bool) -> Option<usize> {
+        let Some(end) = self.matching_end(self.idx) else {
+            let eof = self.span(self.text.len() as u32, self.text.len() as u32);
+            UnterminatedPreprocBlock::builder()
+                .message(format!("this {what} is never closed"))
+                .span(call_site, SpanKind::NoMeta)
+                .label(eof, "expected `@end;` before the end of the file")
+                .report();
+            self.idx = self.tokens.len();
+            return None;
+        };
+        self.idx = end + 2;
+        if statement {
+            self.eat(TokenKind::Semi);
+        }
+        Some(end)
+    }
+
+    /// copies `body` to the end of the working text, with the names in `substitutions` replaced,
+    /// and registers the copy as an `Expansion` of `call_site`
+    ///
+    /// a name right after an `@` is a macro, and is never replaced
+    fn expand(
+        &mut self,
+        body: &[Token],
+        substitutions: &[(String, Replacement)],
+        call_site: Span,
+        description: String,
+    ) -> Vec<Token> {
+        let mut pieces = Vec::with_capacity(body.len());
+        let mut after_at = false;
+        for token in body {
+            let replacement = match token.kind {
+                TokenKind::Ident if !after_at => substitutions
+                    .iter()
+                    .find(|(name, _)| name == self.token_text(token))
+                    .map(|(_, replacement)| replacement),
+                _ => None,
+            };
+            after_at = token.kind == TokenKind::At;
+
+            let origin = self.span(token.start, token.end());
+            match replacement {
+                Some(&Replacement::Int(value, ty)) => {
+                    if value < 0 {
+                        pieces.push(Piece::new(TokenKind::Minus, "-", origin));
+                    }
+                    let kind = TokenKind::Literal(LiteralKind::Int {
+                        base: Base::Decimal,
+                        suffix: ty,
+                    });
+                    let text =
+                        format!("{}{}", value.unsigned_abs(), ty.map_or("", NumSuffix::name));
+                    pieces.push(Piece::new(kind, text, origin));
+                }
+                Some(Replacement::Ident(name)) => {
+                    pieces.push(Piece::new(TokenKind::Ident, name.clone(), origin));
+                }
+                None => pieces.push(self.piece(token)),
+            }
+        }
+        self.emit(pieces, call_site, description)
+    }
+
+    /// a copy of `token`
+    fn piece(&self, token: &Token) -> Piece {
+        let origin = self.span(token.start, token.end());
+        Piece::new(token.kind, self.token_text(token), origin)
+    }
+
+    /// adds `pieces` to the end of the working text, and registers them as an `Expansion`
+    /// of `call_site`. returns the tokens they became
+    fn emit(&mut self, pieces: Vec<Piece>, call_site: Span, description: String) -> Vec<Token> {
+        let range_start = self.text.len() as u32;
+        let mut tokens = Vec::with_capacity(pieces.len());
+        let mut origins = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            let start = self.text.len() as u32;
+            self.text.push_str(&piece.text);
+            origins.push((start, piece.origin));
+            tokens.push(Token {
+                kind: piece.kind,
+                start,
+                len: piece.text.len() as u32,
+            });
+        }
+
+        let expansion = Expansion {
+            range: range_start..self.text.len() as u32,
+            call_site,
+            description,
+            origins,
+        };
+        SourceMap::with_mut(|mut map| map.add_expansion(self.file, expansion));
+        tokens
+    }
+
+    /// reads the `(constexpr let N: Type => ..):` after a `@for`
+    /// a header that's malformed, or can't be unrolled, is reported, and is `None`
+    fn loop_header(&mut self) -> Option<(Variable, Vec<i64>)> {
+        let open = self.skip_trivia(self.idx);
+        if self.tokens.get(open).map(|token| token.kind) != Some(TokenKind::LeftParen) {
+            self.expected(self.idx, "`(` after `@for`");
+            self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+            return None;
+        }
+        let Some(close) = self.matching_paren(open) else {
+            self.idx = self.tokens.len();
+            return None;
+        };
+
+        let binding = self.binding(open + 1, close);
+        self.idx = close + 1;
+        if !self.eat(TokenKind::Colon) {
+            self.expected(self.idx, "`:` after the loop");
+        }
+        binding
+    }
+
+    /// `constexpr let N: Type => ..`, the inside of the parentheses of a `@for`,
+    /// which ends at the `)` at `close`
+    fn binding(&self, from: usize, close: usize) -> Option<(Variable, Vec<i64>)> {
+        let (var, arrow) = self.variable(from, Owner::Loop)?;
+        if self.tokens[arrow].kind != TokenKind::FatArrow {
+            self.expected(arrow, "`=>` after the variable");
+            return None;
+        }
+
+        let first = self.skip_trivia(arrow + 1);
+        if first == close {
+            self.expected(first, "a range or a tuple after `=>`");
+            return None;
+        }
+        let span = self.trimmed_span(first..close);
+        let values = self.loop_values(first, close, span)?;
+
+        if let Some(ty) = var.ty
+            && let Some(value) = values.iter().find(|&&value| !fits(ty, value))
+        {
+            InvalidPreprocLoop::builder()
+                .message(format!("`{value}` doesn't fit in a `{}`", ty.name()))
+                .span(span, SpanKind::NoMeta)
+                .label(var.span, "the variable is declared here")
+                .report();
+            return None;
+        }
+        Some((var, values))
+    }
+
+    /// `constexpr let N: Type`, the variable of a `@for` or a parameter of a `@template`,
+    /// which starts at `from`. returns it along with the index of the token after it
+    fn variable(&self, from: usize, owner: Owner) -> Option<(Variable, usize)> {
+        // variables are always inside of parentheses, so none of these go past the `)`
+        let kind = |idx: usize| self.tokens[idx].kind;
+        let constexpr = self.skip_trivia(from);
+        if kind(constexpr) != TokenKind::Ident
+            || self.token_text(&self.tokens[constexpr]) != "constexpr"
+        {
+            self.expected(constexpr, "`constexpr let`");
+            return None;
+        }
+        let keyword = self.skip_trivia(constexpr + 1);
+        if kind(keyword) != TokenKind::Keyword(Kw::Let) {
+            self.expected(keyword, "`let` after `constexpr`");
+            return None;
+        }
+        let name = self.skip_trivia(keyword + 1);
+        if kind(name) != TokenKind::Ident {
+            self.expected(name, "a name after `let`");
+            return None;
+        }
+
+        let mut end = name;
+        let mut ty = None;
+        let colon = self.skip_trivia(name + 1);
+        if kind(colon) == TokenKind::Colon {
+            end = self.skip_trivia(colon + 1);
+            if kind(end) != TokenKind::Ident {
+                self.expected(end, "a type after `:`");
+                return None;
+            }
+            ty = Some(self.variable_type(end, owner)?);
+        }
+
+        let var = Variable {
+            name: self.token_text(&self.tokens[name]).to_string(),
+            ty,
+            span: self.span(self.tokens[name].start, self.tokens[end].end()),
+        };
+        Some((var, self.skip_trivia(end + 1)))
+    }
+
+    /// the type of a variable, which has to be an integer type
+    fn variable_type(&self, idx: usize, owner: Owner) -> Option<NumSuffix> {
+        let token = self.tokens[idx];
+        let text = self.token_text(&token);
+        if let Some(ty) = NumSuffix::from_name(text)
+            && !ty.is_float()
+        {
+            return Some(ty);
+        }
+
+        let message = format!("`{text}` isn't an integer type");
+        let span = self.span(token.start, token.end());
+        match owner {
+            Owner::Loop => InvalidPreprocLoop::builder()
+                .message(message)
+                .span(span, SpanKind::NoMeta)
+                .note("the variable of a `@for` has to be an integer, like `Uint64`")
+                .report(),
+            Owner::Template => InvalidPreprocTemplate::builder()
+                .message(message)
+                .span(span, SpanKind::NoMeta)
+                .note("the parameters of a `@template` have to be integers, like `Uint64`")
+                .report(),
+        }
+        None
+    }
+
+    /// the values of a `@for`, from a range like `0..4` or `0..=3`,
+    /// or a tuple like `(1, 2, 4)`, which starts at `first` and ends before `close`
+    fn loop_values(&self, first: usize, close: usize, span: Span) -> Option<Vec<i64>> {
+        let mut depth = 0usize;
+        let mut dots = None;
+        for idx in first..close {
+            match self.tokens[idx].kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth = depth.saturating_sub(1),
+                TokenKind::Dot if depth == 0 && self.tokens[idx + 1].kind == TokenKind::Dot => {
+                    dots = Some(idx);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(dots) = dots {
+            let inclusive = self.tokens[dots + 2].kind == TokenKind::Eq;
+            let upper = dots + 2 + usize::from(inclusive);
+            let (lo, hi) = (self.bound(first, dots), self.bound(upper, close));
+            let (lo, hi) = (lo?, hi?);
+
+            let count = (i128::from(hi) - i128::from(lo) + i128::from(inclusive)).max(0);
+            if count > MAX_UNROLL as i128 {
+                self.too_many(span, count);
+                return None;
+            }
+            return Some(match inclusive {
+                true => (lo..=hi).collect(),
+                false => (lo..hi).collect(),
+            });
+        }
+
+        if self.tokens[first].kind == TokenKind::LeftParen
+            && let Some(end) = self.matching_paren(first)
+            && self.skip_trivia(end + 1) == close
+        {
+            // evaluate every value before giving up, so all of the errors are reported
+            let values = self
+                .list(first, end)
+                .into_iter()
+                .map(|value| self.bound(value.start, value.end))
+                .collect::<Vec<_>>();
+            let values = values.into_iter().collect::<Option<Vec<_>>>()?;
+            if values.len() > MAX_UNROLL {
+                self.too_many(span, values.len() as i128);
+                return None;
+            }
+            return Some(values);
+        }
+
+        InvalidPreprocLoop::builder()
+            .message("expected a range like `0..4`, or a tuple like `(1, 2, 4)`".to_string())
+            .span(span, SpanKind::NoMeta)
+            .report();
+        None
+    }
+
+    /// the elements of the list in the parentheses from `open` to `close`, like `(1, 2 * 4)`,
+    /// as the tokens between the commas. a trailing comma doesn't add an empty element
+    fn list(&self, open: usize, close: usize) -> Vec<Range<usize>> {
+        let mut elements = Vec::new();
+        let mut start = open + 1;
+        let mut depth = 0usize;
+        for idx in open + 1..=close {
+            match self.tokens[idx].kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen if depth > 0 => depth -= 1,
+                TokenKind::Comma | TokenKind::RightParen if depth == 0 => {
+                    let trailing = idx == close && self.skip_trivia(start) == close;
+                    if !trailing {
+                        elements.push(start..idx);
+                    }
+                    start = idx + 1;
+                }
+                _ => {}
+            }
+        }
+        elements
+    }
+
+    /// the span of `tokens`, without the trivia around them
+    fn trimmed_span(&self, tokens: Range<usize>) -> Span {
+        let mut code = self.tokens[tokens.clone()]
+            .iter()
+            .filter(|token| !token.kind.is_trivia());
+        match (code.next(), code.next_back()) {
+            (Some(first), last) => self.span(first.start, last.unwrap_or(first).end()),
+            (None, _) => {
+                let at = self.tokens[tokens.end].start;
+                self.span(at, at)
+            }
+        }
+    }
+
+    /// evaluates the bound of a `@for` from `from` up to `to`
+    fn bound(&self, from: usize, to: usize) -> Option<i64> {
+        Evaluator::new(
+            &self.text,
+            self.file,
+            &self.env,
+            &self.tokens[from..to],
+            self.tokens[to].start,
+        )
+        .bound()
+    }
+
+    fn too_many(&self, span: Span, count: i128) {
+        InvalidPreprocLoop::builder()
+            .message(format!("this `@for` would make {count} copies of its body"))
+            .span(span, SpanKind::NoMeta)
+            .note(format!("a `@for` can make at most {MAX_UNROLL} copies"))
+            .report();
+    }
+
+    fn eval(&mut self, at: Token) {
+        let statement = self.at_statement_start();
+        let outer = self.active();
+        self.idx += 2;
+        let holds = self.condition(outer);
+        trace!(
+            Preproc,
+            Debug,
+            "`@eval` at {}: {}",
+            at.start,
+            if holds { "taken" } else { "skipped" }
+        );
+
+        self.blocks.push(Block::Eval {
+            span: self.span(at.start, self.prev_end()),
+            active: holds,
+            taken: holds || !outer,
+            finished: false,
+            statement,
+        });
+    }
+
+    /// `@else @eval(..):` or `@else:`, which starts the next branch of the open chain
+    fn else_branch(&mut self, at: Token) {
+        let state = match self.blocks.last() {
+            Some(Block::Eval {
+                span,
+                taken,
+                finished,
+                ..
+            }) => Some((*span, *taken, *finished)),
+            _ => None,
+        };
+        match state {
+            None => self.unmatched(at, "`@else` without a matching `@eval`"),
+            Some((span, _, true)) => {
+                let end = self.tokens[self.idx + 1].end();
+                UnmatchedPreprocMacro::builder()
+                    .message("`@else` after the last branch of an `@eval`".to_string())
+                    .span(self.span(at.start, end), SpanKind::NoMeta)
+                    .label(span, "the chain started here")
+                    .note("a plain `@else:` has to be the last branch before `@end;`")
+                    .report();
+            }
+            Some(_) => {}
+        }
+        self.idx += 2;
+
+        let open = state.is_some_and(|(_, taken, finished)| !taken && !finished);
+        let next = self.skip_trivia(self.idx);
+        let chained = self.macro_name(next) == Some(Macro::Eval);
+        let holds = if chained {
+            self.idx = next + 2;
+            self.condition(open)
+        } else {
+            if !self.eat(TokenKind::Colon) {
+                self.expected(self.idx, "`:` or `@eval` after `@else`");
+            }
+            true
+        };
+        trace!(
+            Preproc,
+            Debug,
+            "`@else` at {}: {}",
+            at.start,
+            if open && holds { "taken" } else { "skipped" }
+        );
+
+        if let Some(Block::Eval {
+            active,
+            taken,
+            finished,
+            ..
+        }) = self.blocks.last_mut()
+            && state.is_some()
+        {
+            *active = open && holds;
+            *taken |= *active;
+            *finished |= !chained;
+        }
+    }
+
+    /// `@end`, which closes the open chain
+    fn end(&mut self) {
+        self.idx += 2;
+        if let Some(Block::Eval { statement, .. }) = self.blocks.pop()
+            && statement
+        {
+            self.eat(TokenKind::Semi);
+        }
+    }
+}
+
+/// whether `value` is in the range of the integer type `ty`
+fn fits(ty: NumSuffix, value: i64) -> bool {
+    match ty {
+        NumSuffix::Int8 => i8::try_from(value).is_ok(),
+        NumSuffix::Int16 => i16::try_from(value).is_ok(),
+        NumSuffix::Int32 => i32::try_from(value).is_ok(),
+        NumSuffix::Int64 => true,
+        NumSuffix::Uint8 => u8::try_from(value).is_ok(),
+        NumSuffix::Uint16 => u16::try_from(value).is_ok(),
+        NumSuffix::Uint32 => u32::try_from(value).is_ok(),
+        NumSuffix::Uint64 => value >= 0,
+        NumSuffix::Float32 | NumSuffix::Float64 => false,
+    }
+}
+
+/// preprocesses `text` as an unnamed file, and joins the tokens that are left with spaces
+#[cfg(test)]
+pub(crate) fn expand_for_test(text: &str, cfg: &Cfg) -> String {
+    expand_file_for_test(text, FileId::DUMMY, cfg)
+}
+
+/// like `expand_for_test`, for a file that's in the source map, which is needed to find
+/// the headers next to it
+#[cfg(test)]
+pub(crate) fn expand_file_for_test(text: &str, file: FileId, cfg: &Cfg) -> String {
+    use crate::betac_tokenizer::run_tokenizer;
+
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = preprocess(text, &tokens, file, cfg);
+    expanded
+        .tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .map(|token| &expanded.text[token.start as usize..token.end() as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+#[cfg(test)]
+fn test_conditional_compilation() {
+    let mut cfg = Cfg::empty();
+    cfg.set("OS", "LINUX");
+    cfg.set("OS_FAMILY", "UNIX");
+    cfg.set("CPU_BITS", "64");
+
+    let expand = |text: &str| expand_for_test(text, &cfg);
+
+    assert_eq!(
+        expand("alias Usize => @eval(CPU_BITS == 64): Uint64 @else: Uint32 @end;"),
+        "alias Usize => Uint64 ;"
+    );
+    assert_eq!(
+        expand("alias Usize => @eval(CPU_BITS != 64): Uint64 @else: Uint32 @end;"),
+        "alias Usize => Uint32 ;"
+    );
+    assert_eq!(
+        expand(
+            "@eval(OS == WIN32): let a => 1;
+            @else @eval(OS == LINUX && !(CPU_BITS == 32)): let b => 2;
+            @else: let c => 3;
+            @end;
+            let d => 4;"
+        ),
+        "let b => 2 ; let d => 4 ;"
+    );
+    assert_eq!(
+        expand("@eval(OS == WIN32 || OS_FAMILY == UNIX): a; @end; b;"),
+        "a ; b ;"
+    );
+
+    // nested chains, and blocks that aren't `@eval` inside a branch that's dropped
+    assert_eq!(
+        expand(
+            "defun f() {
+                @eval(OS_FAMILY == UNIX):
+                    @eval(1): x; @else: y; @end;
+                @else:
+                    @asm(): \"nop;\" @end;
+                    z;
+                @end;
+            }"
+        ),
+        "defun f ( ) { x ; }"
+    );
+    assert_eq!(
+        expand("@asm(): \"nop;\" @end;"),
+        "@ asm ( ) : \"nop;\" @ end ;"
+    );
+}
+
+#[test]
+#[cfg(test)]
+fn test_defs_and_constant_folding() {
+    let mut cfg = Cfg::empty();
+    cfg.set("CPU_BITS", "64");
+
+    let expand = |text: &str| expand_for_test(text, &cfg);
+
+    assert_eq!(
+        expand(
+            "@def NO_CFG => 1;
+            @def WORDS => CPU_BITS / 8 * 2 + -1;
+            @def WIDE => WORDS >= 15 && !(NO_CFG == 0);
+            @eval(WORDS == 15 && WIDE): a; @end;
+            @eval(WORDS % 4 != 3 || 2 - 3 * 2 > -4): b; @end;"
+        ),
+        "a ;"
+    );
+
+    // a `@def` in a branch that isn't taken doesn't exist
+    assert_eq!(
+        expand(
+            "@eval(CPU_BITS < 32): @def SMALL => 1; @else: @def SMALL => 0; @end;
+            @eval(SMALL): small; @else: big; @end;"
+        ),
+        "big ;"
+    );
+
+    // the right side of `&&` and `||` is only evaluated when the left side doesn't decide it
+    use crate::betac_errors::Emitter;
+
+    let text = "@def N => 0;
+        @eval(N != 0 && 10 / N > 2): a; @else: b; @end;
+        @eval(N == 0 || 10 % N == 1): c; @end;";
+    let file = SourceMap::with_mut(|mut map| map.add_file("short_circuit.beta", text));
+    assert_eq!(expand_file_for_test(text, file, &cfg), "b ; c ;");
+    assert_eq!(Emitter::messages_in(file), Vec::<String>::new());
+}
+
+#[test]
+#[cfg(test)]
+fn test_for_unrolling() {
+    use crate::betac_tokenizer::run_tokenizer;
+
+    let mut cfg = Cfg::empty();
+    cfg.set("CPU_BITS", "64");
+
+    let expand = |text: &str| expand_for_test(text, &cfg);
+
+    assert_eq!(
+        expand("@for(constexpr let N: Uint64 => 0..3): f(N); @end;"),
+        "f ( 0Uint64 ) ; f ( 1Uint64 ) ; f ( 2Uint64 ) ;"
+    );
+    assert_eq!(
+        expand("@def LAST => CPU_BITS / 32; @for(constexpr let I => -1..=LAST): I @end;"),
+        "- 1 0 1 2"
+    );
+    assert_eq!(
+        expand("@for(constexpr let N: Uint8 => (4, 2 * 4,)): N; @end;"),
+        "4Uint8 ; 8Uint8 ;"
+    );
+    assert_eq!(expand("@for(constexpr let N => 3..0): N; @end; x;"), "x ;");
+
+    // the copies are preprocessed again, so they can use the variable in macros
+    assert_eq!(
+        expand(
+            "@for(constexpr let N: Uint64 => 0..4):
+                @eval(N % 2 == 0): even(N); @else: odd(N); @end;
+            @end;"
+        ),
+        "even ( 0Uint64 ) ; odd ( 1Uint64 ) ; even ( 2Uint64 ) ; odd ( 3Uint64 ) ;"
+    );
+    assert_eq!(
+        expand(
+            "@for(constexpr let I => 0..2):
+                @for(constexpr let J => (I, 5)): f(I, J); @end;
+            @end;"
+        ),
+        "f ( 0 , 0 ) ; f ( 0 , 5 ) ; f ( 1 , 1 ) ; f ( 1 , 5 ) ;"
+    );
+
+    // the `@eval` of an `@else @eval(..):` continues the chain, so it needs no `@end` of its own
+    assert_eq!(
+        expand(
+            "@for(constexpr let N => 0..2):
+                @eval(N == 0): zero; @else @eval(N == 1): one; @else: many; @end;
+            @end;
+            after;"
+        ),
+        "zero ; one ; after ;"
+    );
+
+    // every copy traces back to the body, through the loop it came from
+    let text = "@for(constexpr let N: Uint64 => 0..2):\n    f(N);\n@end;\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("for.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = preprocess(text, &tokens, file, &cfg);
+    let second = expanded
+        .tokens
+        .iter()
+        .filter(|token| token.kind.is_literal())
+        .nth(1)
+        .unwrap();
+    assert_eq!(
+        &expanded.text[second.start as usize..second.end() as usize],
+        "1Uint64"
+    );
+
+    let (span, frames) =
+        SourceMap::with(|map| map.resolve(Span::new(file, second.start, second.len)));
+    let n = text.rfind('N').unwrap() as u32;
+    assert_eq!(span, Span::new(file, n, 1));
+    assert_eq!(
+        frames,
+        vec![(
+            Span::new(file, 0, text.find("):").unwrap() as u32 + 2),
+            "in this `@for`, where `N = 1`".to_string()
+        )]
+    );
+}
@@ -0,0 +1,385 @@
+use crate::{
+    betac_ast::Span,
+    betac_errors::{
+        preproc_errors::{InvalidPreprocTemplate, PreprocRedefinition},
+        Reportable, SpanKind,
+    },
+    betac_runner::fx_hasher::FxHashMap,
+    betac_tokenizer::token::{Token, TokenKind},
+    betac_util::trace::trace,
+};
+
+use super::{expr::Evaluator, fits, Owner, Piece, Preprocessor, Replacement, Variable};
+
+/// the most instances a single template can have
+const MAX_INSTANCES: usize = 256;
+
+/// a `@template(..): .. @end;`, which is only expanded when it's instantiated
+pub(super) struct Template {
+    /// the `@template(..):`, which is where the template is defined
+    pub(super) span: Span,
+    params: Vec<Variable>,
+    body: Vec<Token>,
+    /// the name of the instance for every list of arguments the template was instantiated with,
+    /// so each one is only expanded once
+    instances: FxHashMap<Vec<i64>, String>,
+}
+
+/// the name of the instance of `template` for `args`, like `Buffer__16` for `@Buffer(16)`
+fn instance_name(template: &str, args: &[i64]) -> String {
+    let args = args
+        .iter()
+        .map(|&arg| match arg {
+            ..0 => format!("n{}", arg.unsigned_abs()),
+            _ => arg.to_string(),
+        })
+        .collect::<Vec<_>>();
+    format!("{template}__{}", args.join("_"))
+}
+
+impl Preprocessor<'_> {
+    /// `@template(constexpr let N: Type, ..): .. @end;`, which defines a template
+    ///
+    /// the template is named after the item in its body, like the `Buffer` in `obj Buffer`.
+    /// its body is only preprocessed once it's instantiated, with the parameters replaced
+    pub(super) fn template(&mut self, at: Token) {
+        let statement = self.at_statement_start();
+        self.idx += 2;
+        let params = self.params();
+        let span = self.span(at.start, self.prev_end());
+
+        let body = self.idx;
+        let Some(end) = self.block_end("`@template`", span, statement) else {
+            return;
+        };
+        let Some(params) = params else {
+            return;
+        };
+        let body = self.tokens[body..end].to_vec();
+
+        // the name of the item is the first name after a keyword, like in `pub obj Buffer`
+        let code = body
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .collect::<Vec<_>>();
+        let Some(name) = code
+            .windows(2)
+            .find_map(|pair| match (pair[0].kind, pair[1].kind) {
+                (TokenKind::Keyword(_), TokenKind::Ident) => Some(*pair[1]),
+                _ => None,
+            })
+        else {
+            InvalidPreprocTemplate::builder()
+                .message("this `@template` doesn't declare a named item".to_string())
+                .span(span, SpanKind::NoMeta)
+                .help("a template has to contain an item, like `obj Buffer { .. }`")
+                .report();
+            return;
+        };
+        let name_span = self.span(name.start, name.end());
+        let name = self.token_text(&name).to_string();
+        trace!(
+            Preproc,
+            Debug,
+            "`@template` at {}: `{name}` with {} parameters",
+            at.start,
+            params.len()
+        );
+
+        let first = match (self.templates.get(&name), self.macros.get(&name)) {
+            (Some(first), _) => Some(first.span),
+            (_, Some(first)) => Some(first.span),
+            (None, None) => None,
+        };
+        if let Some(first) = first {
+            PreprocRedefinition::builder()
+                .message(format!("the template `{name}` is defined more than once"))
+                .span(name_span, SpanKind::NoMeta)
+                .label(first, "first defined here")
+                .note("templates and macros share their names, and can't be changed")
+                .report();
+            return;
+        }
+        self.templates.insert(
+            name,
+            Template {
+                span,
+                params,
+                body,
+                instances: FxHashMap::default(),
+            },
+        );
+    }
+
+    /// reads the `(constexpr let N: Type, ..):` after a `@template`
+    /// parameters that are malformed are reported, and make this `None`
+    fn params(&mut self) -> Option<Vec<Variable>> {
+        let open = self.skip_trivia(self.idx);
+        if self.tokens.get(open).map(|token| token.kind) != Some(TokenKind::LeftParen) {
+            self.expected(self.idx, "`(` after `@template`");
+            self.skip_past(|kind| matches!(kind, TokenKind::Colon | TokenKind::NewLine));
+            return None;
+        }
+        let Some(close) = self.matching_paren(open) else {
+            self.idx = self.tokens.len();
+            return None;
+        };
+
+        let mut params = Some(Vec::new());
+        for param in self.list(open, close) {
+            let param = match self.variable(param.start, Owner::Template) {
+                Some((_, next)) if next != param.end => {
+                    self.expected(next, "`,` or `)` after the parameter");
+                    None
+                }
+                param => param.map(|(param, _)| param),
+            };
+            params = params.zip(param).map(|(mut params, param)| {
+                params.push(param);
+                params
+            });
+        }
+
+        self.idx = close + 1;
+        if !self.eat(TokenKind::Colon) {
+            self.expected(self.idx, "`:` after the parameters");
+        }
+        params
+    }
+
+    /// `@Name(..)`, which is replaced with the name of the template's instance for those arguments
+    ///
+    /// the instance is only made the first time, and is added to the end of the file,
+    /// where it's preprocessed like any other code
+    pub(super) fn instantiate(&mut self, at: Token) {
+        let start = self.idx;
+        let name = self.token_text(&self.tokens[start + 1]).to_string();
+        self.idx += 2;
+
+        let open = self.skip_trivia(self.idx);
+        if self.tokens.get(open).map(|token| token.kind) != Some(TokenKind::LeftParen) {
+            self.expected(self.idx, "`(` after the name of the template");
+            self.replace(start..self.idx, []);
+            return;
+        }
+        let Some(close) = self.matching_paren(open) else {
+            self.idx = self.tokens.len();
+            return;
+        };
+        self.idx = close + 1;
+        let call_site = self.span(at.start, self.prev_end());
+
+        // evaluate every argument before giving up, so all of the errors are reported
+        let args = self
+            .list(open, close)
+            .into_iter()
+            .map(|arg| {
+                let value = Evaluator::new(
+                    &self.text,
+                    self.file,
+                    &self.env,
+                    &self.tokens[arg.clone()],
+                    self.tokens[arg.end].start,
+                )
+                .argument();
+                value.map(|value| (value, self.trimmed_span(arg)))
+            })
+            .collect::<Vec<_>>();
+        let instance = args
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .and_then(|args| self.instance(&name, &args, call_site));
+
+        // an instantiation that fails is dropped, since its errors are already reported
+        let replacement = instance.map(|instance| {
+            let piece = Piece::new(TokenKind::Ident, &instance, call_site);
+            self.emit(vec![piece], call_site, format!("this is `{instance}`"))
+        });
+        self.replace(start..self.idx, replacement.into_iter().flatten());
+    }
+
+    /// the name of the instance of the template `name` for `args`, which is made if it's new
+    fn instance(&mut self, name: &str, args: &[(i64, Span)], call_site: Span) -> Option<String> {
+        let template = &self.templates[name];
+        if args.len() != template.params.len() {
+            let expected = match template.params.len() {
+                1 => "1 argument".to_string(),
+                len => format!("{len} arguments"),
+            };
+            InvalidPreprocTemplate::builder()
+                .message(format!(
+                    "`{name}` takes {expected}, but {} were given",
+                    args.len()
+                ))
+                .span(call_site, SpanKind::NoMeta)
+                .label(template.span, "the template is defined here")
+                .report();
+            return None;
+        }
+
+        let mut valid = true;
+        for (param, &(value, span)) in template.params.iter().zip(args) {
+            if let Some(ty) = param.ty
+                && !fits(ty, value)
+            {
+                InvalidPreprocTemplate::builder()
+                    .message(format!("`{value}` doesn't fit in a `{}`", ty.name()))
+                    .span(span, SpanKind::NoMeta)
+                    .label(param.span, "the parameter is declared here")
+                    .report();
+                valid = false;
+            }
+        }
+        if !valid {
+            return None;
+        }
+
+        let values = args.iter().map(|&(value, _)| value).collect::<Vec<_>>();
+        if let Some(instance) = template.instances.get(&values) {
+            return Some(instance.clone());
+        }
+        if template.instances.len() >= MAX_INSTANCES {
+            InvalidPreprocTemplate::builder()
+                .message(format!("`{name}` has too many instances"))
+                .span(call_site, SpanKind::NoMeta)
+                .label(template.span, "the template is defined here")
+                .note(format!(
+                    "a template can have at most {MAX_INSTANCES} instances, \
+                     which usually means that it instantiates itself without end"
+                ))
+                .report();
+            return None;
+        }
+
+        let instance = instance_name(name, &values);
+        trace!(Preproc, Debug, "new instance of `{name}`: `{instance}`");
+        let substitutions = template
+            .params
+            .iter()
+            .zip(&values)
+            .map(|(param, &value)| (param.name.clone(), Replacement::Int(value, param.ty)))
+            .chain([(name.to_string(), Replacement::Ident(instance.clone()))])
+            .collect::<Vec<_>>();
+        let assignments = template
+            .params
+            .iter()
+            .zip(&values)
+            .map(|(param, value)| format!("`{} = {value}`", param.name))
+            .collect::<Vec<_>>();
+        let description = match assignments.is_empty() {
+            true => format!("in this instance of `{name}`"),
+            false => format!(
+                "in this instance of `{name}`, where {}",
+                assignments.join(", ")
+            ),
+        };
+
+        let body = template.body.clone();
+        let copy = self.expand(&body, &substitutions, call_site, description);
+        self.tokens.extend(copy);
+        self.templates
+            .get_mut(name)
+            .unwrap()
+            .instances
+            .insert(values, instance.clone());
+        Some(instance)
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_template_instances() {
+    use super::{cfg::Cfg, expand_for_test, preprocess};
+    use crate::{
+        betac_errors::Emitter,
+        betac_parser::{traits::Parse, GlobalParser},
+        betac_tokenizer::run_tokenizer,
+        betac_util::source_map::SourceMap,
+    };
+
+    let cfg = Cfg::empty();
+    let expand = |text: &str| expand_for_test(text, &cfg);
+
+    // every list of arguments is only expanded once, at the end of the file
+    assert_eq!(
+        expand(
+            "@template(constexpr let N: Uint64): obj Buf { ret N; } @end;
+            alias A = @Buf(4);
+            alias B = @Buf(2 * 2);
+            alias C = @Buf(8);"
+        ),
+        "alias A = Buf__4 ; alias B = Buf__4 ; alias C = Buf__8 ; \
+         obj Buf__4 { ret 4Uint64 ; } obj Buf__8 { ret 8Uint64 ; }"
+    );
+    assert_eq!(
+        expand(
+            "@template(constexpr let X, constexpr let Y: Int8): pub obj Point { x: X; y: Y; } @end;
+            alias P = @Point(-1, 2);"
+        ),
+        "alias P = Point__n1_2 ; pub obj Point__n1_2 { x : - 1 ; y : 2Int8 ; }"
+    );
+
+    // instances are preprocessed, so templates can instantiate themselves
+    assert_eq!(
+        expand(
+            "@template(constexpr let N):
+                @eval(N > 0): obj Fact { next: @Fact(N - 1); } @else: obj Fact {} @end;
+            @end;
+            alias F = @Fact(2);"
+        ),
+        "alias F = Fact__2 ; obj Fact__2 { next : Fact__1 ; } \
+         obj Fact__1 { next : Fact__0 ; } obj Fact__0 { }"
+    );
+    assert_eq!(
+        expand(
+            "@template(constexpr let N):
+                @eval(N == 0): obj Bits {} @else @eval(N == 1): obj Bits { a: Bit; } @end;
+            @end;
+            alias B = @Bits(1);"
+        ),
+        "alias B = Bits__1 ; obj Bits__1 { a : Bit ; }"
+    );
+
+    // instances, and the names that refer to them, are items the parser accepts
+    let text = "@template(constexpr let N: Uint64):\n    pub obj Buf { data: Int32; }\n@end;\n\
+        alias A = @Buf(4);\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("parsed_template.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = preprocess(text, &tokens, file, &cfg);
+    let mut parser = GlobalParser::new(file, expanded.text, expanded.tokens.into_iter());
+    while parser.next_expression() {}
+    assert_eq!(Emitter::messages_in(file), Vec::<String>::new());
+
+    // code in an instance traces back to the template, through the instantiation
+    let text =
+        "@template(constexpr let N: Uint64):\n    obj Buf { ret N; }\n@end;\nalias A = @Buf(4);\n";
+    let file = SourceMap::with_mut(|mut map| map.add_file("template.beta", text));
+    let tokens = run_tokenizer(text, file).collect::<Vec<_>>();
+    let expanded = preprocess(text, &tokens, file, &cfg);
+    let token_text = |token: &Token| &expanded.text[token.start as usize..token.end() as usize];
+    let name = expanded
+        .tokens
+        .iter()
+        .find(|token| token_text(token) == "Buf__4")
+        .unwrap();
+    let value = expanded
+        .tokens
+        .iter()
+        .find(|token| token_text(token) == "4Uint64")
+        .unwrap();
+
+    let call_site = Span::new(file, text.find("@Buf").unwrap() as u32, 7);
+    let resolve =
+        |token: &Token| SourceMap::with(|map| map.resolve(Span::new(file, token.start, token.len)));
+    assert_eq!(resolve(name), (call_site, vec![]));
+    assert_eq!(
+        resolve(value),
+        (
+            Span::new(file, text.rfind('N').unwrap() as u32, 1),
+            vec![(
+                call_site,
+                "in this instance of `Buf`, where `N = 4`".to_string()
+            )]
+        )
+    );
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::{BuildHasherDefault, Hasher},
     ops::BitXor,
 };
@@ -87,3 +87,5 @@ impl Hasher for FxHasher {
 pub type BuildFxHasher = BuildHasherDefault<FxHasher>;
 
 pub type FxHashMap<K, V> = HashMap<K, V, BuildFxHasher>;
+
+pub type FxHashSet<T> = HashSet<T, BuildFxHasher>;
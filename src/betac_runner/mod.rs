@@ -3,12 +3,16 @@ use std::{
     env::{self, Args},
     io::ErrorKind,
     iter::Skip,
+    path::PathBuf,
 };
 
 pub mod fx_hasher;
 pub mod runner;
 
 pub mod simple_runner;
+use crate::betac_ast::Span;
+use crate::betac_preproc::cfg::Cfg;
+use crate::betac_util::trace::{self, Target, TraceLevel};
 use fx_hasher::FxHashMap;
 use std::sync::{LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
@@ -16,6 +20,9 @@ static SESSION: LazyLock<RwLock<Session>> = LazyLock::new(|| {
     RwLock::new(Session {
         defined_symbols_in_global_scope: HashMap::default(),
         contents: String::new(),
+        cfg: Cfg::host(),
+        include_dirs: Vec::new(),
+        start: None,
         flags: 0,
     })
 });
@@ -39,9 +46,32 @@ pub enum SymbolKind {
     Constexpr,
 }
 
+/// where a program starts, which is set by `@start`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartPoint {
+    /// `@start main;`, with the span of `main`
+    Function(String, Span),
+    /// `@start NO_START;`, for packages, which don't start anywhere
+    NoStart(Span),
+}
+
+impl StartPoint {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Function(_, span) | Self::NoStart(span) => *span,
+        }
+    }
+}
+
 pub struct Session {
     pub defined_symbols_in_global_scope: FxHashMap<String, SymbolKind>,
     pub contents: String,
+    /// the target configuration that `@eval` conditions are checked against
+    pub cfg: Cfg,
+    /// where `@external` looks for headers, after the directory of the file it's in
+    pub include_dirs: Vec<PathBuf>,
+    /// the `@start` of the program, if it's been seen yet
+    pub start: Option<StartPoint>,
     flags: u16,
 }
 
@@ -50,6 +80,8 @@ impl Session {
     const DEBUG_MODE: u16 = 1 << 1;
     const BUILD_MODE: u16 = 1 << 2;
     const COMPILE_MODE: u16 = 1 << 3;
+    const JSON_ERROR_FORMAT: u16 = 1 << 4;
+    const EXPAND_MARKERS: u16 = 1 << 5;
 
     pub fn enter_write_critical_section<F, R>(f: F) -> R
     where
@@ -71,8 +103,14 @@ impl Session {
         })
     }
 
+    /// turns on debug mode, which traces every target at `info` or more
     pub fn set_debug_mode() {
-        Self::enter_write_critical_section(|mut lock| lock.flags |= Self::DEBUG_MODE)
+        Self::enter_write_critical_section(|mut lock| lock.flags |= Self::DEBUG_MODE);
+        for target in Target::ALL {
+            if !trace::enabled(target, TraceLevel::Info) {
+                trace::enable(target, TraceLevel::Info);
+            }
+        }
     }
 
     pub fn set_compile_mode_flag() {
@@ -98,13 +136,89 @@ impl Session {
     pub fn has_full_tree_backtrace_set() -> bool {
         Self::enter_read_section(|lock| lock.flags & Self::FULL_BACKTRACE_TREE != 0)
     }
+
+    pub fn set_json_error_format() {
+        Self::enter_write_critical_section(|mut lock| lock.flags |= Self::JSON_ERROR_FORMAT)
+    }
+
+    pub fn has_json_error_format_set() -> bool {
+        Self::enter_read_section(|lock| lock.flags & Self::JSON_ERROR_FORMAT != 0)
+    }
+
+    /// `betac expand` puts a `// from file:line` marker before the lines that need one
+    pub fn set_expand_markers() {
+        Self::enter_write_critical_section(|mut lock| lock.flags |= Self::EXPAND_MARKERS)
+    }
+
+    pub fn has_expand_markers_set() -> bool {
+        Self::enter_read_section(|lock| lock.flags & Self::EXPAND_MARKERS != 0)
+    }
+
+    /// sets a key of the target configuration, like `--cfg OS=LINUX` does
+    pub fn set_cfg(key: String, value: String) {
+        Self::enter_write_critical_section(|mut lock| lock.cfg.set(key, value))
+    }
+
+    pub fn cfg() -> Cfg {
+        Self::enter_read_section(|lock| lock.cfg.clone())
+    }
+
+    /// adds a directory to look for headers in, like `-I DIR` does
+    pub fn add_include_dir(dir: PathBuf) {
+        Self::enter_write_critical_section(|mut lock| lock.include_dirs.push(dir))
+    }
+
+    pub fn include_dirs() -> Vec<PathBuf> {
+        Self::enter_read_section(|lock| lock.include_dirs.clone())
+    }
+
+    pub fn start_point() -> Option<StartPoint> {
+        Self::enter_read_section(|lock| lock.start.clone())
+    }
+
+    /// sets where the program starts, unless that's already been set,
+    /// in which case the first `@start` is returned
+    pub fn set_start_point(start: StartPoint) -> Result<(), StartPoint> {
+        Self::enter_write_critical_section(|mut lock| match &lock.start {
+            Some(first) => Err(first.clone()),
+            None => {
+                lock.start = Some(start);
+                Ok(())
+            }
+        })
+    }
+
+    /// runs `f` without an entry point, like `betac build` if `build` is set, and clears the
+    /// entry point and build mode once it's done. only one of these runs at a time, so the
+    /// tests that set an entry point don't see each other's
+    #[cfg(test)]
+    pub fn with_start_point_reset<T>(build: bool, f: impl FnOnce() -> T) -> T {
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let _guard = LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        Self::enter_write_critical_section(|mut lock| {
+            lock.start = None;
+            if build {
+                lock.flags |= Self::BUILD_MODE;
+            }
+        });
+        let result = f();
+        Self::enter_write_critical_section(|mut lock| {
+            lock.start = None;
+            lock.flags &= !Self::BUILD_MODE;
+        });
+        result
+    }
 }
 
 pub enum Response {
     Help,
     Version,
     Run { file_name: String },
-    Build,
+    Expand { file_name: String },
+    Build { file_name: String },
+    Explain { code: String },
 }
 
 pub fn parse_command_line_args() -> Result<Response, std::io::Error> {
@@ -121,15 +235,44 @@ pub fn parse_command_line_args() -> Result<Response, std::io::Error> {
         "--version" => return Ok(Response::Version),
         "compile" => {
             Session::set_compile_mode_flag();
-            let file_name = args.next().unwrap();
-            parse_options(args).unwrap();
-            return Ok(Response::Run {
-                file_name: file_name.to_string(),
-            });
+            let Some(file_name) = args.next() else {
+                return Err(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    "compile requires a file, like `betac compile main.beta`",
+                ));
+            };
+            parse_options(args)?;
+            return Ok(Response::Run { file_name });
+        }
+        "expand" => {
+            let Some(file_name) = args.next() else {
+                return Err(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    "expand requires a file, like `betac expand main.beta`",
+                ));
+            };
+            parse_options(args)?;
+            return Ok(Response::Expand { file_name });
         }
         "build" => {
             Session::set_build_mode_flag();
-            todo!()
+            let Some(file_name) = args.next() else {
+                return Err(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    "build requires a file, like `betac build main.beta`",
+                ));
+            };
+            parse_options(args)?;
+            return Ok(Response::Build { file_name });
+        }
+        "explain" => {
+            let Some(code) = args.next() else {
+                return Err(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    "explain requires an error code, like `betac explain B0001`",
+                ));
+            };
+            return Ok(Response::Explain { code });
         }
         s => {
             return Err(std::io::Error::new(
@@ -140,29 +283,67 @@ pub fn parse_command_line_args() -> Result<Response, std::io::Error> {
     }
 }
 
-fn parse_options(iter: Skip<Args>) -> Option<()> {
+fn parse_options(mut iter: Skip<Args>) -> Result<(), std::io::Error> {
     if iter.len() == 0 {
-        return Some(());
+        return Ok(());
     } else {
-        for arg in iter {
+        while let Some(arg) = iter.next() {
             match arg.as_str() {
                 "-fbt" | "--set-full-backtrace" => {
                     if !Session::has_full_tree_backtrace_set() {
                         Session::set_full_tree_backtrace();
                     } else {
-                        return None;
+                        return Err(invalid_option(format!("`{arg}` is given more than once")));
                     }
                 }
                 "-dbg" | "--debug" => {
                     if !Session::has_debug_mode_enabled() {
                         Session::set_debug_mode();
                     } else {
-                        return None;
+                        return Err(invalid_option(format!("`{arg}` is given more than once")));
+                    }
+                }
+                // unlike debug mode, -Ztrace only turns on the targets it names
+                s if let Some(spec) = s.strip_prefix("-Ztrace=") => {
+                    let spec = trace::parse_spec(spec).map_err(|part| {
+                        invalid_option(format!(
+                            "`{part}` in `-Ztrace` isn't valid; the targets are `lexer`, \
+                            `parser`, `preproc`, `driver` and `all`, and the levels are \
+                            `info`, `debug` and `trace`, like `parser=trace`"
+                        ))
+                    })?;
+                    for (target, level) in spec {
+                        trace::enable(target, level);
                     }
                 }
-                _ => todo!(),
+                "--cfg" => {
+                    let pair = iter.next();
+                    let (key, value) =
+                        pair.as_deref().and_then(Cfg::parse_pair).ok_or_else(|| {
+                            let found =
+                                pair.map_or("nothing".to_string(), |pair| format!("`{pair}`"));
+                            invalid_option(format!("`--cfg` expects KEY=VALUE, found {found}"))
+                        })?;
+                    Session::set_cfg(key, value);
+                }
+                "-I" => {
+                    let dir = iter.next().ok_or_else(|| {
+                        invalid_option("`-I` expects a directory, like `-I include`".to_string())
+                    })?;
+                    Session::add_include_dir(dir.into());
+                }
+                s if let Some(dir) = s.strip_prefix("-I") => Session::add_include_dir(dir.into()),
+                "--error-format=json" => Session::set_json_error_format(),
+                "--error-format=human" => {}
+                "--markers" => Session::set_expand_markers(),
+                _ => return Err(invalid_option(format!("unknown option `{arg}`"))),
             }
         }
-        Some(())
+        Ok(())
     }
 }
+
+/// an error for a command line option that can't be used
+fn invalid_option(message: String) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidInput, message)
+}
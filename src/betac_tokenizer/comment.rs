@@ -0,0 +1,99 @@
+use super::{
+    token::{DocStyle, TokenKind},
+    Tokenizer,
+};
+use crate::betac_errors::{lexer_errors::UnterminatedComment, Reportable, SpanKind};
+
+impl<'a> Tokenizer<'a> {
+    /// lexes a `//` comment, up to (but not including) the newline
+    /// the first `/` has already been consumed
+    ///
+    /// `///` and `//!` are doc comments, but `////` is a plain comment, like in rust
+    pub(super) fn handle_line_comment(&mut self) -> TokenKind {
+        self.bump();
+        let kind = match (self.next_alt(), self.second()) {
+            ('/', c) if c != '/' => TokenKind::DocComment(DocStyle::Outer),
+            ('!', _) => TokenKind::DocComment(DocStyle::Inner),
+            _ => TokenKind::Comment,
+        };
+        self.eat_while(|c| c != '\n');
+        kind
+    }
+
+    /// lexes a `/* */` comment, which can be nested
+    /// the first `/` has already been consumed
+    ///
+    /// `/** */` and `/*! */` are doc comments, but `/***` and the empty `/**/` aren't
+    pub(super) fn handle_block_comment(&mut self, start: u32) -> TokenKind {
+        self.bump();
+        let kind = match (self.next_alt(), self.second()) {
+            ('*', c) if c != '*' && c != '/' => TokenKind::DocComment(DocStyle::Outer),
+            ('!', _) => TokenKind::DocComment(DocStyle::Inner),
+            _ => TokenKind::Comment,
+        };
+
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump() {
+                None => {
+                    let start = start as usize;
+                    let mut err = UnterminatedComment::builder()
+                        .message("unterminated block comment".to_string())
+                        .span(self.span(start, start + 2), SpanKind::NoMeta)
+                        .label(self.span(self.idx, self.idx), "expected `*/` here");
+                    if depth > 1 {
+                        err = err.note(format!(
+                            "block comments nest, and {depth} of them are still open"
+                        ));
+                    }
+                    err.report();
+                    break;
+                }
+                Some('/') if self.next_alt() == '*' => {
+                    self.bump();
+                    depth += 1;
+                }
+                Some('*') if self.next_alt() == '/' => {
+                    self.bump();
+                    depth -= 1;
+                }
+                Some(_) => {}
+            }
+        }
+        kind
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_comment_kinds() {
+    use super::run_tokenizer;
+    use crate::betac_util::source_map::FileId;
+
+    let lex = |input| {
+        run_tokenizer(input, FileId::DUMMY)
+            .map(|token| token.kind())
+            .filter(|kind| *kind != TokenKind::Whitespace)
+            .collect::<Vec<_>>()
+    };
+    let outer = TokenKind::DocComment(DocStyle::Outer);
+    let inner = TokenKind::DocComment(DocStyle::Inner);
+
+    assert_eq!(lex("// plain"), [TokenKind::Comment]);
+    assert_eq!(lex("//// plain"), [TokenKind::Comment]);
+    assert_eq!(
+        lex("/// docs\nx"),
+        [outer, TokenKind::NewLine, TokenKind::Ident]
+    );
+    assert_eq!(lex("//! module docs"), [inner]);
+    assert_eq!(
+        lex("/* a /* nested */ comment */ ;"),
+        [TokenKind::Comment, TokenKind::Semi]
+    );
+    assert_eq!(lex("/** docs */ /*! docs */"), [outer, inner]);
+    assert_eq!(lex("/**/ /***/"), [TokenKind::Comment, TokenKind::Comment]);
+    assert_eq!(
+        lex("a / b"),
+        [TokenKind::Ident, TokenKind::ForwardSlash, TokenKind::Ident]
+    );
+}
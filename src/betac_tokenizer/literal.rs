@@ -0,0 +1,557 @@
+use super::{
+    token::{Base, LiteralKind, NumSuffix, TokenKind},
+    Tokenizer,
+};
+use crate::betac_errors::{
+    lexer_errors::{InvalidLiteral, UnterminatedLiteral},
+    Reportable, SpanKind,
+};
+
+/// the kind of quoted literal being read, which decides the quote and the allowed escapes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Str,
+    Char,
+    ByteStr,
+    Byte,
+}
+
+impl Mode {
+    pub const fn quote(self) -> char {
+        match self {
+            Self::Str | Self::ByteStr => '"',
+            Self::Char | Self::Byte => '\'',
+        }
+    }
+
+    pub const fn is_byte(self) -> bool {
+        matches!(self, Self::ByteStr | Self::Byte)
+    }
+
+    /// char and byte literals hold exactly one char, and can't span lines
+    pub const fn is_single(self) -> bool {
+        matches!(self, Self::Char | Self::Byte)
+    }
+
+    const fn kind(self) -> LiteralKind {
+        match self {
+            Self::Str => LiteralKind::Str,
+            Self::Char => LiteralKind::Char,
+            Self::ByteStr => LiteralKind::ByteStr,
+            Self::Byte => LiteralKind::Byte,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Str => "string",
+            Self::Char => "char",
+            Self::ByteStr => "byte string",
+            Self::Byte => "byte",
+        }
+    }
+}
+
+/// something escapes can be read from
+/// this lets the tokenizer and `unescape` share `scan_escape`
+trait Cursor {
+    /// the next char, or `'\0'` at the end
+    fn peek(&self) -> char;
+    fn bump(&mut self) -> Option<char>;
+}
+
+impl Cursor for Tokenizer<'_> {
+    fn peek(&self) -> char {
+        self.next_alt()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        Tokenizer::bump(self)
+    }
+}
+
+impl Cursor for std::str::Chars<'_> {
+    fn peek(&self) -> char {
+        self.clone().next().unwrap_or('\0')
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        self.next()
+    }
+}
+
+/// reads one escape sequence, with `cursor` just past the `\`
+///
+/// the supported escapes are `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'`,
+/// `\xHH` (up to `7F`, or `FF` in byte literals) and `\u{H..}` (not in byte literals).
+/// in byte literals, `\xHH` decodes to the char with the same value, `U+00HH`
+///
+/// this never consumes a newline, or a quote that isn't part of the escape,
+/// so a bad escape doesn't also make the literal unterminated
+fn scan_escape(cursor: &mut impl Cursor, mode: Mode) -> Result<char, String> {
+    if cursor.peek() == '\n' {
+        return Err("expected an escape after `\\`".to_string());
+    }
+    let Some(c) = cursor.bump() else {
+        return Err("expected an escape after `\\`".to_string());
+    };
+
+    match c {
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        '0' => Ok('\0'),
+        '\\' => Ok('\\'),
+        '"' => Ok('"'),
+        '\'' => Ok('\''),
+        'x' => {
+            let mut value = 0;
+            for _ in 0..2 {
+                let Some(digit) = cursor.peek().to_digit(16) else {
+                    return Err("`\\x` escapes need exactly two hex digits".to_string());
+                };
+                cursor.bump();
+                value = value * 16 + digit;
+            }
+            if value > 0x7F && !mode.is_byte() {
+                return Err(format!(
+                    "`\\x{value:02X}` is out of range, only `\\x00` to `\\x7F` are allowed in a {} literal",
+                    mode.name()
+                ));
+            }
+            Ok(char::from(value as u8))
+        }
+        'u' if mode.is_byte() => Err(format!(
+            "`\\u` escapes aren't allowed in a {} literal",
+            mode.name()
+        )),
+        'u' => {
+            if cursor.peek() != '{' {
+                return Err("`\\u` escapes must look like `\\u{7FFF}`".to_string());
+            }
+            cursor.bump();
+
+            let mut value: u32 = 0;
+            let mut digits = 0;
+            loop {
+                match cursor.peek() {
+                    '}' => {
+                        cursor.bump();
+                        break;
+                    }
+                    '_' => {
+                        cursor.bump();
+                    }
+                    c if let Some(digit) = c.to_digit(16) => {
+                        cursor.bump();
+                        digits += 1;
+                        value = value.saturating_mul(16).saturating_add(digit);
+                    }
+                    _ => return Err("unterminated `\\u{...}` escape".to_string()),
+                }
+            }
+
+            if digits == 0 || digits > 6 {
+                return Err("`\\u{...}` escapes need between one and six hex digits".to_string());
+            }
+            char::from_u32(value).ok_or_else(|| format!("`{value:X}` isn't a valid unicode char"))
+        }
+        c => Err(format!("unknown escape `\\{c}`")),
+    }
+}
+
+/// decodes the escapes in `content`, the text between the quotes of a lexed literal
+///
+/// returns `None` if an escape is invalid; the tokenizer has already reported it by then.
+/// for byte literals, every char in the result is at most `U+00FF`, so it can be cast to a `u8`
+pub fn unescape(content: &str, mode: Mode) -> Option<String> {
+    let mut chars = content.chars();
+    let mut out = String::with_capacity(content.len());
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            out.push(scan_escape(&mut chars, mode).ok()?);
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+/// the value of a lexed integer literal, like `0xFF` or `1_000Uint64`
+///
+/// returns `None` if it doesn't fit in a `u64`, or if its digits are invalid;
+/// the tokenizer has already reported the second case
+pub fn int_value(text: &str, base: Base) -> Option<u64> {
+    let digits = match base {
+        Base::Decimal => text,
+        _ => &text[2..],
+    };
+    let mut value = 0u64;
+    for c in digits
+        .chars()
+        .take_while(|c| *c == '_' || c.is_ascii_hexdigit())
+    {
+        if c == '_' {
+            continue;
+        }
+        let digit = c.to_digit(base as u32)?;
+        value = value.checked_mul(base as u64)?.checked_add(digit as u64)?;
+    }
+    Some(value)
+}
+
+impl<'a> Tokenizer<'a> {
+    /// builds an `InvalidLiteral` error covering `start..end`
+    /// the caller can attach help text before reporting it
+    fn invalid_literal(
+        &self,
+        start: usize,
+        end: usize,
+        message: impl Into<String>,
+    ) -> InvalidLiteral {
+        InvalidLiteral::builder()
+            .message(message.into())
+            .span(self.span(start, end), SpanKind::NoMeta)
+    }
+
+    fn unterminated(&self, start: usize, name: &str) {
+        UnterminatedLiteral::builder()
+            .message(format!("unterminated {name} literal"))
+            .span(self.span(start, start + 1), SpanKind::NoMeta)
+            .label(
+                self.span(self.idx, self.idx),
+                "expected a closing quote here",
+            )
+            .report();
+    }
+
+    /// lexes a quoted literal, whose opening quote (and `b` prefix) have already been consumed
+    /// escapes are checked, but not decoded; use `unescape` on the contents for that
+    pub(super) fn handle_quoted(&mut self, start: u32, mode: Mode) -> TokenKind {
+        let start = start as usize;
+        let kind = TokenKind::Literal(mode.kind());
+        let mut count = 0;
+        let mut valid = true;
+
+        loop {
+            match self.next_alt() {
+                _ if self.is_eof() => {
+                    self.unterminated(start, mode.name());
+                    return kind;
+                }
+                '\n' if mode.is_single() => {
+                    self.unterminated(start, mode.name());
+                    return kind;
+                }
+                c if c == mode.quote() => {
+                    self.bump();
+                    break;
+                }
+                '\\' => {
+                    let escape = self.idx;
+                    self.bump();
+                    if let Err(message) = scan_escape(self, mode) {
+                        self.invalid_literal(escape, self.idx.max(escape + 1), message)
+                            .report();
+                        valid = false;
+                    }
+                    count += 1;
+                }
+                c => {
+                    let pos = self.idx;
+                    self.bump();
+                    if mode.is_byte() && !c.is_ascii() {
+                        self.invalid_literal(
+                            pos,
+                            self.idx,
+                            format!("non-ASCII character in {} literal", mode.name()),
+                        )
+                        .help("use a `\\xHH` escape instead")
+                        .report();
+                    }
+                    count += 1;
+                }
+            }
+        }
+
+        if mode.is_single() && valid && count != 1 {
+            let message = match count {
+                0 => format!("empty {} literal", mode.name()),
+                _ => format!("{} literal must hold exactly one char", mode.name()),
+            };
+            let help = match mode {
+                Mode::Byte => "use a byte string, like `b\"..\"`, to write more than one byte",
+                _ => "use a string, like `\"..\"`, to write more than one char",
+            };
+            self.invalid_literal(start, self.idx, message)
+                .help(help)
+                .report();
+        }
+        kind
+    }
+
+    /// lexes a raw string, like `r"C:\path"` or `r#"say "hi""#`
+    /// the `r` (and `b`, for raw byte strings) has already been consumed
+    pub(super) fn handle_raw(&mut self, start: u32, is_byte: bool) -> TokenKind {
+        let start = start as usize;
+        let (name, kind) = match is_byte {
+            true => ("raw byte string", LiteralKind::RawByteStr),
+            false => ("raw string", LiteralKind::RawStr),
+        };
+
+        let mut hashes = 0;
+        while self.next_alt() == '#' {
+            self.bump();
+            hashes += 1;
+        }
+        if self.next_alt() != '"' {
+            self.invalid_literal(start, self.idx, format!("expected `\"` to start a {name}"))
+                .report();
+            return TokenKind::Literal(kind);
+        }
+        self.bump();
+
+        loop {
+            let pos = self.idx;
+            match self.bump() {
+                None => {
+                    self.unterminated(start, name);
+                    break;
+                }
+                Some('"') => {
+                    let mut closing = 0;
+                    while closing < hashes && self.next_alt() == '#' {
+                        self.bump();
+                        closing += 1;
+                    }
+                    if closing == hashes {
+                        break;
+                    }
+                }
+                Some(c) if is_byte && !c.is_ascii() => {
+                    self.invalid_literal(pos, self.idx, format!("non-ASCII character in {name}"))
+                        .report();
+                }
+                Some(_) => {}
+            }
+        }
+        TokenKind::Literal(kind)
+    }
+
+    fn text(&self, start: usize, end: usize) -> &str {
+        self.input.get(start..end).unwrap_or("")
+    }
+
+    /// lexes a number literal, whose first digit `first` has already been consumed
+    ///
+    /// ```text
+    /// number  := (radix | decimal) suffix?
+    /// radix   := '0' ('x' | 'o' | 'b') digits
+    /// decimal := digits ('.' digits)? (('e' | 'E') ('+' | '-')? digits)?
+    /// suffix  := '_'? one of the core number types, like `Int64` or `Float32`
+    /// ```
+    /// digits can be separated with `_`, like `1_000_000`
+    ///
+    /// malformed literals are reported, but still lexed as one literal token,
+    /// so that the parser doesn't see a cascade of stray tokens afterwards
+    pub(super) fn handle_number(&mut self, start: u32, first: char) -> TokenKind {
+        let start = start as usize;
+        let base = match (first, self.next_alt()) {
+            ('0', 'x') => Base::Hexadecimal,
+            ('0', 'o') => Base::Octal,
+            ('0', 'b') => Base::Binary,
+            _ => Base::Decimal,
+        };
+
+        let mut is_float = false;
+        if base == Base::Decimal {
+            self.eat_while(|c| c.is_ascii_digit() || c == '_');
+
+            if self.next_alt() == '.' && self.second().is_ascii_digit() {
+                self.bump();
+                self.eat_while(|c| c.is_ascii_digit() || c == '_');
+                is_float = true;
+            }
+
+            if matches!(self.next_alt(), 'e' | 'E')
+                && matches!(self.second(), '0'..='9' | '+' | '-' | '_')
+            {
+                self.bump();
+                if matches!(self.next_alt(), '+' | '-') {
+                    self.bump();
+                }
+                let exponent = self.idx;
+                self.eat_while(|c| c.is_ascii_digit() || c == '_');
+                if !self
+                    .text(exponent, self.idx)
+                    .bytes()
+                    .any(|b| b.is_ascii_digit())
+                {
+                    self.invalid_literal(
+                        start,
+                        self.idx,
+                        "expected at least one digit in exponent",
+                    )
+                    .report();
+                }
+                is_float = true;
+            }
+        } else {
+            self.bump();
+            let digits = self.idx;
+            // eat anything that could be a digit, so `0b1021` is one bad literal,
+            // instead of `0b10` followed by `21`
+            self.eat_while(|c| {
+                c.is_ascii_digit()
+                    || c == '_'
+                    || (base == Base::Hexadecimal && c.is_ascii_hexdigit())
+            });
+            self.check_digits(start, digits, base);
+        }
+
+        let suffix = self.handle_number_suffix(start, base, is_float);
+        if is_float || suffix.is_some_and(NumSuffix::is_float) {
+            TokenKind::Literal(LiteralKind::Float { suffix })
+        } else {
+            TokenKind::Literal(LiteralKind::Int { base, suffix })
+        }
+    }
+
+    /// reports digits in `digits..self.idx` that don't fit `base`, or a missing number
+    fn check_digits(&self, start: usize, digits: usize, base: Base) {
+        let text = self.text(digits, self.idx);
+        if !text.bytes().any(|b| b != b'_') {
+            self.invalid_literal(start, self.idx, "no valid digits found for number")
+                .report();
+            return;
+        }
+
+        let name = match base {
+            Base::Binary => "binary",
+            Base::Octal => "octal",
+            Base::Decimal => "decimal",
+            Base::Hexadecimal => "hexadecimal",
+        };
+        for (offset, c) in text.char_indices() {
+            if c != '_' && c.to_digit(base as u32).is_none() {
+                let pos = digits + offset;
+                self.invalid_literal(
+                    pos,
+                    pos + 1,
+                    format!("invalid digit `{c}` in {name} literal"),
+                )
+                .report();
+            }
+        }
+    }
+
+    /// lexes an optional type suffix, like the `Uint8` in `255Uint8` or `255_Uint8`
+    fn handle_number_suffix(
+        &mut self,
+        start: usize,
+        base: Base,
+        is_float: bool,
+    ) -> Option<NumSuffix> {
+        if self.next_alt() == '_' && self.second().is_ascii_alphabetic() {
+            self.bump();
+        }
+        if !self.next_alt().is_ascii_alphabetic() {
+            return None;
+        }
+
+        let suffix_start = self.idx;
+        self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let text = self.text(suffix_start, self.idx);
+
+        let Some(suffix) = NumSuffix::from_name(text) else {
+            let valid = NumSuffix::ALL.map(NumSuffix::name).join(", ");
+            self.invalid_literal(
+                suffix_start,
+                self.idx,
+                format!("invalid suffix `{text}` for number literal"),
+            )
+            .help(format!("the valid suffixes are {valid}"))
+            .report();
+            return None;
+        };
+
+        if suffix.is_float() && base != Base::Decimal {
+            self.invalid_literal(
+                start,
+                self.idx,
+                "float suffixes are only allowed on decimal literals",
+            )
+            .report();
+        } else if is_float && !suffix.is_float() {
+            self.invalid_literal(
+                suffix_start,
+                self.idx,
+                format!("integer suffix `{text}` on a float literal"),
+            )
+            .report();
+        }
+        Some(suffix)
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_number_literal_kinds() {
+    use super::run_tokenizer;
+    use crate::betac_util::source_map::FileId;
+
+    let lex = |input| {
+        run_tokenizer(input, FileId::DUMMY)
+            .map(|token| token.kind())
+            .collect::<Vec<_>>()
+    };
+    let int = |base, suffix| TokenKind::Literal(LiteralKind::Int { base, suffix });
+    let float = |suffix| TokenKind::Literal(LiteralKind::Float { suffix });
+
+    assert_eq!(lex("1_000_000"), [int(Base::Decimal, None)]);
+    assert_eq!(lex("0x1F"), [int(Base::Hexadecimal, None)]);
+    assert_eq!(lex("0b1010"), [int(Base::Binary, None)]);
+    assert_eq!(
+        lex("0o17_Uint8"),
+        [int(Base::Octal, Some(NumSuffix::Uint8))]
+    );
+    assert_eq!(lex("3.14"), [float(None)]);
+    assert_eq!(lex("1e9"), [float(None)]);
+    assert_eq!(lex("2.5e-3Float32"), [float(Some(NumSuffix::Float32))]);
+    assert_eq!(lex("1Float64"), [float(Some(NumSuffix::Float64))]);
+    assert_eq!(
+        lex("255Int64"),
+        [int(Base::Decimal, Some(NumSuffix::Int64))]
+    );
+    assert_eq!(lex("0..4")[0], int(Base::Decimal, None));
+}
+
+#[test]
+#[cfg(test)]
+fn test_quoted_literal_kinds() {
+    use super::run_tokenizer;
+    use crate::betac_util::source_map::FileId;
+
+    let lex = |input| {
+        run_tokenizer(input, FileId::DUMMY)
+            .map(|token| token.kind())
+            .collect::<Vec<_>>()
+    };
+    let lit = TokenKind::Literal;
+
+    assert_eq!(lex(r#""say \"hi\"""#), [lit(LiteralKind::Str)]);
+    assert_eq!(lex(r"'\n'"), [lit(LiteralKind::Char)]);
+    assert_eq!(lex(r"b'\xFF'"), [lit(LiteralKind::Byte)]);
+    assert_eq!(lex(r#"b"\x1b[0m""#), [lit(LiteralKind::ByteStr)]);
+    assert_eq!(lex(r##"r#"a "quoted" word"#"##), [lit(LiteralKind::RawStr)]);
+    assert_eq!(lex(r#"br"\d""#), [lit(LiteralKind::RawByteStr)]);
+
+    assert_eq!(
+        unescape(r"tab\t\u{1F600}\x41\\", Mode::Str).as_deref(),
+        Some("tab\t\u{1F600}A\\")
+    );
+    assert_eq!(unescape(r"\xFF", Mode::ByteStr).as_deref(), Some("\u{FF}"));
+    assert_eq!(unescape(r"\xFF", Mode::Str), None);
+    assert_eq!(unescape(r"\q", Mode::Str), None);
+    assert_eq!(unescape(r"\u{110000}", Mode::Str), None);
+}
@@ -1,55 +1,92 @@
-use std::cell::RefCell;
-use std::rc::Rc;
 use token::{Token, TokenKind};
 
+use crate::{
+    betac_ast::Span,
+    betac_errors::{lexer_errors::InvalidUtf8, Reportable, SpanKind},
+    betac_util::source_map::FileId,
+};
+
+mod comment;
+pub mod literal;
 pub mod token;
 
+#[derive(Clone)]
 pub struct Tokenizer<'a> {
-    input: &'a [u8],
+    input: &'a str,
+    /// the byte offset of the next char, which is always on a char boundary
     idx: usize,
+    /// the file spans in diagnostics point into
+    file: FileId,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(input: &'a str) -> Self {
+        Self::with_file(input, FileId::DUMMY)
+    }
+
+    pub fn with_file(input: &'a str, file: FileId) -> Self {
         Self {
-            input: input.as_bytes(),
+            input,
             idx: 0,
+            file,
         }
     }
 
+    /// moves the tokenizer to `idx`, which must be the start of a token
+    pub fn seek(&mut self, idx: usize) {
+        assert!(
+            self.input.is_char_boundary(idx),
+            "{idx} isn't on a char boundary"
+        );
+        self.idx = idx;
+    }
+
+    /// makes a span in the current file, covering `start..end`
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.file, start as u32, end.saturating_sub(start) as u32)
+    }
+
+    /// the input that hasn't been consumed yet
+    #[inline(always)]
+    fn rest(&self) -> &'a str {
+        &self.input[self.idx..]
+    }
+
     #[deprecated]
     pub fn next(&self) -> char {
-        self.input
-            .get(self.idx + 1)
-            .map(|c| *c as char)
-            .unwrap_or('\0')
+        self.second()
     }
 
     pub fn next_alt(&self) -> char {
-        self.input.get(self.idx).map(|c| *c as char).unwrap_or('\0')
+        self.rest().chars().next().unwrap_or('\0')
+    }
+
+    /// returns the char after `next_alt`, without consuming anything
+    pub fn second(&self) -> char {
+        self.rest().chars().nth(1).unwrap_or('\0')
     }
 
     pub fn prev(&self) -> char {
-        self.input[self.idx - 1] as char
+        self.input[..self.idx].chars().next_back().unwrap_or('\0')
     }
 
     #[deprecated]
     pub fn nth_next(&self, pos: usize) -> char {
-        self.input[self.idx + pos] as char
+        self.rest().chars().nth(pos).unwrap_or('\0')
     }
 
     pub fn nth_prev(&self, pos: usize) -> char {
-        self.input.get(self.idx + pos).map(|i| *i).unwrap_or(b'\0') as char
+        self.rest().chars().nth(pos).unwrap_or('\0')
     }
 
     pub fn bump(&mut self) -> Option<char> {
-        let c = self.input.get(self.idx).map(|i| *i as char)?;
-        self.idx += 1;
+        let c = self.rest().chars().next()?;
+        self.idx += c.len_utf8();
         Some(c)
     }
 
     pub fn is_eof(&self) -> bool {
-        self.input.get(self.idx).is_none()
+        self.idx >= self.input.len()
     }
 
     pub fn eat_while(&mut self, mut pred: impl FnMut(char) -> bool) {
@@ -58,20 +95,153 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
-    pub fn as_str(&self) -> &str {
-        unsafe { std::str::from_utf8_unchecked(self.input) }
+    pub fn as_str(&self) -> &'a str {
+        self.input
+    }
+}
+
+/// a malformed UTF-8 sequence found by `decode_source`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedUtf8 {
+    /// where the `U+FFFD` that replaced the sequence starts in the decoded text
+    pub start: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// decodes a source file, replacing each malformed UTF-8 sequence with `U+FFFD`
+///
+/// the tokenizer only ever sees valid UTF-8, so the sequences are returned instead,
+/// to be reported with `report_malformed_utf8` once the file has an id
+pub fn decode_source(bytes: &[u8]) -> (String, Vec<MalformedUtf8>) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut malformed = Vec::new();
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        if !chunk.invalid().is_empty() {
+            malformed.push(MalformedUtf8 {
+                start: text.len() as u32,
+                bytes: chunk.invalid().to_vec(),
+            });
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+    }
+    (text, malformed)
+}
+
+pub fn report_malformed_utf8(file: FileId, malformed: &[MalformedUtf8]) {
+    for sequence in malformed {
+        let bytes = sequence
+            .bytes
+            .iter()
+            .map(|byte| format!("\\x{byte:02X}"))
+            .collect::<String>();
+        InvalidUtf8::builder()
+            .message(format!("invalid UTF-8 sequence `{bytes}`"))
+            .span(
+                Span::new(
+                    file,
+                    sequence.start,
+                    char::REPLACEMENT_CHARACTER.len_utf8() as u32,
+                ),
+                SpanKind::NoMeta,
+            )
+            .note("source files must be valid UTF-8")
+            .report();
     }
 }
 
-pub fn run_tokenizer(input: &str) -> impl Iterator<Item = Token> + Clone + '_ {
-    // TODO: if we ever do multithreaded stuff, then we need to change to Arc<Mutex<>>
-    let tokenizer = Rc::new(RefCell::new(Tokenizer::new(input)));
-    crate::betac_util::from_fn(move || {
-        let token = tokenizer.borrow_mut().advance_token();
-        if token.kind() == TokenKind::Eof || token.kind() == TokenKind::Unknown {
-            None
-        } else {
-            Some(token)
+/// lexes all of `input`, and returns an iterator over the tokens
+///
+/// the whole file is lexed up front, so errors are only reported once,
+/// and cloning the iterator (for example, to peek ahead in the parser) is cheap.
+/// chars the tokenizer doesn't know become `TokenKind::Unknown` tokens, which have
+/// already been reported, so lexing always continues to the end of the input
+pub fn run_tokenizer(input: &str, file: FileId) -> impl Iterator<Item = Token> + Clone + '_ {
+    let mut tokenizer = Tokenizer::with_file(input, file);
+    let mut tokens = Vec::new();
+    loop {
+        let token = tokenizer.advance_token();
+        if token.kind() == TokenKind::Eof {
+            break;
         }
-    })
+        tokens.push(token);
+    }
+    tokens.into_iter()
+}
+
+#[test]
+#[cfg(test)]
+fn test_unicode_input() {
+    let lex = |input| {
+        run_tokenizer(input, FileId::DUMMY)
+            .map(|token| (token.kind(), token.start))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        lex("größe => Ω;"),
+        [
+            (TokenKind::Ident, 0),
+            (TokenKind::Whitespace, 7),
+            (TokenKind::FatArrow, 8),
+            (TokenKind::Whitespace, 10),
+            (TokenKind::Ident, 11),
+            (TokenKind::Semi, 13),
+        ]
+    );
+    assert_eq!(
+        lex("\"héllo 🌍\" // ünïcode"),
+        [
+            (TokenKind::Literal(token::LiteralKind::Str), 0),
+            (TokenKind::Whitespace, 13),
+            (TokenKind::Comment, 14),
+        ]
+    );
+
+    let (text, malformed) = decode_source(b"ok \xFF\xFE!");
+    assert_eq!(text, "ok \u{FFFD}\u{FFFD}!");
+    assert_eq!(
+        malformed,
+        [
+            MalformedUtf8 {
+                start: 3,
+                bytes: vec![0xFF]
+            },
+            MalformedUtf8 {
+                start: 6,
+                bytes: vec![0xFE]
+            },
+        ]
+    );
+}
+
+#[test]
+#[cfg(test)]
+fn test_whitespace_and_unknown() {
+    let lex = |input| {
+        run_tokenizer(input, FileId::DUMMY)
+            .map(|token| (token.kind(), token.start))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        lex("a \t\u{0C}b\r\n\rc"),
+        [
+            (TokenKind::Ident, 0),
+            (TokenKind::Whitespace, 1),
+            (TokenKind::Ident, 4),
+            (TokenKind::NewLine, 5),
+            (TokenKind::Whitespace, 7),
+            (TokenKind::Ident, 8),
+        ]
+    );
+    assert_eq!(
+        lex("a\u{0}§b"),
+        [
+            (TokenKind::Ident, 0),
+            (TokenKind::Unknown, 1),
+            (TokenKind::Unknown, 2),
+            (TokenKind::Ident, 4),
+        ]
+    );
 }
@@ -1,26 +1,51 @@
-use super::Tokenizer;
+use super::{literal::Mode, Tokenizer};
+use crate::{
+    betac_errors::{lexer_errors::UnknownCharacter, Reportable, SpanKind},
+    betac_util::trace::trace,
+};
+
+/// unicode's `Pattern_White_Space`, except for `\n`, which is its own token
+/// a lone `\r` is whitespace, but `\r\n` is a newline
+fn is_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\t'
+            | '\u{0B}'
+            | '\u{0C}'
+            | '\r'
+            | '\u{85}'
+            | '\u{200E}'
+            | '\u{200F}'
+            | '\u{2028}'
+            | '\u{2029}'
+    )
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
-    pub start: u16,
+    pub start: u32,
+    /// the exact length of the token in bytes, so the source can be rebuilt from the tokens
+    pub len: u32,
 }
 
 impl Token {
     pub const DUMMMY: Self = Self {
         kind: TokenKind::Eof,
         start: 0,
+        len: 0,
     };
 
-    pub fn len(&self) -> Option<u16> {
-        use TokenKind::*;
-        match self.kind {
-            At | Eq | LeftBrace | RightBrace | LeftParen | RightParen | LeftBracket
-            | RightBracket | Ampersand | Pipe | Star | Semi | Colon | Comma | Whitespace | Lt
-            | Gt | Eof => Some(1),
-            AndAnd | PipePipe | FatArrow | EqEq | NotEq | LtEq | GtEq => Some(2),
-            _ => None,
-        }
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn end(&self) -> u32 {
+        self.start + self.len
     }
 
     pub fn kind(&self) -> TokenKind {
@@ -36,7 +61,7 @@ impl Token {
 }
 
 impl std::ops::Sub for Token {
-    type Output = u16;
+    type Output = u32;
 
     /// for `Token` this subtracts the `start` from rhs
     fn sub(self, rhs: Self) -> Self::Output {
@@ -44,7 +69,7 @@ impl std::ops::Sub for Token {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
 pub enum TokenKind {
     At = b'@',
@@ -83,6 +108,7 @@ pub enum TokenKind {
     Underscore = b'_',
     Dash = b'`',
     Tilde = b'~',
+    Plus = b'+',
     Eof = b'\0',
     /// &&
     AndAnd,
@@ -103,31 +129,323 @@ pub enum TokenKind {
     /// ::
     Path,
     Ident,
+    Keyword(Kw),
     Lifetime,
-    Literal,
+    Literal(LiteralKind),
+    /// a `//` or `/* */` comment
+    Comment,
+    /// a `///`, `//!`, `/** */` or `/*! */` comment
+    /// these are kept so documentation can be attached to items
+    DocComment(DocStyle),
     NewLine,
     Unknown,
 }
 
 impl TokenKind {
     fn single_char(c: u8) -> Option<TokenKind> {
-        match c {
-            // SAFETY: TokenKind is guaranteed to be valid at these ranges
-            32..=47 | 58..=64 | 91..=96 | 123..=126 => unsafe { Some(std::mem::transmute(c)) },
-            _ => None,
+        use TokenKind::*;
+        Some(match c {
+            b'@' => At,
+            b'&' => Ampersand,
+            b'|' => Pipe,
+            b'*' => Star,
+            b'=' => Eq,
+            b'!' => Not,
+            b'<' => Lt,
+            b'>' => Gt,
+            b'(' => LeftParen,
+            b')' => RightParen,
+            b'[' => LeftBrace,
+            b']' => RightBrace,
+            b'{' => LeftBracket,
+            b'}' => RightBracket,
+            b';' => Semi,
+            b':' => Colon,
+            b',' => Comma,
+            b' ' => Whitespace,
+            b'"' => Quote,
+            b'#' => Pound,
+            b'$' => Dollar,
+            b'?' => Question,
+            b'%' => Percent,
+            b'\'' => SingleQuote,
+            b'-' => Minus,
+            b'.' => Dot,
+            b'/' => ForwardSlash,
+            b'\\' => BackSlash,
+            b'^' => Carat,
+            b'_' => Underscore,
+            b'`' => Dash,
+            b'~' => Tilde,
+            b'+' => Plus,
+            _ => return None,
+        })
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(self, TokenKind::Literal(_))
+    }
+
+    /// whitespace and comments, which have no meaning to the parser
+    /// doc comments are trivia too, but the parser keeps them around for the next item
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace
+                | TokenKind::NewLine
+                | TokenKind::Comment
+                | TokenKind::DocComment(_)
+        )
+    }
+}
+
+/// what a doc comment documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DocStyle {
+    /// `///` and `/** */`, which document the item after them
+    Outer,
+    /// `//!` and `/*! */`, which document the file they're in
+    Inner,
+}
+
+/// a reserved word, which can't be used as an identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kw {
+    Let,
+    Static,
+    Const,
+    Defun,
+    Obj,
+    Comp,
+    Trait,
+    Extend,
+    Pack,
+    Import,
+    Alias,
+    Pub,
+    Priv,
+    Mut,
+    Unsafe,
+    /// `extern`, on declarations of things defined outside of beta_lang, like C functions
+    Extern,
+    Ret,
+    Throws,
+    Requires,
+    Operator,
+    /// `This`, the type of the current object
+    SelfType,
+    /// `this`, the current object
+    SelfValue,
+}
+
+impl Kw {
+    pub const ALL: [Kw; 22] = [
+        Self::Let,
+        Self::Static,
+        Self::Const,
+        Self::Defun,
+        Self::Obj,
+        Self::Comp,
+        Self::Trait,
+        Self::Extend,
+        Self::Pack,
+        Self::Import,
+        Self::Alias,
+        Self::Pub,
+        Self::Priv,
+        Self::Mut,
+        Self::Unsafe,
+        Self::Extern,
+        Self::Ret,
+        Self::Throws,
+        Self::Requires,
+        Self::Operator,
+        Self::SelfType,
+        Self::SelfValue,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Let => "let",
+            Self::Static => "static",
+            Self::Const => "const",
+            Self::Defun => "defun",
+            Self::Obj => "obj",
+            Self::Comp => "comp",
+            Self::Trait => "trait",
+            Self::Extend => "extend",
+            Self::Pack => "pack",
+            Self::Import => "import",
+            Self::Alias => "alias",
+            Self::Pub => "pub",
+            Self::Priv => "priv",
+            Self::Mut => "mut",
+            Self::Unsafe => "unsafe",
+            Self::Extern => "extern",
+            Self::Ret => "ret",
+            Self::Throws => "throws",
+            Self::Requires => "requires",
+            Self::Operator => "operator",
+            Self::SelfType => "This",
+            Self::SelfValue => "this",
         }
     }
+
+    /// this is called on every identifier, so it's a match instead of a search through `ALL`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "let" => Self::Let,
+            "static" => Self::Static,
+            "const" => Self::Const,
+            "defun" => Self::Defun,
+            "obj" => Self::Obj,
+            "comp" => Self::Comp,
+            "trait" => Self::Trait,
+            "extend" => Self::Extend,
+            "pack" => Self::Pack,
+            "import" => Self::Import,
+            "alias" => Self::Alias,
+            "pub" => Self::Pub,
+            "priv" => Self::Priv,
+            "mut" => Self::Mut,
+            "unsafe" => Self::Unsafe,
+            "extern" => Self::Extern,
+            "ret" => Self::Ret,
+            "throws" => Self::Throws,
+            "requires" => Self::Requires,
+            "operator" => Self::Operator,
+            "This" => Self::SelfType,
+            "this" => Self::SelfValue,
+            _ => return None,
+        })
+    }
+
+    /// whether this keyword starts a new top-level item
+    /// panic-mode recovery in the parser stops in front of these
+    pub const fn starts_item(self) -> bool {
+        matches!(
+            self,
+            Self::Let
+                | Self::Static
+                | Self::Const
+                | Self::Defun
+                | Self::Pub
+                | Self::Priv
+                | Self::Obj
+                | Self::Comp
+                | Self::Trait
+                | Self::Extend
+                | Self::Pack
+                | Self::Import
+                | Self::Alias
+                | Self::Extern
+        )
+    }
+}
+
+/// what sort of literal a `TokenKind::Literal` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LiteralKind {
+    Int {
+        base: Base,
+        suffix: Option<NumSuffix>,
+    },
+    Float {
+        suffix: Option<NumSuffix>,
+    },
+    Str,
+    RawStr,
+    ByteStr,
+    RawByteStr,
+    Char,
+    Byte,
+}
+
+/// the radix of an integer literal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Base {
+    Binary = 2,
+    Octal = 8,
+    Decimal = 10,
+    Hexadecimal = 16,
+}
+
+/// a type suffix on a number literal, like the `Uint8` in `255Uint8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NumSuffix {
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Float32,
+    Float64,
+}
+
+impl NumSuffix {
+    pub const ALL: [NumSuffix; 10] = [
+        Self::Int8,
+        Self::Int16,
+        Self::Int32,
+        Self::Int64,
+        Self::Uint8,
+        Self::Uint16,
+        Self::Uint32,
+        Self::Uint64,
+        Self::Float32,
+        Self::Float64,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Int8 => "Int8",
+            Self::Int16 => "Int16",
+            Self::Int32 => "Int32",
+            Self::Int64 => "Int64",
+            Self::Uint8 => "Uint8",
+            Self::Uint16 => "Uint16",
+            Self::Uint32 => "Uint32",
+            Self::Uint64 => "Uint64",
+            Self::Float32 => "Float32",
+            Self::Float64 => "Float64",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|suffix| suffix.name() == name)
+    }
+
+    pub const fn is_float(self) -> bool {
+        matches!(self, Self::Float32 | Self::Float64)
+    }
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn advance_token(&mut self) -> Token {
-        let start = self.idx as u16;
-        let next = self.bump().unwrap_or('\0');
+        let start = self.idx as u32;
+        let Some(next) = self.bump() else {
+            return Token {
+                kind: TokenKind::Eof,
+                start,
+                len: 0,
+            };
+        };
         let kind = match next {
-            '\0' => TokenKind::Eof,
+            '\n' => TokenKind::NewLine,
+            '\r' if self.next_alt() == '\n' => {
+                self.bump();
+                TokenKind::NewLine
+            }
+            c if is_whitespace(c) => {
+                self.eat_whitespace();
+                TokenKind::Whitespace
+            }
             // TODO: fix all occurances so we can actually parse correctly
             '=' if self.next_alt() == '>' => {
-                println!("found => at: {start}");
+                trace!(Lexer, Trace, "found `=>` at: {start}");
                 self.bump();
                 TokenKind::FatArrow
             }
@@ -163,54 +481,98 @@ impl<'a> Tokenizer<'a> {
                 self.bump();
                 TokenKind::Path
             }
-            '"' => self.handle_literal_string(),
-            '\'' => self.handle_literal_char(),
-            ident if ident.is_ascii_alphabetic() || ident == '_' => self.handle_ident(),
-            c if let Some(kind) = TokenKind::single_char(c as u8) => kind,
-            num if num.is_ascii_digit() => self.handle_number(),
-            '\n' => TokenKind::NewLine,
+            '/' if self.next_alt() == '/' => self.handle_line_comment(),
+            '/' if self.next_alt() == '*' => self.handle_block_comment(start),
+            '"' => self.handle_quoted(start, Mode::Str),
+            '\'' => self.handle_quoted(start, Mode::Char),
+            'r' if matches!(self.next_alt(), '"' | '#') => self.handle_raw(start, false),
+            'b' if self.next_alt() == '"' => {
+                self.bump();
+                self.handle_quoted(start, Mode::ByteStr)
+            }
+            'b' if self.next_alt() == '\'' => {
+                self.bump();
+                self.handle_quoted(start, Mode::Byte)
+            }
+            'b' if self.next_alt() == 'r' && matches!(self.second(), '"' | '#') => {
+                self.bump();
+                self.handle_raw(start, true)
+            }
+            ident if ident == '_' || unicode_ident::is_xid_start(ident) => self.handle_ident(start),
+            c if c.is_ascii()
+                && let Some(kind) = TokenKind::single_char(c as u8) =>
+            {
+                kind
+            }
+            num if num.is_ascii_digit() => self.handle_number(start, num),
             c => {
-                if c == '\n' {
-                    println!("newline found");
-                }
-                println!("unexpected: {c}", c = c as u8);
-
+                trace!(Lexer, Debug, "unexpected char {c:?} at: {start}");
+                UnknownCharacter::builder()
+                    .message(format!(
+                        "unknown character `{}` (U+{:04X})",
+                        c.escape_debug(),
+                        c as u32
+                    ))
+                    .span(self.span(start as usize, self.idx), SpanKind::NoMeta)
+                    .report();
                 TokenKind::Unknown
             }
         };
-        Token { kind, start }
+        Token {
+            kind,
+            start,
+            len: self.idx as u32 - start,
+        }
     }
 
-    fn handle_number(&mut self) -> TokenKind {
-        self.eat_while(|c| c.is_ascii_hexdigit());
-        TokenKind::Literal
+    /// eats horizontal whitespace, stopping in front of a newline
+    fn eat_whitespace(&mut self) {
+        while is_whitespace(self.next_alt()) && !(self.next_alt() == '\r' && self.second() == '\n')
+        {
+            self.bump();
+        }
     }
 
-    fn handle_literal_char(&mut self) -> TokenKind {
-        match self.bump().unwrap_or('\0') {
-            '\0' => return TokenKind::Eof,
-            '\\' => {
-                self.bump();
-            }
-            _ => {}
+    /// lexes an identifier or keyword, whose first char has already been consumed
+    /// identifiers follow unicode's `XID_Start XID_Continue*`, with `_` allowed at the start
+    fn handle_ident(&mut self, start: u32) -> TokenKind {
+        self.eat_while(unicode_ident::is_xid_continue);
+        match Kw::from_name(&self.as_str()[start as usize..self.idx]) {
+            Some(kw) => TokenKind::Keyword(kw),
+            None => TokenKind::Ident,
         }
-        TokenKind::Literal
     }
+}
 
-    fn handle_literal_string(&mut self) -> TokenKind {
-        self.eat_while(|c| c != '"');
-        TokenKind::Literal
-    }
+#[test]
+#[cfg(test)]
+fn test_keywords() {
+    use super::run_tokenizer;
+    use crate::betac_util::source_map::FileId;
 
-    fn handle_ident(&mut self) -> TokenKind {
-        let mut count = 0;
-        self.eat_while(|c| {
-            count += 1;
-            c.is_ascii_alphanumeric()
-        });
-        if count != 1 {
-            self.bump();
-        }
-        TokenKind::Ident
-    }
+    let lex = |input| {
+        run_tokenizer(input, FileId::DUMMY)
+            .map(|token| token.kind())
+            .filter(|kind| *kind != TokenKind::Whitespace)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        lex("pub defun letter This"),
+        [
+            TokenKind::Keyword(Kw::Pub),
+            TokenKind::Keyword(Kw::Defun),
+            TokenKind::Ident,
+            TokenKind::Keyword(Kw::SelfType),
+        ]
+    );
+    assert!(Kw::ALL
+        .into_iter()
+        .all(|kw| Kw::from_name(kw.name()) == Some(kw)));
+    assert_eq!(
+        run_tokenizer("defun", FileId::DUMMY)
+            .map(|token| token.len())
+            .collect::<Vec<_>>(),
+        [5]
+    );
 }
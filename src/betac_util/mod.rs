@@ -3,7 +3,9 @@ pub mod linked_list;
 
 pub mod ptr;
 pub mod small_vec;
+pub mod source_map;
 pub mod sso;
+pub mod trace;
 
 use std::sync::Mutex;
 
@@ -150,26 +152,49 @@ macro_rules! debug_dbg_impl {
 
 pub(crate) use debug_dbg_impl as ddbg;
 
+/// reports an unexpected construct in the parser, then recovers with `ParseInner::recover`
+///
+/// evaluates to a no-op node, so the parser can keep going and report the rest of the file
+///
+/// `pproc name, start, this`: an unknown `@name` macro, starting at the `@`\n
+/// `name, start, this`: an identifier that can't start an item here\n
+/// `tok token, this`: any other token that can't start an item here\n
 #[macro_export]
 macro_rules! catch {
-    (dbg $caught:ident) => {{
-        println!("caught: {:?}", $caught);
-        todo!()
-    }};
-    ($caught:ident) => {{
-        println!("caught: {}", $caught);
-        todo!()
+    (pproc $caught:ident, $start:expr, $this:expr) => {{
+        let span = $this.span($start, $this.next_start());
+        $crate::betac_errors::Reportable::report(
+            $crate::betac_errors::preproc_errors::UnrecognizedPreprocMacro::builder()
+                .message(format!("unrecognized preprocessor macro `@{}`", $caught))
+                .span(span, $crate::betac_errors::SpanKind::NoMeta),
+        );
+        $this.recover($crate::betac_tokenizer::token::TokenKind::At);
+        $crate::catch!(@recovered)
     }};
     (tok $caught:ident, $this:expr) => {{
-        println!("caught token: {:?}", $caught);
-        if let Some(next) = $this.peek() {
-            let substr = unsafe {
-                $this
-                    .source
-                    .reconstruct_from_start_end_unchecked($caught.start, next.start)
-            };
-            println!("substring: {}", substr);
-        }
-        todo!()
+        let span = $this.span($caught.start, $caught.end());
+        let message = format!("expected an item, found `{}`", $this.text($caught));
+        $crate::betac_errors::Reportable::report(
+            $crate::betac_errors::general_errors::UnexpectedTokenInInput::builder()
+                .message(message)
+                .span(span, $crate::betac_errors::SpanKind::NoMeta),
+        );
+        $this.recover($caught.kind);
+        $crate::catch!(@recovered)
+    }};
+    (@recovered) => {{
+        let node: $crate::betac_util::ptr::Ptr<dyn $crate::betac_ast::AstNode> =
+            $crate::betac_util::ptr::Ptr::new($crate::betac_ast::NoOp);
+        (node, true)
+    }};
+    ($caught:ident, $start:expr, $this:expr) => {{
+        let span = $this.span($start, $this.next_start());
+        $crate::betac_errors::Reportable::report(
+            $crate::betac_errors::general_errors::UnexpectedTokenInInput::builder()
+                .message(format!("expected an item, found `{}`", $caught))
+                .span(span, $crate::betac_errors::SpanKind::NoMeta),
+        );
+        $this.recover($crate::betac_tokenizer::token::TokenKind::Ident);
+        $crate::catch!(@recovered)
     }};
 }
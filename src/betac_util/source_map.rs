@@ -0,0 +1,286 @@
+use std::{
+    ops::Range,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::betac_ast::Span;
+
+/// the global source map
+/// every file that gets loaded by the driver is registered here, so that
+/// diagnostics can turn a `Span` back into a file:line:col
+pub static SOURCE_MAP: RwLock<SourceMap> = RwLock::new(SourceMap::new());
+
+/// identifies a file registered in the `SourceMap`
+///
+/// `FileId::DUMMY` (0) is never handed out, so spans that were never
+/// attached to a file can be told apart from real ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u16);
+
+impl FileId {
+    pub const DUMMY: Self = Self(0);
+
+    pub const fn is_dummy(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// a resolved location inside of a source file
+/// both `line` and `column` are 1-based, and `column` is counted in chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loc {
+    pub file: FileId,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// code the preprocessor made, like one iteration of a `@for`
+///
+/// expansions are placed after the end of the file they were made in, past its real source,
+/// so their spans can be told apart, and traced back to the code they were copied from
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    /// the offsets the expansion covers, which are all past the end of the source
+    pub range: Range<u32>,
+    /// the macro that was expanded, like the `@for(..):` of a loop
+    pub call_site: Span,
+    /// what is shown at the call site, like "in this `@for`, where `N = 2`"
+    pub description: String,
+    /// `(start, origin)` for every token of the expansion, sorted by `start`,
+    /// where `origin` is the token it was copied from, which can be in an older expansion
+    pub origins: Vec<(u32, Span)>,
+}
+
+pub struct SourceFile {
+    name: String,
+    source: Arc<str>,
+    /// byte offsets of the start of every line
+    /// the first entry is always 0
+    line_starts: Vec<u32>,
+    /// sorted by where they start
+    expansions: Vec<Expansion>,
+}
+
+impl SourceFile {
+    pub fn new(name: String, source: Arc<str>) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(
+                source
+                    .bytes()
+                    .enumerate()
+                    .filter(|(_, b)| *b == b'\n')
+                    .map(|(idx, _)| idx as u32 + 1),
+            )
+            .collect();
+
+        Self {
+            name,
+            source,
+            line_starts,
+            expansions: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &Arc<str> {
+        &self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// returns the 0-based index of the line that contains `pos`
+    pub fn line_index(&self, pos: u32) -> usize {
+        match self.line_starts.binary_search(&pos) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    /// returns the byte offset at which the 0-based line `line` starts
+    pub fn line_start(&self, line: usize) -> Option<u32> {
+        self.line_starts.get(line).copied()
+    }
+
+    /// returns the text of the 0-based line `line`, without the trailing newline
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line)? as usize;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map(|end| *end as usize - 1)
+            .unwrap_or(self.source.len());
+        let text = self.source.get(start..end)?;
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// the expansion that `pos` is in, if it's past the end of the source
+    pub fn expansion(&self, pos: u32) -> Option<&Expansion> {
+        let idx = self
+            .expansions
+            .partition_point(|expansion| expansion.range.end <= pos);
+        self.expansions
+            .get(idx)
+            .filter(|expansion| expansion.range.contains(&pos))
+    }
+
+    /// converts a byte offset into a 1-based (line, column) pair
+    pub fn line_col(&self, pos: u32) -> (u32, u32) {
+        let pos = pos.min(self.source.len() as u32);
+        let line = self.line_index(pos);
+        let start = self.line_starts[line] as usize;
+        let column = self
+            .source
+            .get(start..pos as usize)
+            .map(|prefix| prefix.chars().count())
+            .unwrap_or(pos as usize - start);
+        (line as u32 + 1, column as u32 + 1)
+    }
+}
+
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub const fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn with<F, R>(f: F) -> R
+    where
+        F: FnOnce(RwLockReadGuard<'_, SourceMap>) -> R,
+    {
+        f(SOURCE_MAP.read().unwrap())
+    }
+
+    pub fn with_mut<F, R>(f: F) -> R
+    where
+        F: FnOnce(RwLockWriteGuard<'_, SourceMap>) -> R,
+    {
+        f(SOURCE_MAP.write().unwrap())
+    }
+
+    /// registers a new file and returns the id it was given
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<Arc<str>>) -> FileId {
+        let id = self.files.len() + 1;
+        assert!(
+            id <= u16::MAX as usize,
+            "cannot register more than {} files",
+            u16::MAX
+        );
+        self.files.push(SourceFile::new(name.into(), source.into()));
+        FileId(id as u16)
+    }
+
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> {
+        if file.is_dummy() {
+            None
+        } else {
+            self.files.get(file.0 as usize - 1)
+        }
+    }
+
+    /// registers code the preprocessor made in `file`
+    /// expansions have to be added in the order they're placed in
+    pub fn add_expansion(&mut self, file: FileId, expansion: Expansion) {
+        if file.is_dummy() {
+            return;
+        }
+        let file = &mut self.files[file.0 as usize - 1];
+        debug_assert!(file
+            .expansions
+            .last()
+            .is_none_or(|last| last.range.end <= expansion.range.start));
+        file.expansions.push(expansion);
+    }
+
+    /// traces a span in an expansion back to the source it was copied from
+    ///
+    /// returns the span in the real source, along with the call site of every expansion
+    /// it went through, innermost first. spans that aren't in an expansion are returned as is
+    pub fn resolve(&self, span: Span) -> (Span, Vec<(Span, String)>) {
+        let mut span = span;
+        let mut frames = Vec::new();
+        while let Some(file) = self.get(span.file)
+            && span.start_pos as usize >= file.source.len()
+        {
+            let Some(expansion) = file.expansion(span.start_pos) else {
+                // like the end of the file, after everything the preprocessor made
+                let end = file.source.len() as u32;
+                return (Span::new(span.file, end, 0), frames);
+            };
+            let call_site = self.resolve(expansion.call_site).0;
+
+            // map both ends through the tokens they're in, and keep the
+            // whole range if they still belong together
+            let origin = |pos: u32| {
+                let idx = expansion
+                    .origins
+                    .partition_point(|(start, _)| *start <= pos);
+                idx.checked_sub(1).map(|idx| expansion.origins[idx].1)
+            };
+            let Some(start) = origin(span.start_pos) else {
+                frames.push((call_site, expansion.description.clone()));
+                break;
+            };
+            let end = origin(span.end_pos().saturating_sub(1).max(span.start_pos))
+                .filter(|end| end.file == start.file && end.end_pos() >= start.start_pos);
+            span = match end {
+                Some(end) => {
+                    Span::new(start.file, start.start_pos, end.end_pos() - start.start_pos)
+                }
+                None => start,
+            };
+            // an expansion that only stands in for its call site, like the name of
+            // a template's instance, has nothing to add
+            if span != call_site {
+                frames.push((call_site, expansion.description.clone()));
+            }
+        }
+        (span, frames)
+    }
+
+    /// resolves the start of `span` into a `Loc`
+    /// returns `None` if the span is a dummy, or belongs to an unknown file
+    pub fn lookup(&self, span: Span) -> Option<Loc> {
+        let file = self.get(span.file)?;
+        let (line, column) = file.line_col(span.start_pos);
+        Some(Loc {
+            file: span.file,
+            line,
+            column,
+        })
+    }
+
+    /// formats the start of `span` as `file:line:col`
+    pub fn display(&self, span: Span) -> Option<String> {
+        let loc = self.lookup(span)?;
+        let name = self.get(loc.file)?.name();
+        Some(format!("{name}:{}:{}", loc.line, loc.column))
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_source_map_line_col() {
+    let mut map = SourceMap::new();
+    let file = map.add_file("test.blp", "pack core;\n\n  let x => 0;\n");
+    let span = Span::new(file, 14, 3);
+
+    assert_eq!(
+        map.lookup(span),
+        Some(Loc {
+            file,
+            line: 3,
+            column: 3
+        })
+    );
+    assert_eq!(map.get(file).unwrap().line(2), Some("  let x => 0;"));
+    assert_eq!(map.display(span).as_deref(), Some("test.blp:3:3"));
+    assert_eq!(map.lookup(Span::DUMMY), None);
+}
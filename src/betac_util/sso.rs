@@ -209,7 +209,7 @@ impl<'a> Source for Sso<'a> {
         self.len()
     }
 
-    unsafe fn reconstruct_from_start_end_unchecked(&self, start: u16, end: u16) -> &str {
+    unsafe fn reconstruct_from_start_end_unchecked(&self, start: u32, end: u32) -> &str {
         std::str::from_utf8_unchecked(self.as_bytes().get_unchecked(start as usize..end as usize))
     }
 }
@@ -0,0 +1,149 @@
+use std::{
+    fmt,
+    io::Write,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// the parts of the compiler that can emit traces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Lexer,
+    Parser,
+    Preproc,
+    Driver,
+}
+
+impl Target {
+    pub const ALL: [Target; 4] = [Self::Lexer, Self::Parser, Self::Preproc, Self::Driver];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Lexer => "lexer",
+            Self::Parser => "parser",
+            Self::Preproc => "preproc",
+            Self::Driver => "driver",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|target| target.name() == name)
+    }
+}
+
+/// how much detail a trace has
+/// enabling a level also enables every level below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum TraceLevel {
+    Info = 1,
+    Debug = 2,
+    Trace = 3,
+}
+
+impl TraceLevel {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            "trace" => Some(Self::Trace),
+            _ => None,
+        }
+    }
+}
+
+/// the enabled level for each target, indexed by `Target as usize`
+/// `0` means the target is off
+///
+/// these are atomics instead of `Session` flags, since the lexer checks them on every token
+static LEVELS: [AtomicU8; 4] = [const { AtomicU8::new(0) }; 4];
+
+pub fn enable(target: Target, level: TraceLevel) {
+    LEVELS[target as usize].store(level as u8, Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub fn enabled(target: Target, level: TraceLevel) -> bool {
+    LEVELS[target as usize].load(Ordering::Relaxed) >= level as u8
+}
+
+/// parses a `-Ztrace=` spec, like `lexer,parser=trace`
+/// targets without a level are enabled at `debug`, and `all` stands for every target
+///
+/// returns the list of targets to enable, or the part of the spec that isn't valid
+pub fn parse_spec(spec: &str) -> Result<Vec<(Target, TraceLevel)>, String> {
+    let mut enabled = Vec::new();
+    for part in spec.split(',').filter(|part| !part.is_empty()) {
+        let (name, level) = match part.split_once('=') {
+            Some((name, level)) => match TraceLevel::from_name(level) {
+                Some(level) => (name, level),
+                None => return Err(part.to_string()),
+            },
+            None => (part, TraceLevel::Debug),
+        };
+
+        if name == "all" {
+            enabled.extend(Target::ALL.map(|target| (target, level)));
+        } else {
+            let target = Target::from_name(name).ok_or_else(|| part.to_string())?;
+            enabled.push((target, level));
+        }
+    }
+    Ok(enabled)
+}
+
+/// writes a trace line to stderr
+/// this should only be called through the `trace!` macro, which checks `enabled` first
+#[cold]
+pub fn write(target: Target, level: TraceLevel, args: fmt::Arguments<'_>) {
+    let _ = writeln!(
+        std::io::stderr().lock(),
+        "[{} {}] {args}",
+        target.name(),
+        level.name()
+    );
+}
+
+/// writes a trace line to stderr, if `target` is enabled at `level`
+///
+/// ```ignore
+/// trace!(Lexer, Trace, "found `=>` at: {start}");
+/// ```
+macro_rules! trace_impl {
+    ($target:ident, $level:ident, $($arg:tt)+) => {
+        if $crate::betac_util::trace::enabled(
+            $crate::betac_util::trace::Target::$target,
+            $crate::betac_util::trace::TraceLevel::$level,
+        ) {
+            $crate::betac_util::trace::write(
+                $crate::betac_util::trace::Target::$target,
+                $crate::betac_util::trace::TraceLevel::$level,
+                format_args!($($arg)+),
+            );
+        }
+    };
+}
+
+pub(crate) use trace_impl as trace;
+
+#[test]
+#[cfg(test)]
+fn test_parse_trace_spec() {
+    assert_eq!(
+        parse_spec("lexer,parser=trace"),
+        Ok(vec![
+            (Target::Lexer, TraceLevel::Debug),
+            (Target::Parser, TraceLevel::Trace)
+        ])
+    );
+    assert_eq!(parse_spec("all=info").map(|spec| spec.len()), Ok(4));
+    assert_eq!(parse_spec("lexer=loud"), Err("lexer=loud".to_string()));
+    assert_eq!(parse_spec("typeck"), Err("typeck".to_string()));
+}
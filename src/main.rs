@@ -10,8 +10,10 @@ use betac_runner::{parse_command_line_args, Response};
 use std::io;
 
 mod betac_ast;
+mod betac_cst;
 mod betac_errors;
 mod betac_parser;
+mod betac_preproc;
 mod betac_runner;
 mod betac_tokenizer;
 mod betac_util;
@@ -25,17 +27,25 @@ mod betac_util;
 ///
 ///     when we stablize async in this project, switch all these types to async types
 mod driver {
+    use crate::betac_errors::{codes, ErrorFormat, EMITTER};
     use crate::betac_parser::{traits::Parse, GlobalParser};
-    use crate::{betac_errors::EMITTER, betac_tokenizer};
-    use std::io;
+    use crate::betac_preproc::{self, Expanded};
+    use crate::betac_runner::Session;
+    use crate::betac_tokenizer;
+    use crate::betac_util::{
+        source_map::{FileId, SourceMap},
+        trace::trace,
+    };
+    use std::io::{self, IsTerminal};
     use std::time::Instant;
 
     /// emits all the errors that have been collected throughout the process.
     ///
-    /// currently, it's basically a NOOP, but it will eventually do something
+    /// errors are rendered with their source snippets, and colored if stderr is a terminal
     ///
     /// ARGS: \n
-    ///     takes a mutable reference to a writer
+    ///     takes a mutable reference to a writer, which should be stderr, so the errors
+    ///     aren't mixed in with what the command prints, like its JSON
     ///
     ///     TODO: change this to AsyncWrite once we stablize the type
     ///
@@ -47,7 +57,12 @@ mod driver {
     where
         W: io::Write,
     {
-        EMITTER.lock().unwrap().flush(w)?;
+        let mut emitter = EMITTER.lock().unwrap();
+        emitter.set_color(io::stderr().is_terminal());
+        if Session::has_json_error_format_set() {
+            emitter.set_format(ErrorFormat::Json);
+        }
+        emitter.flush(w)?;
         Ok(())
     }
 
@@ -62,11 +77,59 @@ mod driver {
         writeln!(writer, "compile FILE [OPTIONS]: compiles FILE with OPTIONS")?;
         writeln!(
             writer,
-            "build: builds all files in current directory and links them"
+            "    --error-format=human|json: how errors are printed (default: human)"
+        )?;
+        writeln!(
+            writer,
+            "    --cfg KEY=VALUE: sets KEY for `@eval` conditions, like `--cfg OS=LINUX`"
+        )?;
+        writeln!(
+            writer,
+            "    -I DIR: looks for the headers of `@external` in DIR too"
+        )?;
+        writeln!(
+            writer,
+            "    -dbg, --debug: print the compiler's traces to stderr, at least at INFO"
+        )?;
+        writeln!(
+            writer,
+            "    -Ztrace=TARGET[=LEVEL],...: only trace TARGET (lexer, parser, preproc, driver, all)"
+        )?;
+        writeln!(
+            writer,
+            "expand FILE [OPTIONS]: prints FILE after the preprocessor has run over it"
+        )?;
+        writeln!(
+            writer,
+            "    --markers: marks where lines came from, with `// from FILE:LINE`"
+        )?;
+        writeln!(
+            writer,
+            "build FILE [OPTIONS]: builds FILE into a program, which needs an entry point"
+        )?;
+        writeln!(
+            writer,
+            "explain CODE: explains the error with CODE, like B0001"
         )?;
         Ok(())
     }
 
+    /// prints the long-form explanation for an error code
+    /// unknown codes are reported as an `InvalidInput` error
+    pub(super) fn explain<W>(writer: &mut W, code: &str) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let Some(entry) = codes::lookup(code) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{code} is not a known error code"),
+            ));
+        };
+        writeln!(writer, "{}: {}\n", entry.code, entry.title)?;
+        write!(writer, "{}", entry.explanation)
+    }
+
     /// prints the current version
     /// right now, it will always write the string: "version: 0.0.1"
     pub(super) fn print_current_version<W>(writer: &mut W) -> io::Result<()>
@@ -83,29 +146,81 @@ mod driver {
         W: io::Write,
     {
         let start_time = Instant::now();
-        let input = std::fs::read_to_string(&file_name)?;
-        assert!(
-            input.len() <= u16::MAX as usize,
-            "File length must be less than {} bytes",
-            u16::MAX
-        );
-
-        let iter = betac_tokenizer::run_tokenizer(&*input);
+        let (file, expanded) = preprocess_file(file_name)?;
 
-        let mut parser = GlobalParser::new(input.clone(), iter);
+        let parse_time = Instant::now();
+        let mut parser = GlobalParser::new(file, expanded.text, expanded.tokens.into_iter());
 
         while parser.next_expression() {}
+        parser.check_start();
+        trace!(
+            Driver,
+            Info,
+            "parsed in {}us",
+            parse_time.elapsed().as_micros()
+        );
 
         let now = start_time.elapsed();
         writeln!(w, "process finished in {}us", now.as_micros())?;
         Ok(())
     }
 
-    pub(super) fn build<W>(writer: &mut W) -> io::Result<()>
+    /// prints a file the way the parser sees it, once the preprocessor is done with it
+    pub(super) fn expand<W>(w: &mut W, file_name: String) -> io::Result<()>
     where
         W: io::Write,
     {
-        Ok(())
+        let (file, expanded) = preprocess_file(file_name)?;
+        write!(
+            w,
+            "{}",
+            expanded.render(file, Session::has_expand_markers_set())
+        )
+    }
+
+    /// reads, registers, tokenizes and preprocesses a file
+    fn preprocess_file(file_name: String) -> io::Result<(FileId, Expanded)> {
+        let bytes = std::fs::read(&file_name)?;
+        trace!(Driver, Info, "read `{file_name}`, {} bytes", bytes.len());
+        let (input, malformed) = betac_tokenizer::decode_source(&bytes);
+        assert!(
+            input.len() <= u32::MAX as usize,
+            "File length must be less than {} bytes",
+            u32::MAX
+        );
+
+        let file = SourceMap::with_mut(|mut map| map.add_file(file_name, &*input));
+        betac_tokenizer::report_malformed_utf8(file, &malformed);
+
+        let lex_time = Instant::now();
+        let tokens = betac_tokenizer::run_tokenizer(&*input, file).collect::<Vec<_>>();
+        trace!(
+            Driver,
+            Info,
+            "lexed {} tokens in {}us",
+            tokens.len(),
+            lex_time.elapsed().as_micros()
+        );
+
+        let preproc_time = Instant::now();
+        let expanded = betac_preproc::preprocess(&input, &tokens, file, &Session::cfg());
+        trace!(
+            Driver,
+            Info,
+            "preprocessed into {} tokens in {}us",
+            expanded.tokens.len(),
+            preproc_time.elapsed().as_micros()
+        );
+        Ok((file, expanded))
+    }
+
+    /// builds a program, which is only checked for now, like with `compile`,
+    /// except that it needs an entry point
+    pub(super) fn build<W>(writer: &mut W, file_name: String) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        run(writer, file_name)
     }
 
     const CURRENT_VERSION: &str = "0.0.1";
@@ -122,9 +237,11 @@ fn main() -> io::Result<()> {
         Response::Help => driver::print_help_list(&mut writer)?,
         Response::Version => driver::print_current_version(&mut writer)?,
         Response::Run { file_name } => driver::run(&mut writer, file_name)?,
-        Response::Build => driver::build(&mut writer)?,
+        Response::Expand { file_name } => driver::expand(&mut writer, file_name)?,
+        Response::Build { file_name } => driver::build(&mut writer, file_name)?,
+        Response::Explain { code } => driver::explain(&mut writer, &code)?,
     }
 
-    driver::cleanup(&mut writer)?;
+    driver::cleanup(&mut io::stderr().lock())?;
     Ok(())
 }